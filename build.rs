@@ -1,5 +1,9 @@
 use std::io::Result;
 fn main() -> Result<()> {
-    prost_build::compile_protos(&["onnx.proto3"], &["onnx/onnx/"])?;
+    // Initializer data decoded from a memory-mapped model refers to the
+    // mapping instead of being copied
+    prost_build::Config::new()
+        .bytes([".onnx.TensorProto.raw_data"])
+        .compile_protos(&["onnx.proto3"], &["onnx/onnx/"])?;
     Ok(())
 }
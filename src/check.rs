@@ -0,0 +1,131 @@
+use std::{fmt, io};
+
+use console::Style;
+use serde::Serialize;
+
+use crate::summary::Summary;
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Part of the model a finding is about
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Location {
+    Model,
+    Node {
+        index: usize,
+        name: String,
+        op_type: String,
+    },
+    Tensor {
+        name: String,
+    },
+    Opset {
+        domain: String,
+    },
+    /// File of a sharded checkpoint
+    Shard {
+        file: String,
+    },
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Model => write!(f, "model"),
+            Location::Node {
+                index,
+                name,
+                op_type,
+            } if name.is_empty() => write!(f, "node #{} ({})", index, op_type),
+            Location::Node {
+                index,
+                name,
+                op_type,
+            } => write!(f, "node #{} {} ({})", index, name, op_type),
+            Location::Tensor { name } => write!(f, "tensor {}", name),
+            Location::Opset { domain } => write!(f, "opset {}", domain),
+            Location::Shard { file } => write!(f, "shard {}", file),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => Style::new().red().bold().apply_to("error"),
+            Severity::Warning => Style::new().yellow().bold().apply_to("warning"),
+        };
+
+        write!(f, "{}: {}: {}", severity, self.location, self.message)
+    }
+}
+
+/// Findings of validating a model, errors first
+#[derive(Serialize)]
+pub struct CheckReport<'a> {
+    pub filename: Option<&'a str>,
+    pub errors: usize,
+    pub warnings: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl<'a> CheckReport<'a> {
+    pub fn new(filename: Option<&'a str>, mut findings: Vec<Finding>) -> Self {
+        findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+
+        let errors = findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .count();
+
+        CheckReport {
+            filename,
+            errors,
+            warnings: findings.len() - errors,
+            findings,
+        }
+    }
+}
+
+impl<'a> Summary for CheckReport<'a> {
+    fn dump_json(&self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, &self)?)
+    }
+}
+
+impl<'a> fmt::Display for CheckReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        writeln!(
+            f,
+            "{} {}",
+            bold.apply_to("Check:"),
+            self.filename.unwrap_or("<NO FILENAME>")
+        )?;
+        writeln!(f)?;
+
+        for finding in self.findings.iter() {
+            writeln!(f, "{}", finding)?;
+        }
+
+        if !self.findings.is_empty() {
+            writeln!(f)?;
+        }
+
+        writeln!(f, "{} errors, {} warnings", self.errors, self.warnings)
+    }
+}
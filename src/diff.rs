@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::{fmt, io};
+
+use anyhow::bail;
+use console::Style;
+use serde::Serialize;
+
+use crate::model::Model;
+use crate::summary::Summary;
+
+/// Comparable properties of a model keyed by name, e.g. graph inputs and
+/// their types
+pub struct DiffSection {
+    pub name: &'static str,
+    pub entries: BTreeMap<String, String>,
+}
+
+impl DiffSection {
+    pub fn new<K, V>(name: &'static str, entries: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        DiffSection {
+            name,
+            entries: entries
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize)]
+pub struct Change {
+    pub section: &'static str,
+    pub name: String,
+    pub kind: ChangeKind,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ModelDiff<'a> {
+    pub before: &'a str,
+    pub after: &'a str,
+    pub changes: Vec<Change>,
+}
+
+/// Structural differences between two models of the same format
+pub fn diff_models<'a>(
+    before: &dyn Model,
+    before_name: &'a str,
+    after: &dyn Model,
+    after_name: &'a str,
+) -> anyhow::Result<ModelDiff<'a>> {
+    if before.format_name() != after.format_name() {
+        bail!(
+            "Cannot diff models of different formats ({} and {})",
+            before.format_name(),
+            after.format_name()
+        );
+    }
+
+    let mut after_sections = after.diff_sections();
+    let mut changes = Vec::new();
+
+    for section in before.diff_sections() {
+        let after_entries = after_sections
+            .iter_mut()
+            .find(|s| s.name == section.name)
+            .map(|s| std::mem::take(&mut s.entries))
+            .unwrap_or_default();
+
+        diff_entries(section.name, section.entries, after_entries, &mut changes);
+    }
+
+    Ok(ModelDiff {
+        before: before_name,
+        after: after_name,
+        changes,
+    })
+}
+
+fn diff_entries(
+    section: &'static str,
+    before: BTreeMap<String, String>,
+    mut after: BTreeMap<String, String>,
+    changes: &mut Vec<Change>,
+) {
+    let mut section_changes = Vec::new();
+
+    for (name, value) in before {
+        match after.remove(&name) {
+            None => section_changes.push(Change {
+                section,
+                name,
+                kind: ChangeKind::Removed,
+                before: Some(value),
+                after: None,
+            }),
+            Some(after_value) if after_value != value => section_changes.push(Change {
+                section,
+                name,
+                kind: ChangeKind::Changed,
+                before: Some(value),
+                after: Some(after_value),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for (name, value) in after {
+        section_changes.push(Change {
+            section,
+            name,
+            kind: ChangeKind::Added,
+            before: None,
+            after: Some(value),
+        });
+    }
+
+    section_changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes.extend(section_changes);
+}
+
+impl<'a> Summary for ModelDiff<'a> {
+    fn dump_json(&self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, &self)?)
+    }
+}
+
+impl<'a> fmt::Display for ModelDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+        let removed = Style::new().red();
+        let added = Style::new().green();
+        let changed = Style::new().yellow();
+
+        writeln!(
+            f,
+            "{} {} -> {}",
+            bold.apply_to("Diff:"),
+            self.before,
+            self.after
+        )?;
+
+        if self.changes.is_empty() {
+            writeln!(f)?;
+            return writeln!(f, "No differences");
+        }
+
+        let mut section = None;
+
+        for change in self.changes.iter() {
+            if section != Some(change.section) {
+                section = Some(change.section);
+                writeln!(f)?;
+                writeln!(f, "{}:", change.section)?;
+            }
+
+            let before = change.before.as_deref().unwrap_or_default();
+            let after = change.after.as_deref().unwrap_or_default();
+
+            match change.kind {
+                ChangeKind::Removed => writeln!(
+                    f,
+                    "  {}",
+                    removed.apply_to(format!("- {}: {}", change.name, before))
+                )?,
+                ChangeKind::Added => writeln!(
+                    f,
+                    "  {}",
+                    added.apply_to(format!("+ {}: {}", change.name, after))
+                )?,
+                ChangeKind::Changed => writeln!(
+                    f,
+                    "  {}",
+                    changed.apply_to(format!("~ {}: {} -> {}", change.name, before, after))
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
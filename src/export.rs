@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::model::Model;
+use crate::tensor::{ElementType, TensorData};
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+/// Total header length of .npy files is padded to a multiple of this
+const NPY_ALIGNMENT: usize = 64;
+
+/// Numpy type of an element type, `None` if numpy has no equivalent
+fn npy_descr(element_type: ElementType) -> Option<&'static str> {
+    let descr = match element_type {
+        ElementType::F64 => "<f8",
+        ElementType::F32 => "<f4",
+        ElementType::F16 => "<f2",
+        ElementType::BF16
+        | ElementType::F8E4M3FN
+        | ElementType::F8E4M3FNUZ
+        | ElementType::F8E5M2
+        | ElementType::F8E5M2FNUZ => return None,
+        ElementType::I64 => "<i8",
+        ElementType::I32 => "<i4",
+        ElementType::I16 => "<i2",
+        ElementType::I8 => "|i1",
+        ElementType::U64 => "<u8",
+        ElementType::U32 => "<u4",
+        ElementType::U16 => "<u2",
+        ElementType::U8 => "|u1",
+        ElementType::Bool => "|b1",
+        ElementType::C64 => "<c8",
+        ElementType::C128 => "<c16",
+    };
+
+    Some(descr)
+}
+
+/// Header of a .npy file, including magic and length
+fn npy_header(tensor: &TensorData, element_type: ElementType) -> Vec<u8> {
+    let descr = npy_descr(element_type).unwrap_or("<f4");
+
+    let shape = match tensor.shape.as_slice() {
+        [dim] => format!("({},)", dim),
+        dims => {
+            let dims: Vec<String> = dims.iter().map(|d| d.to_string()).collect();
+            format!("({})", dims.join(", "))
+        }
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    let unpadded = NPY_MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(NPY_ALIGNMENT) - unpadded));
+    header.push('\n');
+
+    let mut bytes = NPY_MAGIC.to_vec();
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    bytes
+}
+
+/// Bytes of element data in a .npy file, after any widening
+fn npy_data_len(tensor: &TensorData, element_type: ElementType) -> u64 {
+    match npy_descr(element_type) {
+        Some(_) => tensor.data.len() as u64,
+        None => (tensor.data.len() / element_type.size()) as u64 * 4,
+    }
+}
+
+/// Write a tensor in numpy's .npy format.
+///
+/// Data is little-endian in every supported model format and the header
+/// says so explicitly, so files are the same on any host. Types numpy lacks,
+/// bfloat16 and the float8 variants, are widened to float32.
+fn write_npy(writer: &mut dyn Write, tensor: &TensorData) -> anyhow::Result<()> {
+    let Some(element_type) = tensor.element_type else {
+        bail!(
+            "{} is {}, which cannot be written as .npy",
+            tensor.name,
+            tensor.dtype
+        );
+    };
+
+    writer.write_all(&npy_header(tensor, element_type))?;
+
+    if npy_descr(element_type).is_none() {
+        for value in element_type.values(&tensor.data) {
+            writer.write_all(&(value as f32).to_le_bytes())?;
+        }
+    } else {
+        writer.write_all(&tensor.data)?;
+    }
+
+    Ok(())
+}
+
+fn write_tensor(writer: &mut dyn Write, tensor: &TensorData, raw: bool) -> anyhow::Result<()> {
+    if raw {
+        writer.write_all(&tensor.data)?;
+        Ok(())
+    } else {
+        write_npy(writer, tensor)
+    }
+}
+
+/// File name for a tensor, with characters that are not safe in paths
+/// replaced
+fn tensor_file_name(name: &str, raw: bool) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+
+    format!("{}.{}", stem, if raw { "bin" } else { "npy" })
+}
+
+fn write_file(path: &Path, tensor: &TensorData, raw: bool) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    write_tensor(&mut writer, tensor, raw)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_npz<W: Write + Seek>(
+    model: &dyn Model,
+    names: &[&str],
+    writer: W,
+    export: &mut Export,
+) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new(writer);
+
+    for name in names.iter() {
+        let tensor = model.tensor_data(name)?;
+        let Some(element_type) = tensor.element_type else {
+            export.skip(&tensor);
+            continue;
+        };
+        let size =
+            npy_header(&tensor, element_type).len() as u64 + npy_data_len(&tensor, element_type);
+        // np.load strips the extension to get the array name back
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .large_file(size >= u32::MAX as u64);
+
+        zip.start_file(format!("{}.npy", name), options)?;
+        write_npy(&mut zip, &tensor)?;
+        export.written += 1;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Outcome of exporting tensors
+#[derive(Default)]
+pub struct Export {
+    pub written: usize,
+    /// Names and data types of tensors that have no numeric data to write,
+    /// such as strings
+    pub skipped: Vec<(String, String)>,
+}
+
+impl Export {
+    fn skip(&mut self, tensor: &TensorData) {
+        self.skipped
+            .push((tensor.name.to_string(), tensor.dtype.clone()));
+    }
+}
+
+/// Write tensors of a model to `output`.
+///
+/// An `output` ending in `.npz` becomes a numpy archive. A single tensor is
+/// written to `output` itself unless it is a directory, otherwise each
+/// tensor gets a file in the `output` directory. All tensors are written
+/// when `names` is empty. `raw` writes the bare little-endian data instead
+/// of .npy files. When writing several tensors, those without numeric data
+/// are skipped rather than failing the export.
+pub fn export_tensors(
+    model: &dyn Model,
+    names: &[String],
+    output: &Path,
+    raw: bool,
+) -> anyhow::Result<Export> {
+    let mut export = Export::default();
+    let single = names.len() == 1;
+    let names: Vec<&str> = if names.is_empty() {
+        model.tensor_names()
+    } else {
+        names.iter().map(|n| n.as_str()).collect()
+    };
+
+    if output.extension().is_some_and(|ext| ext == "npz") {
+        if raw {
+            bail!("Raw data cannot be written to a .npz archive");
+        }
+
+        let file =
+            File::create(output).with_context(|| format!("Cannot create {}", output.display()))?;
+        write_npz(model, &names, BufWriter::new(file), &mut export)?;
+
+        return Ok(export);
+    }
+
+    if single && !output.is_dir() {
+        write_file(output, &model.tensor_data(names[0])?, raw)?;
+        export.written = 1;
+        return Ok(export);
+    }
+
+    fs::create_dir_all(output)
+        .with_context(|| format!("Cannot create directory {}", output.display()))?;
+
+    let mut file_names = HashSet::new();
+
+    for name in names.iter() {
+        let file_name = tensor_file_name(name, raw);
+
+        if !file_names.insert(file_name.clone()) {
+            bail!(
+                "{} would overwrite {}, which another tensor was written to",
+                name,
+                file_name
+            );
+        }
+
+        let tensor = model.tensor_data(name)?;
+        if tensor.element_type.is_none() {
+            export.skip(&tensor);
+            continue;
+        }
+
+        write_file(&output.join(file_name), &tensor, raw)?;
+        export.written += 1;
+    }
+
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::Cursor;
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::onnx::fixtures::{load, tensor};
+    use crate::onnx::onnx_proto::tensor_proto::DataType;
+    use crate::onnx::onnx_proto::{GraphProto, TensorProto};
+
+    #[test]
+    fn widened_size_counts_float32_elements() {
+        let tensor = TensorData {
+            name: "t",
+            dtype: "bfloat16".to_string(),
+            element_type: Some(ElementType::BF16),
+            shape: vec![3],
+            data: Cow::Owned(vec![0; 6]),
+        };
+
+        assert_eq!(npy_data_len(&tensor, ElementType::BF16), 12);
+        assert_eq!(
+            npy_header(&tensor, ElementType::BF16).len() % NPY_ALIGNMENT,
+            0
+        );
+    }
+
+    #[test]
+    fn npz_skips_strings_and_keeps_complex() {
+        let strings = TensorProto {
+            string_data: vec![b"a".to_vec()],
+            ..tensor("s", DataType::String, &[1])
+        };
+        let complex = TensorProto {
+            float_data: vec![1.0, -1.0],
+            ..tensor("c", DataType::Complex64, &[1])
+        };
+        let floats = TensorProto {
+            float_data: vec![2.0],
+            ..tensor("f", DataType::Float, &[1])
+        };
+        let model = load(GraphProto {
+            initializer: vec![strings, complex, floats],
+            ..Default::default()
+        });
+
+        let mut export = Export::default();
+        let mut archive = Cursor::new(Vec::new());
+        write_npz(&model, &["s", "c", "f"], &mut archive, &mut export).unwrap();
+
+        assert_eq!(export.written, 2);
+        assert_eq!(
+            export.skipped,
+            vec![("s".to_string(), "string".to_string())]
+        );
+
+        let mut archive = ZipArchive::new(archive).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names.len(), 2);
+
+        let mut npy = Vec::new();
+        std::io::copy(&mut archive.by_name("c.npy").unwrap(), &mut npy).unwrap();
+        let (header, data) = npy.split_at(npy.len() - 8);
+        assert!(String::from_utf8_lossy(header).contains("'descr': '<c8'"));
+        assert_eq!(header.len() % NPY_ALIGNMENT, 0);
+        assert_eq!(
+            data,
+            [1.0f32.to_le_bytes(), (-1.0f32).to_le_bytes()].concat()
+        );
+    }
+}
@@ -1,16 +1,31 @@
 use std::io::stdout;
 use std::path::{Path, PathBuf};
-use std::{fmt, fs};
+use std::{fmt, fs, process};
 
+use crate::check::CheckReport;
+use crate::diff::diff_models;
+use crate::export::export_tensors;
 use crate::model::Model;
+use crate::onnx::query::{Query, QueryTarget};
 use crate::onnx::OnnxModel;
 use crate::safetensors::Safetensors;
-use clap::{Parser, ValueEnum};
+use crate::stats::{StatsFilter, StatsSort, StatsSummary};
+use crate::summary::{Summary, SummaryOptions};
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use clap::{Parser, Subcommand, ValueEnum};
+use console::Style;
+use prost::Message;
 
+mod check;
+mod diff;
+mod export;
 mod model;
 mod onnx;
 mod safetensors;
+mod stats;
 mod summary;
+mod tensor;
 
 #[derive(Debug, Copy, Clone, ValueEnum)]
 enum OutputFormat {
@@ -18,6 +33,8 @@ enum OutputFormat {
     Text,
     /// Json summary of model
     Json,
+    /// Graphviz DOT graph of the model
+    Dot,
 }
 
 impl fmt::Display for OutputFormat {
@@ -25,46 +42,422 @@ impl fmt::Display for OutputFormat {
         match self {
             OutputFormat::Text => write!(f, "text"),
             OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Dot => write!(f, "dot"),
         }
     }
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Model file to load
-    model_file: PathBuf,
+    #[arg(required = true)]
+    model_file: Option<PathBuf>,
     #[arg(short, long, default_value_t=OutputFormat::Text)]
     output: OutputFormat,
+    /// Bind a symbolic dimension to a size, e.g. `--dim batch=1`
+    #[arg(long = "dim", value_name = "NAME=SIZE", value_parser = parse_dim)]
+    dims: Vec<(String, u64)>,
+    /// Replace calls to model-local onnx functions with their bodies
+    #[arg(long)]
+    inline_functions: bool,
+    /// Number of entries shown in largest/top listings
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+    /// Levels of the module tree shown in the text summary, from onnx name
+    /// scopes or dotted safetensors tensor names
+    #[arg(long, default_value_t = 2)]
+    module_depth: usize,
+    /// Draw the modules at this depth as single nodes in dot output
+    #[arg(long, value_name = "DEPTH")]
+    collapse_depth: Option<usize>,
 }
 
-fn load_any_model(path: &Path) -> anyhow::Result<Box<dyn Model>> {
-    let model_bytes = fs::read(path)?;
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare the structure of two models of the same format
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+        #[arg(short, long, default_value_t=OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Write the part of an onnx model computing `--to` from `--from` as a
+    /// new model
+    Extract {
+        model_file: PathBuf,
+        output_file: PathBuf,
+        /// Tensors that become inputs of the extracted model
+        #[arg(long, value_delimiter = ',', required = true)]
+        from: Vec<String>,
+        /// Tensors that become outputs of the extracted model
+        #[arg(long, value_delimiter = ',', required = true)]
+        to: Vec<String>,
+    },
+    /// Show the attributes, tensors and neighbours of an onnx node
+    Node {
+        model_file: PathBuf,
+        /// Node name, or index in the graph when no node has that name
+        node: String,
+        #[arg(short, long, default_value_t=OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Find onnx nodes or tensors matching a filter expression, e.g.
+    /// `op == Conv and attr.group > 1`
+    Query {
+        model_file: PathBuf,
+        expr: String,
+        /// Match tensors instead of nodes
+        #[arg(long)]
+        tensors: bool,
+        #[arg(short, long, default_value_t=OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Validate the model structure, exits with an error status on errors
+    Check {
+        model_file: PathBuf,
+        #[arg(short, long, default_value_t=OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Value statistics of the model's tensors, e.g. to spot NaNs or
+    /// exploding weights. Exits with an error status when the data of a
+    /// tensor cannot be read
+    Stats {
+        model_file: PathBuf,
+        /// Order of the listed tensors, names ascending and values descending
+        #[arg(long, value_enum, default_value_t = StatsSort::Name)]
+        sort: StatsSort,
+        /// Only tensors whose largest absolute value is above this
+        #[arg(long, value_name = "VALUE")]
+        absmax_above: Option<f64>,
+        /// Only tensors whose standard deviation is below this
+        #[arg(long, value_name = "VALUE")]
+        std_below: Option<f64>,
+        /// Only tensors with a larger fraction of exact zeros, e.g. `0.5`
+        #[arg(long, value_name = "FRACTION")]
+        zeros_above: Option<f64>,
+        /// Only tensors holding NaN or infinite values
+        #[arg(long)]
+        non_finite: bool,
+        /// Maximum number of tensors listed
+        #[arg(long)]
+        limit: Option<usize>,
+        #[arg(short, long, default_value_t=OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Work with the tensors stored in a model
+    Tensor {
+        #[command(subcommand)]
+        command: TensorCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TensorCommand {
+    /// Write tensors as .npy files, a .npz archive or raw little-endian data
+    Export {
+        model_file: PathBuf,
+        /// Tensors to write, all tensors when none are given
+        names: Vec<String>,
+        /// File for a single tensor, a `.npz` archive, or a directory
+        /// getting one file per tensor
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Write the bare data instead of .npy files
+        #[arg(long)]
+        raw: bool,
+    },
+}
+
+fn parse_dim(s: &str) -> Result<(String, u64), String> {
+    let (name, size) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=SIZE, got {:?}", s))?;
+    let size = size
+        .parse()
+        .map_err(|err| format!("invalid size {:?}: {}", size, err))?;
+
+    Ok((name.to_string(), size))
+}
+
+/// Apply load options and resolve external data of a decoded onnx model
+fn prepare_onnx_model(
+    path: &Path,
+    mut onnx_model: OnnxModel,
+    inline_functions: bool,
+) -> anyhow::Result<OnnxModel> {
+    if inline_functions {
+        onnx_model = onnx_model.inline_functions();
+    }
+
+    let model_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    onnx_model.resolve_external_data(model_dir)?;
 
-    if let Ok(onnx_model) = OnnxModel::from_bytes(model_bytes.as_slice()) {
-        return Ok(Box::new(onnx_model));
+    Ok(onnx_model)
+}
+
+fn load_onnx_model(path: &Path) -> anyhow::Result<OnnxModel> {
+    let model_bytes = Bytes::from_owner(model::map_file(path)?);
+    let onnx_model = OnnxModel::from_bytes(model_bytes)?;
+
+    prepare_onnx_model(path, onnx_model, false)
+}
+
+fn load_any_model(path: &Path, inline_functions: bool) -> anyhow::Result<Box<dyn Model>> {
+    if path.is_dir() || safetensors::is_index(path) {
+        return Ok(Box::new(Safetensors::from_index(path)?));
+    }
+
+    // Mapped rather than read, onnx initializers refer to the mapping and
+    // safetensors only read their header
+    let model_bytes = Bytes::from_owner(model::map_file(path)?);
+
+    if !safetensors::has_header(&model_bytes) {
+        if let Ok(onnx_model) = OnnxModel::from_bytes(model_bytes) {
+            let onnx_model = prepare_onnx_model(path, onnx_model, inline_functions)?;
+            return Ok(Box::new(onnx_model));
+        }
     }
 
-    let model = Safetensors::from_bytes(model_bytes.into())?;
+    let model = Safetensors::open(path)?;
     Ok(Box::new(model))
 }
 
+fn extract(
+    model_file: &Path,
+    output_file: &Path,
+    from: &[String],
+    to: &[String],
+) -> anyhow::Result<()> {
+    let model = load_onnx_model(model_file)?;
+    let extracted = model.extract(from, to)?;
+    let graph = extracted.graph.as_ref().unwrap();
+
+    fs::write(output_file, extracted.encode_to_vec())?;
+
+    println!(
+        "Extracted {} nodes and {} initializers to {}",
+        graph.node.len(),
+        graph.initializer.len(),
+        output_file.display()
+    );
+
+    Ok(())
+}
+
+fn diff(before: &Path, after: &Path, output: OutputFormat) -> anyhow::Result<()> {
+    let before_model = load_any_model(before, false)?;
+    let after_model = load_any_model(after, false)?;
+    let before_name = before.to_string_lossy();
+    let after_name = after.to_string_lossy();
+
+    let diff = diff_models(
+        before_model.as_ref(),
+        &before_name,
+        after_model.as_ref(),
+        &after_name,
+    )?;
+
+    match output {
+        OutputFormat::Text => print!("{}", diff),
+        OutputFormat::Json => diff.dump_json(&mut stdout().lock())?,
+        OutputFormat::Dot => bail!("Dot output is not supported for diffs"),
+    }
+
+    Ok(())
+}
+
+fn node(model_file: &Path, query: &str, output: OutputFormat) -> anyhow::Result<()> {
+    let model = load_onnx_model(model_file)?;
+    let details = model.node_details(query)?;
+
+    match output {
+        OutputFormat::Text => print!("{}", details),
+        OutputFormat::Json => details.dump_json(&mut stdout().lock())?,
+        OutputFormat::Dot => bail!("Dot output is not supported for nodes"),
+    }
+
+    Ok(())
+}
+
+fn query(model_file: &Path, expr: &str, tensors: bool, output: OutputFormat) -> anyhow::Result<()> {
+    let target = if tensors {
+        QueryTarget::Tensors
+    } else {
+        QueryTarget::Nodes
+    };
+    let query = Query::parse(expr, target)?;
+    let model = load_onnx_model(model_file)?;
+    let matches = model.query(&query, expr);
+
+    match output {
+        OutputFormat::Text => print!("{}", matches),
+        OutputFormat::Json => matches.dump_json(&mut stdout().lock())?,
+        OutputFormat::Dot => bail!("Dot output is not supported for queries"),
+    }
+
+    Ok(())
+}
+
+fn check(model_file: &Path, output: OutputFormat) -> anyhow::Result<()> {
+    let model = load_any_model(model_file, false)?;
+    let findings = model
+        .check()
+        .ok_or_else(|| anyhow!("Check is not supported for {} models", model.format_name()))?;
+    let report = CheckReport::new(model_file.file_name().and_then(|s| s.to_str()), findings);
+
+    match output {
+        OutputFormat::Text => print!("{}", report),
+        OutputFormat::Json => report.dump_json(&mut stdout().lock())?,
+        OutputFormat::Dot => bail!("Dot output is not supported for checks"),
+    }
+
+    if report.errors > 0 {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn export(model_file: &Path, names: &[String], output: &Path, raw: bool) -> anyhow::Result<()> {
+    let model = load_any_model(model_file, false)?;
+    let export = export_tensors(model.as_ref(), names, output, raw)?;
+
+    for (name, dtype) in export.skipped.iter() {
+        eprintln!(
+            "{}: skipped {}, {} has no numeric data",
+            Style::new().yellow().bold().apply_to("warning"),
+            name,
+            dtype
+        );
+    }
+
+    println!(
+        "Exported {} tensors to {}",
+        export.written,
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn stats(
+    model_file: &Path,
+    filter: &StatsFilter,
+    sort: StatsSort,
+    limit: Option<usize>,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let model = load_any_model(model_file, false)?;
+    let summary = StatsSummary::new(
+        model_file.file_name().and_then(|s| s.to_str()),
+        model.as_ref(),
+        filter,
+        sort,
+        limit,
+    );
+
+    match output {
+        OutputFormat::Text => print!("{}", summary),
+        OutputFormat::Json => summary.dump_json(&mut stdout().lock())?,
+        OutputFormat::Dot => bail!("Dot output is not supported for statistics"),
+    }
+
+    if !summary.errors.is_empty() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let model = load_any_model(&args.model_file)?;
-    let filename = args.model_file.file_name().and_then(|s| s.to_str());
+    match args.command {
+        Some(Command::Diff {
+            before,
+            after,
+            output,
+        }) => return diff(&before, &after, output),
+        Some(Command::Extract {
+            model_file,
+            output_file,
+            from,
+            to,
+        }) => return extract(&model_file, &output_file, &from, &to),
+        Some(Command::Node {
+            model_file,
+            node: query,
+            output,
+        }) => return node(&model_file, &query, output),
+        Some(Command::Query {
+            model_file,
+            expr,
+            tensors,
+            output,
+        }) => return query(&model_file, &expr, tensors, output),
+        Some(Command::Check { model_file, output }) => return check(&model_file, output),
+        Some(Command::Stats {
+            model_file,
+            sort,
+            absmax_above,
+            std_below,
+            zeros_above,
+            non_finite,
+            limit,
+            output,
+        }) => {
+            let filter = StatsFilter {
+                absmax_above,
+                std_below,
+                zeros_above,
+                non_finite,
+            };
+            return stats(&model_file, &filter, sort, limit, output);
+        }
+        Some(Command::Tensor {
+            command:
+                TensorCommand::Export {
+                    model_file,
+                    names,
+                    output,
+                    raw,
+                },
+        }) => return export(&model_file, &names, &output, raw),
+        None => {}
+    }
 
-    let summary = model.summary(filename);
+    // Required unless a subcommand is given
+    let model_file = args.model_file.expect("model file is required");
+
+    let model = load_any_model(&model_file, args.inline_functions)?;
+    let filename = model_file.file_name().and_then(|s| s.to_str());
+    let options = SummaryOptions {
+        dims: args.dims.into_iter().collect(),
+        top: args.top,
+        module_depth: args.module_depth,
+        collapse_depth: args.collapse_depth,
+    };
 
     match args.output {
         OutputFormat::Text => {
-            print!("{}", summary);
+            print!("{}", model.summary(filename, &options));
         }
         OutputFormat::Json => {
             let stdout = stdout();
             let mut stdout_lock = stdout.lock();
-            summary.dump_json(&mut stdout_lock)?;
+            model
+                .summary(filename, &options)
+                .dump_json(&mut stdout_lock)?;
+        }
+        OutputFormat::Dot => {
+            let dot = model
+                .dot(&options)
+                .ok_or_else(|| anyhow!("Dot output is only supported for onnx models"))?;
+            print!("{}", dot);
         }
     }
 
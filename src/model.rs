@@ -1,5 +1,49 @@
-use crate::summary::Summary;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::check::Finding;
+use crate::diff::DiffSection;
+use crate::summary::{Summary, SummaryOptions};
+use crate::tensor::TensorData;
 
 pub trait Model {
-    fn summary<'a>(&'a self, filename: Option<&'a str>) -> Box<dyn Summary + 'a>;
+    fn summary<'a>(
+        &'a self,
+        filename: Option<&'a str>,
+        options: &SummaryOptions,
+    ) -> Box<dyn Summary + 'a>;
+
+    /// Graphviz DOT rendering of the model graph, if the format has one
+    fn dot(&self, _options: &SummaryOptions) -> Option<Box<dyn fmt::Display + '_>> {
+        None
+    }
+
+    /// Structural validation findings, if the format has a validator
+    fn check(&self) -> Option<Vec<Finding>> {
+        None
+    }
+
+    /// Name of the file format, only models of the same format can be diffed
+    fn format_name(&self) -> &'static str;
+
+    /// Properties compared when diffing two models
+    fn diff_sections(&self) -> Vec<DiffSection>;
+
+    /// Names of the tensors whose data is stored in the model
+    fn tensor_names(&self) -> Vec<&str>;
+
+    /// Data of a stored tensor, fails if there is no tensor with that name
+    fn tensor_data(&self, name: &str) -> anyhow::Result<TensorData<'_>>;
+}
+
+/// Memory-map a model file, so only the parts that are read get loaded
+pub fn map_file(path: &Path) -> io::Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: the mapping is only read from. Like any reader of the file we
+    // rely on it not being truncated or rewritten while ungraph runs.
+    unsafe { Mmap::map(&file) }
 }
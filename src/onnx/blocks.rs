@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashMap};
+
+use petgraph::algo::toposort;
+use petgraph::Direction;
+
+use crate::onnx::summary::{OperatorCount, RepeatedBlock};
+use crate::onnx::{NodeId, OnnxModel, ValueSource};
+
+/// Blocks found from the op sequence need at least this many nodes, so that
+/// runs like `Add, Add, Add` are not reported
+const MIN_STRUCTURAL_BLOCK_NODES: usize = 3;
+
+/// Largest block found from the op sequence, which keeps the search linear
+/// in the number of nodes
+const MAX_STRUCTURAL_BLOCK_NODES: usize = 512;
+
+/// Split a scope component like `layer.3`, `h_3` or `3` into its name and
+/// index
+fn indexed_component(component: &str) -> Option<(&str, usize)> {
+    let name = component.trim_end_matches(|c: char| c.is_ascii_digit());
+
+    if name.len() == component.len() {
+        return None;
+    }
+
+    let index = component[name.len()..].parse().ok()?;
+
+    match name {
+        "" => Some((name, index)),
+        _ if name.ends_with('.') || name.ends_with('_') => Some((name, index)),
+        _ => None,
+    }
+}
+
+/// Outermost indexed scope of an exporter node name such as
+/// `/encoder/layer.3/attn/MatMul`, as a `/encoder/layer.*` template and the
+/// index
+fn block_scope(node_name: &str) -> Option<(String, usize)> {
+    let components: Vec<&str> = node_name.split('/').collect();
+    // The last component names the node itself, not a scope
+    let scopes = &components[..components.len().saturating_sub(1)];
+
+    for (position, component) in scopes.iter().enumerate() {
+        if let Some((name, index)) = indexed_component(component) {
+            let mut template = scopes[..position].join("/");
+            template.push('/');
+            template.push_str(name);
+            template.push('*');
+
+            return Some((template, index));
+        }
+    }
+
+    None
+}
+
+/// Op types in order of first appearance with their counts
+pub fn operator_counts<'a>(model: &'a OnnxModel, nodes: &[NodeId]) -> Vec<OperatorCount<'a>> {
+    let mut operators: Vec<OperatorCount> = Vec::new();
+
+    for node_id in nodes.iter() {
+        let op_type = model.nodes[*node_id].proto.op_type.as_str();
+
+        match operators.iter_mut().find(|o| o.op_type == op_type) {
+            Some(operator) => operator.count += 1,
+            None => operators.push(OperatorCount { op_type, count: 1 }),
+        }
+    }
+
+    operators
+}
+
+fn signature(operators: &[OperatorCount]) -> BTreeMap<String, usize> {
+    operators
+        .iter()
+        .map(|o| (o.op_type.to_string(), o.count))
+        .collect()
+}
+
+/// Instances of a block whose op counts match the most common ones
+struct BlockInstances {
+    name: String,
+    detection: &'static str,
+    instances: Vec<Vec<NodeId>>,
+}
+
+impl OnnxModel {
+    /// Elements of the initializers used by a set of nodes
+    fn block_parameters(&self, nodes: &[NodeId]) -> u64 {
+        self.initializer_elements(
+            nodes
+                .iter()
+                .flat_map(|node_id| self.nodes[*node_id].proto.input.iter())
+                .filter_map(|input| self.values.get_id_by_name(input))
+                .filter_map(|value_id| match self.values.get_by_id(value_id).source {
+                    Some(ValueSource::Initializer(init_id)) => Some(init_id),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Blocks named like `/encoder/layer.0/...`, `/encoder/layer.1/...`
+    fn scoped_blocks(&self) -> Vec<BlockInstances> {
+        let mut scopes: Vec<(String, BTreeMap<usize, Vec<NodeId>>)> = Vec::new();
+
+        for (node_id, node) in self.nodes.iter().enumerate() {
+            let Some((template, index)) = block_scope(&node.proto.name) else {
+                continue;
+            };
+
+            let position = match scopes.iter().position(|(t, _)| *t == template) {
+                Some(position) => position,
+                None => {
+                    scopes.push((template, BTreeMap::new()));
+                    scopes.len() - 1
+                }
+            };
+
+            scopes[position].1.entry(index).or_default().push(node_id);
+        }
+
+        scopes
+            .into_iter()
+            .filter(|(_, instances)| instances.len() > 1)
+            .map(|(template, instances)| {
+                let first = instances.keys().next().unwrap();
+                let last = instances.keys().next_back().unwrap();
+
+                BlockInstances {
+                    name: template.replace('*', &format!("{{{}..{}}}", first, last)),
+                    detection: "name scope",
+                    instances: instances.into_values().collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Nodes in topological order, each with an id standing for its op type
+    /// and how far back in that order its inputs are produced
+    fn structural_tokens(&self) -> (Vec<NodeId>, Vec<usize>) {
+        // Fall back to file order when the graph has cycles
+        let order: Vec<NodeId> =
+            toposort(&self.node_graph, None).unwrap_or_else(|_| (0..self.nodes.len()).collect());
+        let mut positions = vec![0; self.nodes.len()];
+        for (position, node_id) in order.iter().enumerate() {
+            positions[*node_id] = position;
+        }
+
+        let mut ids: HashMap<(&str, Vec<usize>), usize> = HashMap::new();
+        let tokens = order
+            .iter()
+            .enumerate()
+            .map(|(position, node_id)| {
+                // Producers further back than any block, such as shared
+                // constants, differ between repeats and are left out
+                let mut offsets: Vec<usize> = self
+                    .node_graph
+                    .neighbors_directed(*node_id, Direction::Incoming)
+                    .map(|producer| position.saturating_sub(positions[producer]))
+                    .filter(|offset| *offset <= MAX_STRUCTURAL_BLOCK_NODES)
+                    .collect();
+                offsets.sort();
+
+                let next = ids.len();
+                *ids.entry((self.nodes[*node_id].proto.op_type.as_str(), offsets))
+                    .or_insert(next)
+            })
+            .collect();
+
+        (order, tokens)
+    }
+
+    /// Longest run of a repeated sequence of connected ops in topological
+    /// order, for models whose node names carry no scopes
+    fn structural_block(&self) -> Option<BlockInstances> {
+        let (order, tokens) = self.structural_tokens();
+        // (covered nodes, period, start, repeats)
+        let mut best: Option<(usize, usize, usize, usize)> = None;
+
+        for period in
+            MIN_STRUCTURAL_BLOCK_NODES..=(tokens.len() / 2).min(MAX_STRUCTURAL_BLOCK_NODES)
+        {
+            let mut run = 0;
+
+            for i in 0..tokens.len() - period {
+                if tokens[i] == tokens[i + period] {
+                    run += 1;
+                } else {
+                    run = 0;
+                    continue;
+                }
+
+                let repeats = run / period + 1;
+                let covered = repeats * period;
+
+                if repeats > 1 && best.is_none_or(|(best_covered, ..)| covered > best_covered) {
+                    let start = i + 1 - run;
+                    best = Some((covered, period, start, repeats));
+                }
+            }
+        }
+
+        let (_, period, start, repeats) = best?;
+        let end = start + period * repeats;
+
+        Some(BlockInstances {
+            name: format!("nodes #{}..#{}", order[start], order[end - 1]),
+            detection: "structure",
+            instances: order[start..end]
+                .chunks(period)
+                .map(|nodes| nodes.to_vec())
+                .collect(),
+        })
+    }
+
+    /// Repeated blocks such as transformer layers, found from exporter name
+    /// scopes or else from repeating op sequences
+    pub fn repeated_blocks(&self) -> Vec<RepeatedBlock<'_>> {
+        let mut blocks = self.scoped_blocks();
+
+        if blocks.is_empty() {
+            blocks.extend(self.structural_block());
+        }
+
+        blocks
+            .into_iter()
+            .map(|block| {
+                let operators: Vec<Vec<OperatorCount>> = block
+                    .instances
+                    .iter()
+                    .map(|nodes| operator_counts(self, nodes))
+                    .collect();
+
+                // Most common op counts, the earliest on ties
+                let mut signatures: Vec<(BTreeMap<String, usize>, usize)> = Vec::new();
+                for ops in operators.iter() {
+                    let ops = signature(ops);
+
+                    match signatures.iter_mut().find(|(s, _)| *s == ops) {
+                        Some((_, count)) => *count += 1,
+                        None => signatures.push((ops, 1)),
+                    }
+                }
+                let common = signatures
+                    .into_iter()
+                    .rev()
+                    .max_by_key(|(_, count)| *count)
+                    .map(|(signature, _)| signature)
+                    .unwrap_or_default();
+
+                let matching: Vec<usize> = (0..block.instances.len())
+                    .filter(|i| signature(&operators[*i]) == common)
+                    .collect();
+                let first = matching[0];
+
+                RepeatedBlock {
+                    name: block.name,
+                    detection: block.detection,
+                    count: matching.len(),
+                    differing: block.instances.len() - matching.len(),
+                    nodes: block.instances[first].len(),
+                    operators: operators.into_iter().nth(first).unwrap(),
+                    parameters: matching
+                        .iter()
+                        .map(|i| self.block_parameters(&block.instances[*i]))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{load, node};
+    use crate::onnx::onnx_proto::GraphProto;
+
+    /// Unnamed chain of a stem and `layers` blocks of `ops`, stored in
+    /// reverse order
+    fn chain_model(ops: &[&str], layers: usize) -> OnnxModel {
+        let mut nodes = vec![node("Conv", &["x"], &["stem"])];
+        let mut input = "stem".to_string();
+
+        for layer in 0..layers {
+            for (i, op) in ops.iter().enumerate() {
+                let output = format!("t{}_{}", layer, i);
+                nodes.push(node(op, &[&input], &[&output]));
+                input = output;
+            }
+        }
+        nodes.reverse();
+
+        load(GraphProto {
+            node: nodes,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn block_scope_of_exporter_names() {
+        assert_eq!(
+            block_scope("/encoder/layer.3/attn/MatMul"),
+            Some(("/encoder/layer.*".to_string(), 3))
+        );
+        assert_eq!(block_scope("/h_11/mlp/Add"), Some(("/h_*".to_string(), 11)));
+        assert_eq!(block_scope("/encoder/conv2/Conv"), None);
+        assert_eq!(block_scope("MatMul_12"), None);
+    }
+
+    #[test]
+    fn structural_block_follows_topological_order() {
+        let model = chain_model(&["MatMul", "Add", "Relu", "Mul"], 3);
+
+        let blocks = model.repeated_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].detection, "structure");
+        assert_eq!(blocks[0].count, 3);
+        assert_eq!(blocks[0].nodes, 4);
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::{HashMap, HashSet};
+
+use petgraph::algo::toposort;
+
+use crate::check::{Finding, Location, Severity};
+use crate::onnx::onnx_proto::{type_proto, NodeProto, TensorProto, ValueInfoProto};
+use crate::onnx::shape::Dim;
+use crate::onnx::weights::tensor_type_name;
+use crate::onnx::{data_type_name, NodeId, OnnxModel};
+
+/// Initializers had to be listed as graph inputs before IR version 4
+const IR_VERSION_OPTIONAL_INITIALIZER_INPUTS: i64 = 4;
+
+fn node_location(index: NodeId, node: &NodeProto) -> Location {
+    Location::Node {
+        index,
+        name: node.name.clone(),
+        op_type: node.op_type.clone(),
+    }
+}
+
+fn tensor_location(name: &str) -> Location {
+    Location::Tensor {
+        name: name.to_string(),
+    }
+}
+
+fn opset_domain(domain: &str) -> &str {
+    if domain.is_empty() {
+        "ai.onnx"
+    } else {
+        domain
+    }
+}
+
+struct Checker<'a> {
+    model: &'a OnnxModel,
+    findings: Vec<Finding>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, severity: Severity, location: Location, message: String) {
+        self.findings.push(Finding {
+            severity,
+            location,
+            message,
+        });
+    }
+
+    fn error(&mut self, location: Location, message: String) {
+        self.report(Severity::Error, location, message)
+    }
+
+    fn warning(&mut self, location: Location, message: String) {
+        self.report(Severity::Warning, location, message)
+    }
+
+    fn check_model(&mut self) {
+        if self.model.proto.ir_version <= 0 {
+            self.error(
+                Location::Model,
+                "Model does not declare an IR version".to_string(),
+            );
+        }
+    }
+
+    /// Names must be defined once across graph inputs and node outputs, and
+    /// node names should be unique
+    fn check_duplicates(&mut self) {
+        let graph = self.model.graph_proto();
+        let mut inputs = HashSet::new();
+        let mut initializers = HashSet::new();
+        let mut producers: HashMap<&str, NodeId> = HashMap::new();
+        let mut node_names: HashMap<&str, NodeId> = HashMap::new();
+
+        for input in graph.input.iter() {
+            if !inputs.insert(input.name.as_str()) {
+                self.error(
+                    tensor_location(&input.name),
+                    "Graph input is declared more than once".to_string(),
+                );
+            }
+        }
+
+        for init in graph.initializer.iter() {
+            if !initializers.insert(init.name.as_str()) {
+                self.error(
+                    tensor_location(&init.name),
+                    "Initializer is declared more than once".to_string(),
+                );
+            }
+        }
+
+        for (node_id, node) in graph.node.iter().enumerate() {
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                if let Some(producer) = producers.insert(output, node_id) {
+                    self.error(
+                        node_location(node_id, node),
+                        format!(
+                            "Output {} is already produced by node #{}",
+                            output, producer
+                        ),
+                    );
+                } else if inputs.contains(output.as_str()) {
+                    self.error(
+                        node_location(node_id, node),
+                        format!("Output {} is also a graph input", output),
+                    );
+                } else if initializers.contains(output.as_str()) {
+                    self.error(
+                        node_location(node_id, node),
+                        format!("Output {} is also an initializer", output),
+                    );
+                }
+            }
+
+            if node.name.is_empty() {
+                continue;
+            }
+
+            if let Some(other) = node_names.insert(&node.name, node_id) {
+                self.warning(
+                    node_location(node_id, node),
+                    format!("Node name is also used by node #{}", other),
+                );
+            }
+        }
+    }
+
+    /// Every input must be defined before the node using it, which also
+    /// covers values captured by the node's subgraphs
+    fn check_inputs(&mut self) {
+        let graph = self.model.graph_proto();
+        let mut defined: HashSet<&str> = graph
+            .input
+            .iter()
+            .map(|i| i.name.as_str())
+            .chain(graph.initializer.iter().map(|i| i.name.as_str()))
+            .collect();
+        let producers: HashMap<&str, NodeId> = graph
+            .node
+            .iter()
+            .enumerate()
+            .flat_map(|(node_id, node)| node.output.iter().map(move |o| (o.as_str(), node_id)))
+            .collect();
+
+        for (node_id, node) in graph.node.iter().enumerate() {
+            let outer_refs = self
+                .model
+                .subgraphs
+                .iter()
+                .filter(|s| s.parent.is_none() && s.node == node_id)
+                .flat_map(|s| s.outer_refs.iter());
+
+            for input in node.input.iter().chain(outer_refs) {
+                if input.is_empty() || defined.contains(input.as_str()) {
+                    continue;
+                }
+
+                match producers.get(input.as_str()) {
+                    Some(producer) => self.error(
+                        node_location(node_id, node),
+                        format!(
+                            "Input {} is produced by later node #{}, nodes are not topologically sorted",
+                            input, producer
+                        ),
+                    ),
+                    None => self.error(
+                        node_location(node_id, node),
+                        format!("Input {} is not defined", input),
+                    ),
+                }
+            }
+
+            defined.extend(node.output.iter().map(|o| o.as_str()));
+        }
+    }
+
+    fn check_cycles(&mut self) {
+        if let Err(cycle) = toposort(&self.model.node_graph, None) {
+            let node_id = cycle.node_id();
+            let node = &self.model.nodes[node_id].proto;
+
+            self.error(
+                node_location(node_id, node),
+                "Node is part of a cycle".to_string(),
+            );
+        }
+    }
+
+    fn check_outputs(&mut self) {
+        let graph = self.model.graph_proto();
+        let defined: HashSet<&str> = graph
+            .input
+            .iter()
+            .map(|i| i.name.as_str())
+            .chain(graph.initializer.iter().map(|i| i.name.as_str()))
+            .chain(
+                graph
+                    .node
+                    .iter()
+                    .flat_map(|n| n.output.iter().map(|o| o.as_str())),
+            )
+            .collect();
+
+        for output in graph.output.iter() {
+            if !defined.contains(output.name.as_str()) {
+                self.error(
+                    tensor_location(&output.name),
+                    "Graph output is not produced by any node".to_string(),
+                );
+            }
+        }
+    }
+
+    /// Domains used by nodes, including subgraph nodes, must be imported
+    fn check_opsets(&mut self) {
+        let imported: HashSet<&str> = self
+            .model
+            .proto
+            .opset_import
+            .iter()
+            .map(|o| opset_domain(&o.domain))
+            .collect();
+        let nodes = self
+            .model
+            .nodes
+            .iter()
+            .map(|n| &n.proto)
+            .chain(self.model.subgraphs.iter().flat_map(|s| s.nodes()));
+        let mut reported = HashSet::new();
+
+        for node in nodes {
+            let domain = opset_domain(&node.domain);
+
+            if !imported.contains(domain) && reported.insert(domain) {
+                self.error(
+                    Location::Opset {
+                        domain: domain.to_string(),
+                    },
+                    format!("Domain is used by {} but not imported", node.op_type),
+                );
+            }
+        }
+    }
+
+    /// Initializers listed as graph inputs must agree with the declared type
+    fn check_initializer_inputs(&mut self) {
+        let graph = self.model.graph_proto();
+        let inputs: HashMap<&str, &ValueInfoProto> =
+            graph.input.iter().map(|i| (i.name.as_str(), i)).collect();
+
+        for init in graph.initializer.iter() {
+            match inputs.get(init.name.as_str()) {
+                Some(input) => self.check_initializer_type(init, input),
+                None if self.model.proto.ir_version < IR_VERSION_OPTIONAL_INITIALIZER_INPUTS => {
+                    self.error(
+                        tensor_location(&init.name),
+                        format!(
+                            "Initializer is not a graph input, required before IR version {}",
+                            IR_VERSION_OPTIONAL_INITIALIZER_INPUTS
+                        ),
+                    )
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn check_initializer_type(&mut self, init: &TensorProto, input: &ValueInfoProto) {
+        let Some(type_proto::Value::TensorType(tensor)) =
+            input.r#type.as_ref().and_then(|t| t.value.as_ref())
+        else {
+            return;
+        };
+
+        if tensor.elem_type != init.data_type {
+            self.error(
+                tensor_location(&init.name),
+                format!(
+                    "Initializer is {} but the graph input declares {}",
+                    data_type_name(init.data_type),
+                    data_type_name(tensor.elem_type)
+                ),
+            );
+            return;
+        }
+
+        let Some(shape) = tensor.shape.as_ref() else {
+            return;
+        };
+
+        let dims: Vec<Dim> = shape.dim.iter().map(Dim::from_proto).collect();
+        let matches = dims.len() == init.dims.len()
+            && dims
+                .iter()
+                .zip(init.dims.iter())
+                .all(|(dim, size)| match dim {
+                    Dim::Value(value) => value == size,
+                    _ => true,
+                });
+
+        if !matches {
+            let declared: Vec<String> = dims.iter().map(|d| d.to_string()).collect();
+
+            self.error(
+                tensor_location(&init.name),
+                format!(
+                    "Initializer is {} but the graph input declares shape [{}]",
+                    tensor_type_name(init.data_type, &init.dims),
+                    declared.join(",")
+                ),
+            );
+        }
+    }
+}
+
+impl OnnxModel {
+    /// Structural validation in the spirit of `onnx.checker`
+    pub fn check(&self) -> Vec<Finding> {
+        let mut checker = Checker {
+            model: self,
+            findings: self.diagnostics.clone(),
+        };
+
+        checker.check_model();
+        checker.check_duplicates();
+        checker.check_inputs();
+        checker.check_cycles();
+        checker.check_outputs();
+        checker.check_opsets();
+        checker.check_initializer_inputs();
+        checker.findings.extend(self.verify_checksums());
+
+        checker.findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{model, node, tensor, value};
+    use crate::onnx::onnx_proto::tensor_proto::DataType;
+    use crate::onnx::onnx_proto::{GraphProto, ModelProto, OperatorSetIdProto};
+
+    /// Graph over a float input `x` with an output `y`
+    fn graph(nodes: Vec<NodeProto>) -> GraphProto {
+        GraphProto {
+            node: nodes,
+            input: vec![value("x", &[2])],
+            output: vec![value("y", &[2])],
+            ..Default::default()
+        }
+    }
+
+    fn check_model(proto: ModelProto) -> Vec<(Severity, String)> {
+        OnnxModel::from_proto(proto)
+            .check()
+            .into_iter()
+            .map(|f| (f.severity, format!("{}: {}", f.location, f.message)))
+            .collect()
+    }
+
+    /// Findings of a graph importing the default opset
+    fn check(graph: GraphProto) -> Vec<(Severity, String)> {
+        check_model(ModelProto {
+            opset_import: vec![OperatorSetIdProto {
+                domain: String::new(),
+                version: 18,
+            }],
+            ..model(graph)
+        })
+    }
+
+    fn errors(messages: &[&str]) -> Vec<(Severity, String)> {
+        messages
+            .iter()
+            .map(|m| (Severity::Error, m.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_graph_has_no_findings() {
+        let findings = check(graph(vec![
+            node("Relu", &["x"], &["t"]),
+            node("Neg", &["t"], &["y"]),
+        ]));
+
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+
+    #[test]
+    fn duplicate_output() {
+        let findings = check(graph(vec![
+            node("Relu", &["x"], &["y"]),
+            node("Sigmoid", &["x"], &["y"]),
+        ]));
+
+        assert_eq!(
+            findings,
+            errors(&["node #1 (Sigmoid): Output y is already produced by node #0"])
+        );
+    }
+
+    #[test]
+    fn undefined_and_out_of_order_inputs() {
+        let findings = check(graph(vec![
+            node("Add", &["x", "t"], &["y"]),
+            node("Relu", &["u"], &["t"]),
+        ]));
+
+        assert_eq!(
+            findings,
+            errors(&[
+                "node #0 (Add): Input t is produced by later node #1, nodes are not topologically sorted",
+                "node #1 (Relu): Input u is not defined",
+            ])
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let findings = check(graph(vec![
+            node("Add", &["x", "b"], &["a"]),
+            node("Relu", &["a"], &["b"]),
+            node("Neg", &["b"], &["y"]),
+        ]));
+
+        assert_eq!(findings.len(), 2, "{:?}", findings);
+        assert!(findings[0]
+            .1
+            .ends_with("nodes are not topologically sorted"));
+        assert_eq!(findings[1].0, Severity::Error);
+        assert!(findings[1].1.ends_with("Node is part of a cycle"));
+    }
+
+    #[test]
+    fn cycle_through_a_duplicated_output() {
+        // The last producer of t wins in the node graph, so the cycle through
+        // node #0 only shows as an ordering error
+        let findings = check(graph(vec![
+            node("Neg", &["u"], &["t"]),
+            node("Relu", &["t"], &["u"]),
+            node("Sigmoid", &["x"], &["t"]),
+            node("Add", &["t", "u"], &["y"]),
+        ]));
+
+        assert_eq!(
+            findings,
+            errors(&[
+                "node #2 (Sigmoid): Output t is already produced by node #0",
+                "node #0 (Neg): Input u is produced by later node #1, nodes are not topologically sorted",
+            ])
+        );
+    }
+
+    #[test]
+    fn unproduced_output() {
+        let findings = check(graph(vec![node("Relu", &["x"], &["t"])]));
+
+        assert_eq!(
+            findings,
+            errors(&["tensor y: Graph output is not produced by any node"])
+        );
+    }
+
+    #[test]
+    fn missing_opset() {
+        let contrib = NodeProto {
+            domain: "com.microsoft".to_string(),
+            ..node("FusedGemm", &["x", "x"], &["y"])
+        };
+        let findings = check_model(model(graph(vec![node("Relu", &["x"], &["t"]), contrib])));
+
+        assert_eq!(
+            findings,
+            errors(&[
+                "opset ai.onnx: Domain is used by Relu but not imported",
+                "opset com.microsoft: Domain is used by FusedGemm but not imported",
+            ])
+        );
+    }
+
+    #[test]
+    fn initializer_input_mismatch() {
+        let mut graph = graph(vec![node("Add", &["x", "w"], &["y"])]);
+        graph.input.push(value("w", &[3]));
+        graph.input.push(value("b", &[2]));
+        graph.initializer = vec![
+            tensor("w", DataType::Float, &[2]),
+            tensor("b", DataType::Int64, &[2]),
+        ];
+
+        assert_eq!(
+            check(graph),
+            errors(&[
+                "tensor w: Initializer is f32[2] but the graph input declares shape [3]",
+                "tensor b: Initializer is i64 but the graph input declares f32",
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_ir_version_and_type_are_reported() {
+        let mut graph = graph(vec![node("Relu", &["x"], &["y"])]);
+        graph.output[0].r#type = None;
+        let findings = check_model(ModelProto {
+            ir_version: 0,
+            ..model(graph)
+        });
+
+        assert!(findings.contains(&(
+            Severity::Error,
+            "model: Model does not declare an IR version".to_string()
+        )));
+        assert!(findings
+            .iter()
+            .any(|(severity, _)| *severity == Severity::Warning));
+    }
+}
@@ -0,0 +1,171 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Context};
+
+use crate::onnx::external::ExternalData;
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::TensorProto;
+use crate::onnx::{data_type_name, is_onnx_domain, NodeId, OnnxModel, ValueSource};
+use crate::tensor::{ElementType, TensorData};
+
+/// Element type of an onnx data type, `None` for strings
+pub fn element_type(data_type: DataType) -> Option<ElementType> {
+    let element_type = match data_type {
+        DataType::Double => ElementType::F64,
+        DataType::Float => ElementType::F32,
+        DataType::Float16 => ElementType::F16,
+        DataType::Bfloat16 => ElementType::BF16,
+        DataType::Float8e4m3fn => ElementType::F8E4M3FN,
+        DataType::Float8e4m3fnuz => ElementType::F8E4M3FNUZ,
+        DataType::Float8e5m2 => ElementType::F8E5M2,
+        DataType::Float8e5m2fnuz => ElementType::F8E5M2FNUZ,
+        DataType::Int64 => ElementType::I64,
+        DataType::Int32 => ElementType::I32,
+        DataType::Int16 => ElementType::I16,
+        DataType::Int8 => ElementType::I8,
+        DataType::Uint64 => ElementType::U64,
+        DataType::Uint32 => ElementType::U32,
+        DataType::Uint16 => ElementType::U16,
+        DataType::Uint8 => ElementType::U8,
+        DataType::Bool => ElementType::Bool,
+        DataType::Complex64 => ElementType::C64,
+        DataType::Complex128 => ElementType::C128,
+        DataType::Undefined | DataType::String => return None,
+    };
+
+    Some(element_type)
+}
+
+/// Little-endian bytes of the typed `*_data` field holding the tensor's
+/// elements.
+///
+/// Types narrower than 32 bits are stored one element per `int32_data`
+/// entry, so only the low bytes of each entry are kept.
+fn typed_data(tensor: &TensorProto, element_type: ElementType) -> Vec<u8> {
+    let size = element_type.size();
+
+    match element_type {
+        ElementType::F64 | ElementType::C128 => tensor
+            .double_data
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+        ElementType::F32 | ElementType::C64 => tensor
+            .float_data
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+        ElementType::I64 => tensor
+            .int64_data
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+        ElementType::U64 | ElementType::U32 => tensor
+            .uint64_data
+            .iter()
+            .flat_map(|v| v.to_le_bytes().into_iter().take(size))
+            .collect(),
+        _ => tensor
+            .int32_data
+            .iter()
+            .flat_map(|v| v.to_le_bytes().into_iter().take(size))
+            .collect(),
+    }
+}
+
+/// Data of a tensor proto, wherever it is stored
+fn tensor_data<'a>(
+    name: &'a str,
+    tensor: &'a TensorProto,
+    external: Option<&ExternalData>,
+) -> anyhow::Result<TensorData<'a>> {
+    let element_type = DataType::from_i32(tensor.data_type).and_then(element_type);
+
+    let data = match (external, element_type) {
+        (Some(external), _) => Cow::Owned(
+            external
+                .read()
+                .with_context(|| format!("Cannot read data of {}", name))?,
+        ),
+        (None, _) if !tensor.raw_data.is_empty() => Cow::Borrowed(&tensor.raw_data[..]),
+        (None, Some(element_type)) => Cow::Owned(typed_data(tensor, element_type)),
+        (None, None) => Cow::Borrowed(&[][..]),
+    };
+
+    let shape: Vec<u64> = tensor.dims.iter().map(|d| (*d).max(0) as u64).collect();
+
+    if let Some(element_type) = element_type {
+        let Some(expected) = shape
+            .iter()
+            .try_fold(element_type.size() as u64, |size, d| size.checked_mul(*d))
+        else {
+            bail!("{} has a shape {:?} too large to hold", name, shape);
+        };
+
+        if data.len() as u64 != expected {
+            bail!(
+                "{} holds {} bytes of data but its shape needs {}",
+                name,
+                data.len(),
+                expected
+            );
+        }
+    }
+
+    Ok(TensorData {
+        name,
+        dtype: data_type_name(tensor.data_type),
+        element_type,
+        shape,
+        data,
+    })
+}
+
+impl OnnxModel {
+    /// Tensor held by a `Constant` node
+    fn constant_tensor(&self, node_id: NodeId) -> Option<&TensorProto> {
+        let node = &self.nodes[node_id].proto;
+
+        if node.op_type != "Constant" || !is_onnx_domain(&node.domain) {
+            return None;
+        }
+
+        node.attribute
+            .iter()
+            .find(|attr| attr.name == "value")?
+            .t
+            .as_ref()
+    }
+
+    /// Initializers, followed by outputs of `Constant` nodes holding a tensor
+    pub fn tensor_names(&self) -> Vec<&str> {
+        let initializers = self
+            .graph_proto()
+            .initializer
+            .iter()
+            .map(|init| init.name.as_str());
+        let constants = (0..self.nodes.len())
+            .filter(|node_id| self.constant_tensor(*node_id).is_some())
+            .filter_map(|node_id| self.nodes[node_id].proto.output.first())
+            .map(|name| name.as_str());
+
+        initializers.chain(constants).collect()
+    }
+
+    /// Data of an initializer or constant tensor
+    pub fn tensor_data(&self, name: &str) -> anyhow::Result<TensorData<'_>> {
+        let value = self.value_by_name(name)?;
+
+        match value.source.as_ref() {
+            Some(ValueSource::Initializer(init_id)) => {
+                let init = &self.graph_proto().initializer[*init_id];
+                tensor_data(&init.name, init, self.external_data.get(init_id))
+            }
+            Some(ValueSource::Node(node_id)) => match self.constant_tensor(*node_id) {
+                Some(tensor) => tensor_data(&self.nodes[*node_id].proto.output[0], tensor, None),
+                None => bail!("Tensor {} is computed by a node, not stored", name),
+            },
+            None => bail!("Tensor {} is a graph input, not stored", name),
+        }
+    }
+}
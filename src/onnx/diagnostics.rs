@@ -0,0 +1,152 @@
+use crate::check::{Finding, Location, Severity};
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::{type_proto, GraphProto, ModelProto, TypeProto, ValueInfoProto};
+use crate::onnx::subgraph::node_subgraphs;
+
+fn is_known_data_type(data_type: i32) -> bool {
+    DataType::from_i32(data_type).is_some()
+}
+
+/// Unknown data types anywhere in a type, such as the key of a map
+fn unknown_data_types(ty: &TypeProto, unknown: &mut Vec<i32>) {
+    let mut add = |data_type: i32| {
+        if !is_known_data_type(data_type) && !unknown.contains(&data_type) {
+            unknown.push(data_type);
+        }
+    };
+
+    match ty.value.as_ref() {
+        Some(type_proto::Value::TensorType(tensor)) => add(tensor.elem_type),
+        Some(type_proto::Value::SparseTensorType(tensor)) => add(tensor.elem_type),
+        Some(type_proto::Value::MapType(map)) => {
+            add(map.key_type);
+
+            if let Some(value) = map.value_type.as_deref() {
+                unknown_data_types(value, unknown);
+            }
+        }
+        Some(type_proto::Value::SequenceType(seq)) => {
+            if let Some(elem) = seq.elem_type.as_deref() {
+                unknown_data_types(elem, unknown);
+            }
+        }
+        Some(type_proto::Value::OptionalType(opt)) => {
+            if let Some(elem) = opt.elem_type.as_deref() {
+                unknown_data_types(elem, unknown);
+            }
+        }
+        None => {}
+    }
+}
+
+/// Collects problems found while loading a model that do not stop it from
+/// being summarized
+struct Diagnostics {
+    findings: Vec<Finding>,
+}
+
+impl Diagnostics {
+    fn report(&mut self, severity: Severity, location: Location, message: String) {
+        self.findings.push(Finding {
+            severity,
+            location,
+            message,
+        });
+    }
+
+    fn check_value(&mut self, value: &ValueInfoProto, role: &str) {
+        let location = Location::Tensor {
+            name: value.name.clone(),
+        };
+
+        let Some(ty) = value.r#type.as_ref() else {
+            if !role.is_empty() {
+                self.report(
+                    Severity::Warning,
+                    location,
+                    format!("Graph {} declares no type", role),
+                );
+            }
+            return;
+        };
+
+        let mut unknown = Vec::new();
+        unknown_data_types(ty, &mut unknown);
+
+        for data_type in unknown {
+            self.report(
+                Severity::Warning,
+                location.clone(),
+                format!("Unknown data type {}", data_type),
+            );
+        }
+    }
+
+    fn check_data_type(&mut self, name: &str, data_type: i32) {
+        if !is_known_data_type(data_type) {
+            self.report(
+                Severity::Warning,
+                Location::Tensor {
+                    name: name.to_string(),
+                },
+                format!("Unknown data type {}", data_type),
+            );
+        }
+    }
+
+    fn check_graph(&mut self, graph: &GraphProto) {
+        for input in graph.input.iter() {
+            self.check_value(input, "input");
+        }
+
+        for output in graph.output.iter() {
+            self.check_value(output, "output");
+        }
+
+        for value in graph.value_info.iter() {
+            self.check_value(value, "");
+        }
+
+        for init in graph.initializer.iter() {
+            self.check_data_type(&init.name, init.data_type);
+        }
+
+        for sparse in graph.sparse_initializer.iter() {
+            if let Some(values) = sparse.values.as_ref() {
+                self.check_data_type(&values.name, values.data_type);
+            }
+        }
+
+        for node in graph.node.iter() {
+            for tensor in node
+                .attribute
+                .iter()
+                .flat_map(|attr| attr.t.iter().chain(attr.tensors.iter()))
+            {
+                self.check_data_type(&tensor.name, tensor.data_type);
+            }
+
+            for (_, subgraph) in node_subgraphs(node) {
+                self.check_graph(subgraph);
+            }
+        }
+    }
+}
+
+/// Problems of a model's structure that loading works around
+pub fn load_diagnostics(proto: &ModelProto) -> Vec<Finding> {
+    let mut diagnostics = Diagnostics {
+        findings: Vec::new(),
+    };
+
+    match proto.graph.as_ref() {
+        Some(graph) => diagnostics.check_graph(graph),
+        None => diagnostics.report(
+            Severity::Error,
+            Location::Model,
+            "Model has no graph".to_string(),
+        ),
+    }
+
+    diagnostics.findings
+}
@@ -0,0 +1,74 @@
+use crate::diff::DiffSection;
+use crate::onnx::weights::tensor_type_name;
+use crate::onnx::OnnxModel;
+
+impl OnnxModel {
+    /// Properties compared by `ungraph diff`
+    pub fn diff_sections(&self) -> Vec<DiffSection> {
+        let opset_name = |domain: &str| -> String {
+            if domain.is_empty() {
+                "ai.onnx".to_string()
+            } else {
+                domain.to_string()
+            }
+        };
+
+        vec![
+            DiffSection::new(
+                "Model",
+                [
+                    ("ir_version", self.proto.ir_version.to_string()),
+                    ("producer_name", self.proto.producer_name.clone()),
+                    ("producer_version", self.proto.producer_version.clone()),
+                    ("domain", self.proto.domain.clone()),
+                    ("model_version", self.proto.model_version.to_string()),
+                    ("graph_name", self.graph_proto().name.clone()),
+                ],
+            ),
+            DiffSection::new(
+                "Opsets",
+                self.proto
+                    .opset_import
+                    .iter()
+                    .map(|opset| (opset_name(&opset.domain), opset.version.to_string())),
+            ),
+            DiffSection::new(
+                "Inputs",
+                self.inputs()
+                    .filter(|v| v.source.is_none())
+                    .map(|v| (v.name(), v.type_info().to_string())),
+            ),
+            DiffSection::new(
+                "Outputs",
+                self.outputs()
+                    .map(|v| (v.name(), v.type_info().to_string())),
+            ),
+            DiffSection::new(
+                "Operators",
+                self.operator_usage().operators.iter().map(|op| {
+                    let count = if op.nested_count > 0 {
+                        format!("{} (+{} nested)", op.count, op.nested_count)
+                    } else {
+                        op.count.to_string()
+                    };
+
+                    (format!("{}.{}", op.domain, op.name), count)
+                }),
+            ),
+            DiffSection::new(
+                "Initializers",
+                self.graph_proto()
+                    .initializer
+                    .iter()
+                    .map(|init| (&init.name, tensor_type_name(init.data_type, &init.dims))),
+            ),
+            DiffSection::new(
+                "Metadata",
+                self.proto
+                    .metadata_props
+                    .iter()
+                    .map(|prop| (&prop.key, &prop.value)),
+            ),
+        ]
+    }
+}
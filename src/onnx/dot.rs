@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use petgraph::dot::{self, Dot};
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use crate::onnx::modules::scope_path;
+use crate::onnx::weights::tensor_type_name;
+use crate::onnx::{InitId, NodeId, OnnxModel, ValueId, ValueSource};
+
+/// Vertex of the dataflow graph rendered to graphviz
+#[derive(Debug, Copy, Clone)]
+pub enum Vertex {
+    Input(ValueId),
+    Output(ValueId),
+    Initializer(InitId),
+    Node(NodeId),
+    /// Nodes of a module merged when collapsing to a depth
+    Module(usize),
+}
+
+/// Module merging the nodes below a collapse depth
+struct CollapsedModule {
+    path: String,
+    nodes: usize,
+}
+
+/// Graphviz DOT rendering of an onnx model's dataflow graph.
+///
+/// Edges are the tensors flowing between vertices and are labeled with the
+/// tensor name and, when known, its type. With a collapse depth, nodes in
+/// deeper name scopes are drawn as one vertex per module at that depth.
+pub struct OnnxDot<'a> {
+    model: &'a OnnxModel,
+    graph: DiGraph<Vertex, &'a str>,
+    modules: Vec<CollapsedModule>,
+}
+
+impl<'a> OnnxDot<'a> {
+    pub fn new(model: &'a OnnxModel, collapse_depth: Option<usize>) -> Self {
+        let graph_proto = model.graph_proto();
+        let mut graph = DiGraph::new();
+        let mut producers: HashMap<&str, NodeIndex> = HashMap::new();
+
+        let init_vertices: Vec<NodeIndex> = graph_proto
+            .initializer
+            .iter()
+            .enumerate()
+            .map(|(init_id, init)| {
+                let vertex = graph.add_node(Vertex::Initializer(init_id));
+                producers.insert(init.name.as_str(), vertex);
+                vertex
+            })
+            .collect();
+
+        for &value_id in model.inputs.iter() {
+            let input = model.values.get_by_id(value_id);
+
+            // Inputs backed by an initializer are drawn as the initializer
+            let vertex = match input.source {
+                Some(ValueSource::Initializer(init_id)) => init_vertices[init_id],
+                _ => graph.add_node(Vertex::Input(value_id)),
+            };
+
+            producers.insert(input.name(), vertex);
+        }
+
+        let mut modules: Vec<CollapsedModule> = Vec::new();
+        let mut module_vertices: HashMap<String, NodeIndex> = HashMap::new();
+
+        let node_vertices: Vec<NodeIndex> = model
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(node_id, node)| {
+                let path = scope_path(&node.proto.name);
+                let vertex = match collapse_depth {
+                    Some(depth) if depth > 0 && path.len() >= depth => {
+                        let module_path = format!("/{}", path[..depth].join("/"));
+
+                        *module_vertices
+                            .entry(module_path)
+                            .or_insert_with_key(|path| {
+                                modules.push(CollapsedModule {
+                                    path: path.clone(),
+                                    nodes: 0,
+                                });
+                                graph.add_node(Vertex::Module(modules.len() - 1))
+                            })
+                    }
+                    _ => graph.add_node(Vertex::Node(node_id)),
+                };
+
+                if let Vertex::Module(module) = graph[vertex] {
+                    modules[module].nodes += 1;
+                }
+
+                for output in node.proto.output.iter().filter(|o| !o.is_empty()) {
+                    producers.insert(output.as_str(), vertex);
+                }
+
+                vertex
+            })
+            .collect();
+
+        // Nodes merged into a module would otherwise repeat edges and loop
+        // back to their own module
+        let mut edges = HashSet::new();
+
+        for (node, vertex) in model.nodes.iter().zip(node_vertices) {
+            for input in node.proto.input.iter().filter(|i| !i.is_empty()) {
+                if let Some(&producer) = producers.get(input.as_str()) {
+                    let merged = matches!(graph[vertex], Vertex::Module(_));
+
+                    if !merged
+                        || (producer != vertex && edges.insert((producer, vertex, input.as_str())))
+                    {
+                        graph.add_edge(producer, vertex, input.as_str());
+                    }
+                }
+            }
+        }
+
+        for &value_id in model.outputs.iter() {
+            let output = model.values.get_by_id(value_id);
+            let vertex = graph.add_node(Vertex::Output(value_id));
+
+            if let Some(producer) = producers.get(output.name()) {
+                graph.add_edge(*producer, vertex, output.name());
+            }
+        }
+
+        if collapse_depth.is_some() {
+            // Weights of collapsed modules only add clutter
+            let unused: HashSet<NodeIndex> = graph
+                .node_indices()
+                .filter(|vertex| matches!(graph[*vertex], Vertex::Initializer(_)))
+                .filter(|vertex| {
+                    graph
+                        .neighbors(*vertex)
+                        .all(|consumer| matches!(graph[consumer], Vertex::Module(_)))
+                })
+                .collect();
+
+            graph = graph.filter_map(
+                |vertex, weight| (!unused.contains(&vertex)).then_some(*weight),
+                |_, tensor| Some(*tensor),
+            );
+        }
+
+        OnnxDot {
+            model,
+            graph,
+            modules,
+        }
+    }
+
+    fn vertex_attrs(&self, vertex: Vertex) -> String {
+        match vertex {
+            Vertex::Input(value_id) => {
+                let value = self.model.values.get_by_id(value_id);
+                format!(
+                    "label = \"{}\\n{}\" shape = ellipse style = filled fillcolor = lightblue",
+                    escape(value.name()),
+                    escape(&value.type_info().to_string())
+                )
+            }
+            Vertex::Output(value_id) => {
+                let value = self.model.values.get_by_id(value_id);
+                format!(
+                    "label = \"{}\\n{}\" shape = ellipse style = filled fillcolor = lightgreen",
+                    escape(value.name()),
+                    escape(&value.type_info().to_string())
+                )
+            }
+            Vertex::Initializer(init_id) => {
+                let init = &self.model.graph_proto().initializer[init_id];
+                format!(
+                    "label = \"{}\\n{}\" shape = note style = filled fillcolor = lightgrey",
+                    escape(&init.name),
+                    tensor_type_name(init.data_type, &init.dims)
+                )
+            }
+            Vertex::Module(module) => {
+                let module = &self.modules[module];
+                format!(
+                    "label = \"{}\\n{} nodes\" shape = box3d",
+                    escape(&module.path),
+                    module.nodes
+                )
+            }
+            Vertex::Node(node_id) => {
+                let node = &self.model.nodes[node_id].proto;
+                let op = if node.domain.is_empty() {
+                    node.op_type.clone()
+                } else {
+                    format!("{}.{}", node.domain, node.op_type)
+                };
+
+                if node.name.is_empty() {
+                    format!("label = \"{}\" shape = box", escape(&op))
+                } else {
+                    format!(
+                        "label = \"{}\\n{}\" shape = box",
+                        escape(&op),
+                        escape(&node.name)
+                    )
+                }
+            }
+        }
+    }
+
+    fn edge_attrs(&self, tensor: &str) -> String {
+        let ty = self
+            .model
+            .values
+            .get_id_by_name(tensor)
+            .map(|value_id| self.model.values.get_by_id(value_id))
+            .filter(|value| value.type_proto().is_some())
+            .map(|value| value.type_info().to_string());
+
+        match ty {
+            Some(ty) => format!("label = \"{}\\n{}\"", escape(tensor), escape(&ty)),
+            None => format!("label = \"{}\"", escape(tensor)),
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<'a> fmt::Display for OnnxDot<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dot_config = [dot::Config::NodeNoLabel, dot::Config::EdgeNoLabel];
+
+        // Labels come from the attribute getters so debug formatting of the
+        // weights is never used
+        write!(
+            f,
+            "{:?}",
+            Dot::with_attr_getters(
+                &self.graph,
+                &dot_config,
+                &|_g, e| self.edge_attrs(e.weight()),
+                &|_g, (_, vertex)| self.vertex_attrs(*vertex),
+            )
+        )
+    }
+}
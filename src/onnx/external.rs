@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use sha1::{Digest, Sha1};
+
+use crate::check::{Finding, Location, Severity};
+use crate::onnx::onnx_proto::tensor_proto::DataLocation;
+use crate::onnx::onnx_proto::TensorProto;
+use crate::onnx::summary::ExternalFile;
+use crate::onnx::weights::tensor_bytes;
+use crate::onnx::{InitId, OnnxModel};
+
+/// Location of a tensor's data outside of the model file
+#[derive(Debug, Clone)]
+pub struct ExternalData {
+    /// Location as written in the model, relative to the model's directory
+    pub location: String,
+    /// Location resolved against the model's directory
+    pub path: PathBuf,
+    pub offset: u64,
+    pub length: u64,
+    pub checksum: Option<String>,
+}
+
+impl ExternalData {
+    /// Parse the `external_data` entries of a tensor, `None` if its data is
+    /// stored inline
+    pub fn from_tensor(tensor: &TensorProto, model_dir: &Path) -> Option<anyhow::Result<Self>> {
+        if tensor.data_location != DataLocation::External as i32 {
+            return None;
+        }
+
+        Some(Self::parse(tensor, model_dir))
+    }
+
+    fn parse(tensor: &TensorProto, model_dir: &Path) -> anyhow::Result<Self> {
+        let mut location = None;
+        let mut offset = 0;
+        let mut length = None;
+        let mut checksum = None;
+
+        for entry in tensor.external_data.iter() {
+            match entry.key.as_str() {
+                "location" => location = Some(entry.value.clone()),
+                "offset" => {
+                    offset = entry
+                        .value
+                        .parse()
+                        .with_context(|| format!("Invalid offset {:?}", entry.value))?
+                }
+                "length" => {
+                    length = Some(
+                        entry
+                            .value
+                            .parse()
+                            .with_context(|| format!("Invalid length {:?}", entry.value))?,
+                    )
+                }
+                "checksum" => checksum = Some(entry.value.to_ascii_lowercase()),
+                _ => {}
+            }
+        }
+
+        let location = location.ok_or_else(|| anyhow!("Missing location"))?;
+
+        // Same restriction as onnx.checker, external data must live next to
+        // the model
+        let relative = Path::new(&location);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            bail!(
+                "Location {:?} must be relative to the model directory",
+                location
+            );
+        }
+
+        Ok(ExternalData {
+            path: model_dir.join(relative),
+            location,
+            offset,
+            length: length.unwrap_or_else(|| tensor_bytes(tensor)),
+            checksum,
+        })
+    }
+
+    /// Read the tensor's byte range from its file
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; self.length as usize];
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+/// SHA1 digest of a whole file as lowercase hex, streamed from disk
+fn sha1_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl OnnxModel {
+    /// Resolve initializers stored as external data relative to `model_dir`.
+    ///
+    /// Every referenced file must exist and hold the tensor's byte range. All
+    /// problems are reported together rather than stopping at the first one.
+    /// Checksums are left to `verify_checksums`, as they hash whole files.
+    pub fn resolve_external_data(&mut self, model_dir: &Path) -> anyhow::Result<()> {
+        let mut external_data = HashMap::new();
+        let mut file_sizes: HashMap<PathBuf, io::Result<u64>> = HashMap::new();
+        let mut errors = Vec::new();
+
+        for (init_id, init) in self.graph_proto().initializer.iter().enumerate() {
+            let data = match ExternalData::from_tensor(init, model_dir) {
+                None => continue,
+                Some(Ok(data)) => data,
+                Some(Err(err)) => {
+                    errors.push(format!("{}: {:#}", init.name, err));
+                    continue;
+                }
+            };
+
+            let file_size = file_sizes
+                .entry(data.path.clone())
+                .or_insert_with(|| fs::metadata(&data.path).map(|m| m.len()));
+
+            match file_size {
+                Err(err) => {
+                    errors.push(format!(
+                        "{}: Cannot open {}: {}",
+                        init.name,
+                        data.path.display(),
+                        err
+                    ));
+                    continue;
+                }
+                Ok(file_size)
+                    if data
+                        .offset
+                        .checked_add(data.length)
+                        .is_none_or(|end| end > *file_size) =>
+                {
+                    errors.push(format!(
+                        "{}: {} is truncated, expected {} bytes at offset {} but file is {} bytes",
+                        init.name,
+                        data.path.display(),
+                        data.length,
+                        data.offset,
+                        file_size
+                    ));
+                    continue;
+                }
+                Ok(_) => {}
+            }
+
+            external_data.insert(init_id, data);
+        }
+
+        if !errors.is_empty() {
+            bail!("Invalid external data:\n    {}", errors.join("\n    "));
+        }
+
+        self.external_data = external_data;
+
+        Ok(())
+    }
+
+    /// Compare the recorded checksums of external data with the SHA1 of the
+    /// files holding it, hashing each file once
+    pub fn verify_checksums(&self) -> Vec<Finding> {
+        let mut file_checksums: HashMap<&Path, io::Result<String>> = HashMap::new();
+        let mut findings = Vec::new();
+        let mut init_ids: Vec<&InitId> = self.external_data.keys().collect();
+        init_ids.sort();
+
+        for init_id in init_ids {
+            let data = &self.external_data[init_id];
+            let Some(expected) = data.checksum.as_ref() else {
+                continue;
+            };
+
+            let checksum = file_checksums
+                .entry(&data.path)
+                .or_insert_with(|| sha1_file(&data.path));
+
+            let message = match checksum {
+                Err(err) => format!("Cannot read {}: {}", data.path.display(), err),
+                Ok(actual) if actual != expected => format!(
+                    "Checksum mismatch for {}, expected {} but got {}",
+                    data.path.display(),
+                    expected,
+                    actual
+                ),
+                Ok(_) => continue,
+            };
+
+            findings.push(Finding {
+                severity: Severity::Error,
+                location: Location::Tensor {
+                    name: self.graph_proto().initializer[*init_id].name.clone(),
+                },
+                message,
+            });
+        }
+
+        findings
+    }
+
+    /// Files holding external data and how much of the weights they hold
+    pub fn external_files(&self) -> Vec<ExternalFile<'_>> {
+        let mut files: Vec<ExternalFile> = Vec::new();
+        let mut init_ids: Vec<&InitId> = self.external_data.keys().collect();
+        init_ids.sort();
+
+        for init_id in init_ids {
+            let data = &self.external_data[init_id];
+
+            match files.iter_mut().find(|f| f.location == data.location) {
+                Some(file) => {
+                    file.tensors += 1;
+                    file.bytes += data.length;
+                }
+                None => files.push(ExternalFile {
+                    location: &data.location,
+                    tensors: 1,
+                    bytes: data.length,
+                }),
+            }
+        }
+
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::onnx::fixtures::{external, load, tensor};
+    use crate::onnx::onnx_proto::tensor_proto::DataType;
+    use crate::onnx::onnx_proto::{GraphProto, StringStringEntryProto};
+
+    fn external_tensor(name: &str, offset: u64, length: u64) -> TensorProto {
+        external(
+            tensor(name, DataType::Float, &[1]),
+            "weights.bin",
+            offset,
+            length,
+        )
+    }
+
+    #[test]
+    fn ranges_past_the_end_of_the_file_are_errors() {
+        let model_dir = std::env::temp_dir().join(format!("ungraph-external-{}", process::id()));
+        fs::create_dir_all(&model_dir).unwrap();
+        fs::write(model_dir.join("weights.bin"), [0u8; 8]).unwrap();
+
+        let mut model = load(GraphProto {
+            initializer: vec![
+                external_tensor("ok", 4, 4),
+                external_tensor("wrapping", u64::MAX, 4),
+                external_tensor("truncated", 6, 4),
+            ],
+            ..Default::default()
+        });
+
+        let err = model.resolve_external_data(&model_dir).unwrap_err();
+        fs::remove_dir_all(&model_dir).unwrap();
+
+        let message = err.to_string();
+        assert!(!message.contains("ok:"));
+        assert!(message.contains("wrapping: "));
+        assert!(message.contains("truncated: "));
+    }
+
+    #[test]
+    fn checksums_are_verified_by_check_only() {
+        let model_dir = std::env::temp_dir().join(format!("ungraph-checksum-{}", process::id()));
+        fs::create_dir_all(&model_dir).unwrap();
+        fs::write(model_dir.join("weights.bin"), b"abcd").unwrap();
+
+        let with_checksum = |name: &str, checksum: &str| {
+            let mut tensor = external_tensor(name, 0, 4);
+            tensor.external_data.push(StringStringEntryProto {
+                key: "checksum".to_string(),
+                value: checksum.to_string(),
+            });
+            tensor
+        };
+        let mut model = load(GraphProto {
+            initializer: vec![
+                with_checksum("ok", "81FE8BFE87576C3ECB22426F8E57847382917ACF"),
+                with_checksum("bad", "0000000000000000000000000000000000000000"),
+            ],
+            ..Default::default()
+        });
+
+        let resolved = model.resolve_external_data(&model_dir);
+        let findings = model.verify_checksums();
+        fs::remove_dir_all(&model_dir).unwrap();
+
+        resolved.unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].location.to_string(), "tensor bad");
+        assert!(findings[0]
+            .message
+            .ends_with("expected 0000000000000000000000000000000000000000 but got 81fe8bfe87576c3ecb22426f8e57847382917acf"));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::{bail, Context};
+
+use crate::onnx::onnx_proto::tensor_proto::DataLocation;
+use crate::onnx::onnx_proto::{GraphProto, ModelProto, TensorProto, ValueInfoProto};
+use crate::onnx::{InitId, NodeId, OnnxModel, ValueInfo, ValueSource};
+
+/// Declared value info with the inferred type filled in
+fn value_info_proto(value: &ValueInfo) -> ValueInfoProto {
+    ValueInfoProto {
+        r#type: value.type_proto().cloned(),
+        ..value.proto.clone()
+    }
+}
+
+impl OnnxModel {
+    pub fn value_by_name(&self, name: &str) -> anyhow::Result<&ValueInfo> {
+        let value_id = self
+            .values
+            .get_id_by_name(name)
+            .with_context(|| format!("Tensor {} does not exist", name))?;
+
+        Ok(self.values.get_by_id(value_id))
+    }
+
+    /// Initializer with its external data read into `raw_data`, so the
+    /// extracted model does not depend on where it is written
+    fn embedded_initializer(&self, init_id: InitId) -> anyhow::Result<TensorProto> {
+        let mut init = self.graph_proto().initializer[init_id].clone();
+
+        if let Some(data) = self.external_data.get(&init_id) {
+            init.raw_data = data
+                .read()
+                .with_context(|| format!("Cannot read data of {}", init.name))?
+                .into();
+            init.external_data.clear();
+            init.data_location = DataLocation::Default as i32;
+        }
+
+        Ok(init)
+    }
+
+    /// Model holding only the nodes and initializers needed to compute
+    /// `outputs` from `inputs`.
+    ///
+    /// Opset imports, metadata and local functions of the original model are
+    /// kept. Fails if the outputs depend on tensors other than the inputs
+    /// and initializers.
+    pub fn extract(&self, inputs: &[String], outputs: &[String]) -> anyhow::Result<ModelProto> {
+        let input_names: HashSet<&str> = inputs.iter().map(|i| i.as_str()).collect();
+        let mut nodes: BTreeSet<NodeId> = BTreeSet::new();
+        let mut initializers: BTreeSet<InitId> = BTreeSet::new();
+        let mut missing: BTreeSet<&str> = BTreeSet::new();
+        let mut visited: HashSet<&str> = HashSet::new();
+
+        for name in inputs.iter() {
+            self.value_by_name(name)?;
+        }
+
+        let mut pending: Vec<&str> = Vec::new();
+        for name in outputs.iter() {
+            self.value_by_name(name)?;
+            pending.push(name);
+        }
+
+        while let Some(name) = pending.pop() {
+            if input_names.contains(name) || !visited.insert(name) {
+                continue;
+            }
+
+            match self.value_by_name(name)?.source {
+                Some(ValueSource::Node(node_id)) => {
+                    nodes.insert(node_id);
+
+                    let outer_refs = self
+                        .subgraphs
+                        .iter()
+                        .filter(|s| s.parent.is_none() && s.node == node_id)
+                        .flat_map(|s| s.outer_refs.iter());
+
+                    pending.extend(
+                        self.nodes[node_id]
+                            .proto
+                            .input
+                            .iter()
+                            .chain(outer_refs)
+                            .filter(|i| !i.is_empty())
+                            .map(|i| i.as_str()),
+                    );
+                }
+                Some(ValueSource::Initializer(init_id)) => {
+                    initializers.insert(init_id);
+                }
+                None => {
+                    missing.insert(name);
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            let missing: Vec<&str> = missing.into_iter().collect();
+            bail!(
+                "Outputs also depend on {}, add them to the inputs",
+                missing.join(", ")
+            );
+        }
+
+        let graph = self.graph_proto();
+        let produced: HashSet<&str> = nodes
+            .iter()
+            .flat_map(|node_id| self.nodes[*node_id].proto.output.iter())
+            .map(|o| o.as_str())
+            .collect();
+
+        let sub_graph = GraphProto {
+            name: graph.name.clone(),
+            doc_string: graph.doc_string.clone(),
+            node: nodes
+                .iter()
+                .map(|node_id| self.nodes[*node_id].proto.clone())
+                .collect(),
+            initializer: initializers
+                .iter()
+                .map(|init_id| self.embedded_initializer(*init_id))
+                .collect::<anyhow::Result<_>>()?,
+            input: inputs
+                .iter()
+                .map(|name| self.value_by_name(name).map(value_info_proto))
+                .collect::<anyhow::Result<_>>()?,
+            output: outputs
+                .iter()
+                .map(|name| self.value_by_name(name).map(value_info_proto))
+                .collect::<anyhow::Result<_>>()?,
+            value_info: graph
+                .value_info
+                .iter()
+                .filter(|v| produced.contains(v.name.as_str()))
+                .filter(|v| !outputs.contains(&v.name))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        // Training info refers to the original graph and is left out
+        Ok(ModelProto {
+            ir_version: self.proto.ir_version,
+            opset_import: self.proto.opset_import.clone(),
+            producer_name: self.proto.producer_name.clone(),
+            producer_version: self.proto.producer_version.clone(),
+            domain: self.proto.domain.clone(),
+            model_version: self.proto.model_version,
+            doc_string: self.proto.doc_string.clone(),
+            graph: Some(sub_graph),
+            metadata_props: self.proto.metadata_props.clone(),
+            functions: self.proto.functions.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+    use crate::onnx::fixtures::{external, graph, load, node, tensor, value};
+    use crate::onnx::onnx_proto::tensor_proto::DataType;
+    use crate::onnx::onnx_proto::NodeProto;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn extract(model: &OnnxModel, inputs: &[&str], outputs: &[&str]) -> GraphProto {
+        model
+            .extract(&names(inputs), &names(outputs))
+            .unwrap()
+            .graph
+            .unwrap()
+    }
+
+    fn op_types(graph: &GraphProto) -> Vec<&str> {
+        graph.node.iter().map(|n| n.op_type.as_str()).collect()
+    }
+
+    /// x -> Relu -> r1 -> Mul(w) -> r2 -> Add(x) -> y
+    fn chain() -> OnnxModel {
+        load(GraphProto {
+            node: vec![
+                node("Relu", &["x"], &["r1"]),
+                node("Mul", &["r1", "w"], &["r2"]),
+                node("Add", &["r2", "x"], &["y"]),
+            ],
+            initializer: vec![
+                tensor("w", DataType::Float, &[4]),
+                tensor("unused", DataType::Float, &[4]),
+            ],
+            input: vec![value("x", &[4])],
+            output: vec![value("y", &[4])],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn mid_graph_cut() {
+        let graph = extract(&chain(), &["r1"], &["r2"]);
+
+        assert_eq!(op_types(&graph), vec!["Mul"]);
+        let initializers: Vec<&str> = graph.initializer.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(initializers, vec!["w"]);
+        assert_eq!(graph.input[0].name, "r1");
+        assert_eq!(graph.output[0].name, "r2");
+        // Inferred types are filled in for the new inputs and outputs
+        assert!(graph.input[0].r#type.is_some());
+        assert!(graph.output[0].r#type.is_some());
+    }
+
+    #[test]
+    fn outputs_depending_on_other_tensors() {
+        let err = chain()
+            .extract(&names(&["r1"]), &names(&["y"]))
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Outputs also depend on x, add them to the inputs"
+        );
+    }
+
+    #[test]
+    fn subgraph_references_pull_in_nodes() {
+        let branch = GraphProto {
+            node: vec![node("Neg", &["t"], &["b"])],
+            output: vec![value("b", &[4])],
+            ..Default::default()
+        };
+        let model = load(GraphProto {
+            node: vec![
+                node("Relu", &["x"], &["t"]),
+                node("Sigmoid", &["x"], &["s"]),
+                NodeProto {
+                    attribute: vec![
+                        graph("then_branch", branch.clone()),
+                        graph("else_branch", branch),
+                    ],
+                    ..node("If", &["c"], &["y"])
+                },
+            ],
+            input: vec![value("x", &[4]), value("c", &[])],
+            ..Default::default()
+        });
+
+        let graph = extract(&model, &["x", "c"], &["y"]);
+
+        assert_eq!(op_types(&graph), vec!["Relu", "If"]);
+    }
+
+    #[test]
+    fn external_data_is_embedded() {
+        let model_dir = std::env::temp_dir().join(format!("ungraph-extract-{}", process::id()));
+        fs::create_dir_all(&model_dir).unwrap();
+        let data = [1.0f32.to_le_bytes(), 2.0f32.to_le_bytes()].concat();
+        fs::write(model_dir.join("weights.bin"), [&[0; 4], &data[..]].concat()).unwrap();
+
+        let mut model = load(GraphProto {
+            node: vec![node("Mul", &["x", "w"], &["y"])],
+            initializer: vec![external(
+                tensor("w", DataType::Float, &[2]),
+                "weights.bin",
+                4,
+                8,
+            )],
+            input: vec![value("x", &[2])],
+            ..Default::default()
+        });
+        model.resolve_external_data(&model_dir).unwrap();
+
+        let graph = model.extract(&names(&["x"]), &names(&["y"]));
+        fs::remove_dir_all(&model_dir).unwrap();
+        let graph = graph.unwrap().graph.unwrap();
+
+        let w = &graph.initializer[0];
+        assert_eq!(w.raw_data, data);
+        assert!(w.external_data.is_empty());
+        assert_eq!(w.data_location, DataLocation::Default as i32);
+    }
+}
@@ -0,0 +1,97 @@
+//! Builders of onnx protos shared by the unit tests
+
+use super::onnx_proto::attribute_proto::AttributeType;
+use super::onnx_proto::tensor_proto::{DataLocation, DataType};
+use super::onnx_proto::{
+    AttributeProto, GraphProto, ModelProto, NodeProto, StringStringEntryProto, TensorProto,
+    ValueInfoProto,
+};
+use super::{tensor_type, OnnxModel};
+
+/// Model of IR version 8 around a graph
+pub fn model(graph: GraphProto) -> ModelProto {
+    ModelProto {
+        ir_version: 8,
+        graph: Some(graph),
+        ..Default::default()
+    }
+}
+
+/// Load a model around a graph the way `OnnxModel::from_bytes` does
+pub fn load(graph: GraphProto) -> OnnxModel {
+    OnnxModel::from_proto(model(graph))
+}
+
+/// Unnamed node of the default domain without attributes
+pub fn node(op_type: &str, inputs: &[&str], outputs: &[&str]) -> NodeProto {
+    NodeProto {
+        op_type: op_type.to_string(),
+        input: inputs.iter().map(|i| i.to_string()).collect(),
+        output: outputs.iter().map(|o| o.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+/// Tensor without data
+pub fn tensor(name: &str, data_type: DataType, dims: &[i64]) -> TensorProto {
+    TensorProto {
+        name: name.to_string(),
+        data_type: data_type as i32,
+        dims: dims.to_vec(),
+        ..Default::default()
+    }
+}
+
+/// Tensor with its data at `offset` in the external file `location`
+pub fn external(tensor: TensorProto, location: &str, offset: u64, length: u64) -> TensorProto {
+    let entry = |key: &str, value: String| StringStringEntryProto {
+        key: key.to_string(),
+        value,
+    };
+
+    TensorProto {
+        data_location: DataLocation::External as i32,
+        external_data: vec![
+            entry("location", location.to_string()),
+            entry("offset", offset.to_string()),
+            entry("length", length.to_string()),
+        ],
+        ..tensor
+    }
+}
+
+/// Float tensor value of a known shape
+pub fn value(name: &str, dims: &[i64]) -> ValueInfoProto {
+    ValueInfoProto {
+        name: name.to_string(),
+        r#type: Some(tensor_type(DataType::Float as i32, dims)),
+        ..Default::default()
+    }
+}
+
+pub fn int(name: &str, value: i64) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: AttributeType::Int as i32,
+        i: value,
+        ..Default::default()
+    }
+}
+
+pub fn ints(name: &str, values: &[i64]) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: AttributeType::Ints as i32,
+        ints: values.to_vec(),
+        ..Default::default()
+    }
+}
+
+pub fn graph(name: &str, graph: GraphProto) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: AttributeType::Graph as i32,
+        g: Some(graph),
+        ..Default::default()
+    }
+}
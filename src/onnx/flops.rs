@@ -0,0 +1,278 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::onnx::onnx_proto::NodeProto;
+use crate::onnx::shape::Dim;
+use crate::onnx::summary::{ComputeSummary, NodeCompute, OpTypeCompute};
+use crate::onnx::{is_onnx_domain, OnnxModel};
+
+/// Why the size of a dimension could not be determined
+enum Unresolved {
+    /// Symbolic dimension with no binding
+    Symbol(String),
+    Unknown,
+}
+
+/// Concrete dimension sizes with symbolic dims replaced by their bindings
+struct DimResolver<'a> {
+    bindings: &'a HashMap<String, u64>,
+}
+
+impl<'a> DimResolver<'a> {
+    fn dim(&self, dim: &Dim) -> Result<u64, Unresolved> {
+        match dim {
+            Dim::Value(v) => Ok((*v).max(0) as u64),
+            // Params may be products such as `batch*seq` from shape inference
+            Dim::Param(param) => param.split('*').try_fold(1u64, |acc, factor| {
+                let size = match factor.parse::<u64>() {
+                    Ok(v) => v,
+                    Err(_) => *self
+                        .bindings
+                        .get(factor)
+                        .ok_or_else(|| Unresolved::Symbol(factor.to_string()))?,
+                };
+                Ok(acc.saturating_mul(size))
+            }),
+            Dim::Unknown => Err(Unresolved::Unknown),
+        }
+    }
+
+    fn shape(&self, shape: &[Dim]) -> Result<Vec<u64>, Unresolved> {
+        shape.iter().map(|d| self.dim(d)).collect()
+    }
+}
+
+fn product(dims: &[u64]) -> u64 {
+    dims.iter().fold(1u64, |acc, d| acc.saturating_mul(*d))
+}
+
+/// Multiply-accumulate count of a node, `Ok(None)` for ops that are not
+/// estimated
+fn node_macs(
+    model: &OnnxModel,
+    node: &NodeProto,
+    resolver: &DimResolver,
+) -> Result<Option<u64>, Unresolved> {
+    let shape = |index: usize, names: &[String]| -> Result<Vec<u64>, Unresolved> {
+        let shape = names
+            .get(index)
+            .and_then(|name| model.values.get_id_by_name(name))
+            .and_then(|value_id| model.values.get_by_id(value_id).shape())
+            .ok_or(Unresolved::Unknown)?;
+        resolver.shape(&shape)
+    };
+    let input = |index: usize| shape(index, &node.input);
+    let output = |index: usize| shape(index, &node.output);
+    let attr_int = |name: &str| node.attribute.iter().find(|a| a.name == name).map(|a| a.i);
+
+    let macs = match (node.domain.as_str(), node.op_type.as_str()) {
+        (domain, "Conv" | "ConvInteger" | "QLinearConv") if is_onnx_domain(domain) => {
+            // Output elements times one filter application over the input
+            // channels of its group
+            let weight = if node.op_type == "QLinearConv" {
+                input(3)?
+            } else {
+                input(1)?
+            };
+            product(&output(0)?).saturating_mul(product(weight.get(1..).unwrap_or_default()))
+        }
+        (domain, "ConvTranspose") if is_onnx_domain(domain) => {
+            // Every input element is scattered through a filter for each output
+            // channel of its group
+            let weight = input(1)?;
+            product(&input(0)?).saturating_mul(product(weight.get(1..).unwrap_or_default()))
+        }
+        (domain, "MatMul" | "MatMulInteger" | "QLinearMatMul") if is_onnx_domain(domain) => {
+            let k = input(0)?.last().copied().unwrap_or(1);
+            product(&output(0)?).saturating_mul(k)
+        }
+        (domain, "Gemm") if is_onnx_domain(domain) => {
+            let a = input(0)?;
+            let k = if attr_int("transA") == Some(1) {
+                a.first()
+            } else {
+                a.get(1)
+            };
+            product(&output(0)?).saturating_mul(k.copied().unwrap_or(1))
+        }
+        (domain, "MaxPool" | "AveragePool" | "LpPool") if is_onnx_domain(domain) => {
+            let kernel = node
+                .attribute
+                .iter()
+                .find(|a| a.name == "kernel_shape")
+                .map_or(1, |a| {
+                    let kernel: Vec<u64> = a.ints.iter().map(|k| (*k).max(0) as u64).collect();
+                    product(&kernel)
+                });
+            product(&output(0)?).saturating_mul(kernel)
+        }
+        (domain, "GlobalAveragePool" | "GlobalMaxPool" | "GlobalLpPool")
+            if is_onnx_domain(domain) =>
+        {
+            product(&input(0)?)
+        }
+        ("com.microsoft", "Attention") => {
+            // Packed QKV projection followed by QK^T and the weighted sum of V
+            let x = input(0)?;
+            let weight = input(1)?;
+            let [batch, seq, hidden_in] = x[..] else {
+                return Err(Unresolved::Unknown);
+            };
+            let qkv_hidden = weight.get(1).copied().unwrap_or(0);
+            let hidden = qkv_hidden / 3;
+
+            product(&[batch, seq, hidden_in, qkv_hidden])
+                .saturating_add(product(&[2, batch, seq, seq, hidden]))
+        }
+        ("com.microsoft", "MultiHeadAttention") => {
+            let query = input(0)?;
+            let key = input(1)?;
+            let [batch, seq, hidden] = query[..] else {
+                return Err(Unresolved::Unknown);
+            };
+            // Keys are [B, L, D], [B, N, L, H] or packed with the values as
+            // [B, L, N, 2, H]
+            let kv_seq = match key[..] {
+                [_, kv_seq, _] | [_, _, kv_seq, _] | [_, kv_seq, _, 2, _] => kv_seq,
+                _ => return Err(Unresolved::Unknown),
+            };
+
+            product(&[2, batch, seq, kv_seq, hidden])
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(macs))
+}
+
+impl OnnxModel {
+    /// Estimate multiply-accumulate counts of the compute heavy nodes.
+    ///
+    /// Symbolic dims are resolved through `bindings`, nodes whose shapes
+    /// still depend on unbound or unknown dims are counted as unresolved.
+    pub fn compute_summary(
+        &self,
+        bindings: &HashMap<String, u64>,
+        top: usize,
+    ) -> ComputeSummary<'_> {
+        let resolver = DimResolver { bindings };
+        let mut nodes = Vec::new();
+        let mut unresolved = 0;
+        let mut unbound = BTreeSet::new();
+
+        for node in self.nodes.iter().map(|n| &n.proto) {
+            match node_macs(self, node, &resolver) {
+                Ok(Some(macs)) => nodes.push(NodeCompute {
+                    // Unnamed nodes are identified by their first output
+                    name: match node.name.as_str() {
+                        "" => node.output.first().map_or("", |o| o.as_str()),
+                        name => name,
+                    },
+                    op_type: &node.op_type,
+                    macs,
+                }),
+                Ok(None) => {}
+                Err(Unresolved::Symbol(symbol)) => {
+                    unresolved += 1;
+                    unbound.insert(symbol);
+                }
+                Err(Unresolved::Unknown) => unresolved += 1,
+            }
+        }
+
+        let mut op_types: HashMap<&str, OpTypeCompute> = HashMap::new();
+        for node in nodes.iter() {
+            let usage = op_types.entry(node.op_type).or_insert(OpTypeCompute {
+                op_type: node.op_type,
+                nodes: 0,
+                macs: 0,
+            });
+            usage.nodes += 1;
+            usage.macs = usage.macs.saturating_add(node.macs);
+        }
+
+        let mut op_types: Vec<OpTypeCompute> = op_types.into_values().collect();
+        op_types.sort_by_key(|usage| Reverse(usage.macs));
+        op_types.truncate(top);
+
+        let macs = nodes.iter().fold(0u64, |acc, n| acc.saturating_add(n.macs));
+
+        nodes.sort_by_key(|n| Reverse(n.macs));
+        nodes.truncate(top);
+
+        ComputeSummary {
+            macs,
+            flops: macs.saturating_mul(2),
+            unresolved_nodes: unresolved,
+            unbound_dims: unbound.into_iter().collect(),
+            op_types,
+            nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{load, node, value};
+    use crate::onnx::onnx_proto::{GraphProto, NodeProto};
+
+    #[test]
+    fn saturated_nodes_do_not_overflow_totals() {
+        let huge = 1 << 31;
+        let model = load(GraphProto {
+            node: vec![
+                node("MatMul", &["a", "b"], &["c"]),
+                node("MatMul", &["a", "b"], &["d"]),
+            ],
+            input: vec![value("a", &[huge, huge]), value("b", &[huge, huge])],
+            ..Default::default()
+        });
+
+        let summary = model.compute_summary(&HashMap::new(), 10);
+        assert_eq!(summary.nodes.len(), 2);
+        assert_eq!(summary.macs, u64::MAX);
+        assert_eq!(summary.op_types[0].macs, u64::MAX);
+        assert_eq!(summary.flops, u64::MAX);
+    }
+
+    #[test]
+    fn attention_macs_by_key_layout() {
+        let contrib = |op_type: &str, inputs: &[&str], output: &str| NodeProto {
+            domain: "com.microsoft".to_string(),
+            ..node(op_type, inputs, &[output])
+        };
+        let model = load(GraphProto {
+            node: vec![
+                contrib("Attention", &["x", "qkv"], "packed"),
+                contrib("MultiHeadAttention", &["x", "k3"], "3d"),
+                contrib("MultiHeadAttention", &["x", "k4"], "4d"),
+                contrib("MultiHeadAttention", &["x", "kv5"], "5d"),
+                contrib("MultiHeadAttention", &["x", "k2"], "2d"),
+            ],
+            input: vec![
+                value("x", &[2, 8, 16]),
+                value("qkv", &[16, 48]),
+                value("k3", &[2, 4, 16]),
+                value("k4", &[2, 4, 6, 4]),
+                value("kv5", &[2, 5, 4, 2, 4]),
+                value("k2", &[2, 16]),
+            ],
+            ..Default::default()
+        });
+
+        let summary = model.compute_summary(&HashMap::new(), 10);
+        let mut macs: Vec<(&str, u64)> = summary.nodes.iter().map(|n| (n.name, n.macs)).collect();
+        macs.sort();
+        assert_eq!(
+            macs,
+            vec![
+                ("3d", 2 * 2 * 8 * 4 * 16),
+                ("4d", 2 * 2 * 8 * 6 * 16),
+                ("5d", 2 * 2 * 8 * 5 * 16),
+                ("packed", 2 * 8 * 16 * 48 + 2 * 2 * 8 * 8 * 16),
+            ]
+        );
+        assert_eq!(summary.unresolved_nodes, 1);
+    }
+}
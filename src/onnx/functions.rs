@@ -0,0 +1,440 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::check::{Finding, Location, Severity};
+use crate::onnx::onnx_proto::{AttributeProto, FunctionProto, GraphProto, ModelProto, NodeProto};
+use crate::onnx::summary::{FunctionSummary, OperatorUsage};
+use crate::onnx::OnnxModel;
+
+/// Calls nested deeper than this are left in place
+const MAX_INLINE_DEPTH: usize = 16;
+
+/// Model-local function a node calls, if any
+pub fn find_function<'a>(
+    functions: &'a [FunctionProto],
+    node: &NodeProto,
+) -> Option<&'a FunctionProto> {
+    functions
+        .iter()
+        .find(|f| f.name == node.op_type && f.domain == node.domain)
+}
+
+/// Whether `domain` and `op_type` name a model-local function
+pub fn is_function(functions: &[FunctionProto], domain: &str, op_type: &str) -> bool {
+    functions
+        .iter()
+        .any(|f| f.domain == domain && f.name == op_type)
+}
+
+/// Number of calls of each local function by `(domain, name)`
+pub type FunctionCalls = HashMap<(String, String), usize>;
+
+/// Replace calls to model-local functions with their bodies, in the main
+/// graph and all subgraphs, and return how often each function was inlined.
+///
+/// Recursive calls are left in place and reported, as are operator set
+/// versions of inlined functions that differ from the model's.
+pub fn inline_functions(proto: &mut ModelProto) -> (FunctionCalls, Vec<Finding>) {
+    let mut inliner = Inliner {
+        functions: &proto.functions,
+        calls: HashMap::new(),
+        stack: Vec::new(),
+        recursive: BTreeSet::new(),
+        findings: Vec::new(),
+    };
+
+    let Some(graph) = proto.graph.as_mut() else {
+        return (inliner.calls, inliner.findings);
+    };
+
+    inliner.inline_graph(graph);
+    let calls = inliner.calls;
+    let mut findings = inliner.findings;
+
+    // Body nodes may use operator sets only the function imports
+    let inlined = proto
+        .functions
+        .iter()
+        .filter(|f| calls.contains_key(&(f.domain.clone(), f.name.clone())));
+
+    for function in inlined {
+        for opset in function.opset_import.iter() {
+            match proto.opset_import.iter().find(|o| o.domain == opset.domain) {
+                None => proto.opset_import.push(opset.clone()),
+                Some(imported) if imported.version != opset.version => {
+                    findings.push(Finding {
+                        severity: Severity::Warning,
+                        location: Location::Opset {
+                            domain: opset.domain.clone(),
+                        },
+                        message: format!(
+                            "Inlined function {} imports version {}, the model's version {} is kept",
+                            function.name, opset.version, imported.version
+                        ),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    (calls, findings)
+}
+
+struct Inliner<'a> {
+    functions: &'a [FunctionProto],
+    calls: FunctionCalls,
+    /// Functions whose bodies are being inlined, innermost last
+    stack: Vec<&'a FunctionProto>,
+    /// Functions already reported as recursive, by `(domain, name)`
+    recursive: BTreeSet<(&'a str, &'a str)>,
+    findings: Vec<Finding>,
+}
+
+impl<'a> Inliner<'a> {
+    fn inline_graph(&mut self, graph: &mut GraphProto) {
+        let nodes = std::mem::take(&mut graph.node);
+        graph.node = self.inline_nodes(nodes);
+    }
+
+    /// Whether a call of `function` can be replaced with its body here,
+    /// reporting calls that would recurse
+    fn can_inline(&mut self, function: &'a FunctionProto) -> bool {
+        let Some(first) = self.stack.iter().position(|f| std::ptr::eq(*f, function)) else {
+            return self.stack.len() < MAX_INLINE_DEPTH;
+        };
+
+        if self.recursive.insert((&function.domain, &function.name)) {
+            let cycle: Vec<&str> = self.stack[first..]
+                .iter()
+                .map(|f| f.name.as_str())
+                .chain([function.name.as_str()])
+                .collect();
+
+            self.findings.push(Finding {
+                severity: Severity::Warning,
+                location: Location::Model,
+                message: format!(
+                    "Function {} is recursive ({}), its recursive calls are not inlined",
+                    function.name,
+                    cycle.join(" -> ")
+                ),
+            });
+        }
+
+        false
+    }
+
+    fn inline_nodes(&mut self, nodes: Vec<NodeProto>) -> Vec<NodeProto> {
+        let mut inlined = Vec::with_capacity(nodes.len());
+
+        for mut node in nodes {
+            let function = find_function(self.functions, &node).filter(|f| self.can_inline(f));
+
+            match function {
+                Some(function) => {
+                    *self
+                        .calls
+                        .entry((function.domain.clone(), function.name.clone()))
+                        .or_default() += 1;
+
+                    let body = instantiate(function, &node);
+                    self.stack.push(function);
+                    inlined.extend(self.inline_nodes(body));
+                    self.stack.pop();
+                }
+                None => {
+                    for attr in node.attribute.iter_mut() {
+                        for graph in attr.g.iter_mut().chain(attr.graphs.iter_mut()) {
+                            self.inline_graph(graph);
+                        }
+                    }
+
+                    inlined.push(node);
+                }
+            }
+        }
+
+        inlined
+    }
+}
+
+/// Body of `function` as called by `node`, with formal parameters replaced by
+/// the node's inputs, outputs and attributes.
+///
+/// Values local to the body are prefixed with the node's name so calls do not
+/// clash with each other or the calling graph.
+fn instantiate(function: &FunctionProto, node: &NodeProto) -> Vec<NodeProto> {
+    let scope = match node.name.as_str() {
+        "" => node
+            .output
+            .first()
+            .map_or(function.name.as_str(), |o| o.as_str()),
+        name => name,
+    };
+
+    let mut renames: HashMap<&str, String> = HashMap::new();
+
+    for (index, formal) in function.input.iter().enumerate() {
+        renames.insert(formal, node.input.get(index).cloned().unwrap_or_default());
+    }
+
+    for (index, formal) in function.output.iter().enumerate() {
+        renames.insert(formal, node.output.get(index).cloned().unwrap_or_default());
+    }
+
+    for body_node in function.node.iter() {
+        for output in body_node.output.iter().filter(|o| !o.is_empty()) {
+            renames
+                .entry(output)
+                .or_insert_with(|| format!("{}/{}", scope, output));
+        }
+    }
+
+    function
+        .node
+        .iter()
+        .map(|body_node| {
+            let mut body_node = body_node.clone();
+
+            if !body_node.name.is_empty() {
+                body_node.name = format!("{}/{}", scope, body_node.name);
+            }
+
+            rename_values(&mut body_node, &renames);
+
+            body_node.attribute = body_node
+                .attribute
+                .into_iter()
+                .filter_map(|attr| bind_attribute(attr, function, node))
+                .collect();
+
+            body_node
+        })
+        .collect()
+}
+
+fn rename_values(node: &mut NodeProto, renames: &HashMap<&str, String>) {
+    for name in node.input.iter_mut().chain(node.output.iter_mut()) {
+        if let Some(renamed) = renames.get(name.as_str()) {
+            *name = renamed.clone();
+        }
+    }
+
+    // Subgraphs of body nodes may capture values of the function body
+    for attr in node.attribute.iter_mut() {
+        for graph in attr.g.iter_mut().chain(attr.graphs.iter_mut()) {
+            for graph_node in graph.node.iter_mut() {
+                rename_values(graph_node, renames);
+            }
+        }
+    }
+}
+
+/// Resolve an attribute referencing one of the function's attributes, `None`
+/// when the caller does not set it and the function has no default
+fn bind_attribute(
+    attr: AttributeProto,
+    function: &FunctionProto,
+    node: &NodeProto,
+) -> Option<AttributeProto> {
+    if attr.ref_attr_name.is_empty() {
+        return Some(attr);
+    }
+
+    let value = node
+        .attribute
+        .iter()
+        .chain(function.attribute_proto.iter())
+        .find(|a| a.name == attr.ref_attr_name)?;
+
+    Some(AttributeProto {
+        name: attr.name,
+        ..value.clone()
+    })
+}
+
+impl OnnxModel {
+    /// Rebuild the model with calls to local functions replaced by their
+    /// bodies
+    pub fn inline_functions(self) -> Self {
+        let mut proto = self.proto;
+        let (calls, findings) = inline_functions(&mut proto);
+
+        let mut model = OnnxModel::from_proto(proto);
+        model.inlined_calls = calls;
+        model.diagnostics.extend(findings);
+        model
+    }
+
+    /// Local functions with the operators used in their bodies
+    pub fn function_summaries(&self) -> Vec<FunctionSummary<'_>> {
+        let calling_nodes = self
+            .nodes
+            .iter()
+            .map(|n| &n.proto)
+            .chain(self.subgraphs.iter().flat_map(|s| s.nodes()));
+        let mut calls: HashMap<(&str, &str), usize> = HashMap::new();
+
+        for node in calling_nodes {
+            if let Some(function) = find_function(&self.proto.functions, node) {
+                *calls.entry((&function.domain, &function.name)).or_default() += 1;
+            }
+        }
+
+        self.proto
+            .functions
+            .iter()
+            .map(|function| {
+                let key = (function.domain.as_str(), function.name.as_str());
+                let inlined = self
+                    .inlined_calls
+                    .get(&(function.domain.clone(), function.name.clone()))
+                    .copied()
+                    .unwrap_or(0);
+
+                FunctionSummary {
+                    domain: &function.domain,
+                    name: &function.name,
+                    inputs: function.input.iter().map(|i| i.as_str()).collect(),
+                    outputs: function.output.iter().map(|o| o.as_str()).collect(),
+                    attributes: function
+                        .attribute
+                        .iter()
+                        .map(|a| a.as_str())
+                        .chain(function.attribute_proto.iter().map(|a| a.name.as_str()))
+                        .collect(),
+                    calls: calls.get(&key).copied().unwrap_or(0),
+                    inlined_calls: inlined,
+                    operators: function_operators(function, &self.proto.functions),
+                }
+            })
+            .collect()
+    }
+}
+
+fn function_operators<'a>(
+    function: &'a FunctionProto,
+    functions: &[FunctionProto],
+) -> Vec<OperatorUsage<'a>> {
+    let mut node_counts: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for node in function.node.iter() {
+        *node_counts
+            .entry((&node.domain, &node.op_type))
+            .or_default() += 1;
+    }
+
+    let mut operators: Vec<OperatorUsage> = node_counts
+        .into_iter()
+        .map(|((domain, name), count)| OperatorUsage {
+            domain: if domain.is_empty() { "ai.onnx" } else { domain },
+            name,
+            count,
+            nested_count: 0,
+            function: is_function(functions, domain, name),
+        })
+        .collect();
+
+    operators.sort_by_key(|op| Reverse(op.count));
+
+    operators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{model, node};
+    use crate::onnx::onnx_proto::OperatorSetIdProto;
+
+    fn opset(domain: &str, version: i64) -> OperatorSetIdProto {
+        OperatorSetIdProto {
+            domain: domain.to_string(),
+            version,
+        }
+    }
+
+    fn function(
+        name: &str,
+        body: Vec<NodeProto>,
+        opsets: Vec<OperatorSetIdProto>,
+    ) -> FunctionProto {
+        FunctionProto {
+            name: name.to_string(),
+            domain: "local".to_string(),
+            input: vec!["x".to_string()],
+            output: vec!["y".to_string()],
+            node: body,
+            opset_import: opsets,
+            ..Default::default()
+        }
+    }
+
+    fn call(name: &str, input: &str, output: &str) -> NodeProto {
+        NodeProto {
+            domain: "local".to_string(),
+            ..node(name, &[input], &[output])
+        }
+    }
+
+    #[test]
+    fn recursive_calls_are_left_in_place() {
+        let body = vec![
+            call("F", "x", "a"),
+            call("F", "a", "b"),
+            call("F", "b", "c"),
+            call("F", "c", "y"),
+        ];
+        let mut proto = ModelProto {
+            functions: vec![function("F", body, Vec::new())],
+            ..model(GraphProto {
+                node: vec![call("F", "in", "out")],
+                ..Default::default()
+            })
+        };
+
+        let (calls, findings) = inline_functions(&mut proto);
+
+        assert_eq!(calls[&("local".to_string(), "F".to_string())], 1);
+        let graph = proto.graph.unwrap();
+        assert_eq!(graph.node.len(), 4);
+        assert!(graph.node.iter().all(|n| n.op_type == "F"));
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("(F -> F)"));
+    }
+
+    #[test]
+    fn opsets_of_inlined_functions_are_merged() {
+        let used = function(
+            "Used",
+            vec![node("Relu", &["x"], &["y"])],
+            vec![opset("", 17), opset("custom", 2)],
+        );
+        let unused = function(
+            "Unused",
+            vec![node("Relu", &["x"], &["y"])],
+            vec![opset("other", 1)],
+        );
+        let mut proto = ModelProto {
+            functions: vec![used, unused],
+            opset_import: vec![opset("", 18)],
+            ..model(GraphProto {
+                node: vec![call("Used", "in", "out")],
+                ..Default::default()
+            })
+        };
+
+        let (_, findings) = inline_functions(&mut proto);
+
+        let opsets: Vec<(&str, i64)> = proto
+            .opset_import
+            .iter()
+            .map(|o| (o.domain.as_str(), o.version))
+            .collect();
+        assert_eq!(opsets, vec![("", 18), ("custom", 2)]);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0]
+            .message
+            .contains("version 17, the model's version 18"));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::onnx::onnx_proto::{GraphProto, StringStringEntryProto};
+use crate::onnx::summary::{
+    MetadataEntry, NodeDocString, OperatorCount, TrainingGraph, TrainingInfo,
+};
+use crate::onnx::OnnxModel;
+
+fn entries(props: &[StringStringEntryProto]) -> Vec<MetadataEntry<'_>> {
+    props
+        .iter()
+        .map(|entry| MetadataEntry {
+            key: &entry.key,
+            value: &entry.value,
+        })
+        .collect()
+}
+
+fn training_graph(graph: &GraphProto) -> TrainingGraph<'_> {
+    let mut operators: Vec<OperatorCount> = Vec::new();
+
+    for node in graph.node.iter() {
+        match operators.iter_mut().find(|o| o.op_type == node.op_type) {
+            Some(operator) => operator.count += 1,
+            None => operators.push(OperatorCount {
+                op_type: &node.op_type,
+                count: 1,
+            }),
+        }
+    }
+
+    TrainingGraph {
+        name: &graph.name,
+        doc_string: &graph.doc_string,
+        nodes: graph.node.len(),
+        initializers: graph.initializer.len(),
+        inputs: graph.input.iter().map(|i| i.name.as_str()).collect(),
+        outputs: graph.output.iter().map(|o| o.name.as_str()).collect(),
+        operators,
+    }
+}
+
+impl OnnxModel {
+    /// Key/value pairs from `metadata_props`, in file order
+    pub fn metadata(&self) -> Vec<MetadataEntry<'_>> {
+        entries(&self.proto.metadata_props)
+    }
+
+    /// Main graph nodes carrying a doc string
+    pub fn node_doc_strings(&self) -> Vec<NodeDocString<'_>> {
+        self.graph_proto()
+            .node
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.doc_string.is_empty())
+            .map(|(index, node)| NodeDocString {
+                index,
+                name: &node.name,
+                op_type: &node.op_type,
+                doc_string: &node.doc_string,
+            })
+            .collect()
+    }
+
+    /// Training algorithm and initialization graphs of the model
+    pub fn training_info(&self) -> Vec<TrainingInfo<'_>> {
+        self.proto
+            .training_info
+            .iter()
+            .map(|info| TrainingInfo {
+                algorithm: info.algorithm.as_ref().map(training_graph),
+                initialization: info.initialization.as_ref().map(training_graph),
+                initialization_binding: entries(&info.initialization_binding),
+                update_binding: entries(&info.update_binding),
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,3 @@
-use petgraph::dot::{self, Dot};
-
 use petgraph::prelude::DiGraphMap;
 use prost::Message;
 use std::cmp::Reverse;
@@ -11,12 +9,39 @@ pub mod onnx_proto {
     include!(concat!(env!("OUT_DIR"), "/onnx.rs"));
 }
 
+pub mod blocks;
+pub mod check;
+pub mod data;
+pub mod diagnostics;
+pub mod diff;
+pub mod dot;
+pub mod external;
+pub mod extract;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod flops;
+pub mod functions;
+pub mod metadata;
+pub mod modules;
+pub mod node;
+pub mod quant;
+pub mod query;
+pub mod shape;
+pub mod subgraph;
 pub mod summary;
+pub mod weights;
 
 use onnx_proto::{NodeProto, TypeProto, ValueInfoProto};
 
+use crate::check::Finding;
+use crate::diff::DiffSection;
 use crate::model::Model;
-use crate::summary::Summary;
+use crate::summary::{Summary, SummaryOptions};
+use crate::tensor::TensorData;
+use dot::OnnxDot;
+use external::ExternalData;
+use functions::FunctionCalls;
+use subgraph::{Subgraph, SubgraphCollector};
 use summary::{OnnxSummary, OperatorUsage, OperatorUsageSummary};
 
 type ValueId = usize;
@@ -36,8 +61,14 @@ impl<T> IdMapper<T> {
         id
     }
 
-    fn get_by_name_mut(&mut self, name: &str) -> Option<&mut T> {
-        self.mapping.get(name).map(|id| &mut self.values[*id])
+    fn get_or_insert_with<F>(&mut self, name: &str, f: F) -> usize
+    where
+        F: FnOnce(&str) -> T,
+    {
+        match self.get_id_by_name(name) {
+            Some(id) => id,
+            None => self.insert(name, f(name)),
+        }
     }
 
     fn new() -> Self {
@@ -54,6 +85,10 @@ impl<T> IdMapper<T> {
     fn get_by_id(&self, value_id: usize) -> &T {
         &self.values[value_id]
     }
+
+    fn get_by_id_mut(&mut self, value_id: usize) -> &mut T {
+        &mut self.values[value_id]
+    }
 }
 
 pub enum ValueSource {
@@ -64,15 +99,76 @@ pub enum ValueSource {
 pub struct ValueInfo {
     pub proto: ValueInfoProto,
     pub source: Option<ValueSource>,
+    /// Type found by shape inference when none with a shape is declared
+    pub inferred: Option<TypeProto>,
 }
 
+/// Type of values that no value_info, graph input or output declares
+static UNKNOWN_TYPE: TypeProto = TypeProto {
+    value: None,
+    denotation: String::new(),
+};
+
 impl ValueInfo {
+    /// Value with no declared type and no known source
+    fn unknown(name: &str) -> Self {
+        ValueInfo {
+            proto: ValueInfoProto {
+                name: name.to_string(),
+                ..Default::default()
+            },
+            source: None,
+            inferred: None,
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.proto.name
     }
 
-    pub fn type_info(&self) -> TypeInfo {
-        TypeInfo(self.proto.r#type.as_ref().unwrap())
+    /// Declared type, or the inferred one when the declaration has no shape
+    pub fn type_proto(&self) -> Option<&TypeProto> {
+        self.inferred.as_ref().or(self.proto.r#type.as_ref())
+    }
+
+    pub fn type_info(&self) -> TypeInfo<'_> {
+        TypeInfo(self.type_proto().unwrap_or(&UNKNOWN_TYPE))
+    }
+}
+
+/// Graph of models that have none, which loading reports as an error
+static EMPTY_GRAPH: onnx_proto::GraphProto = onnx_proto::GraphProto {
+    node: Vec::new(),
+    name: String::new(),
+    initializer: Vec::new(),
+    sparse_initializer: Vec::new(),
+    doc_string: String::new(),
+    input: Vec::new(),
+    output: Vec::new(),
+    value_info: Vec::new(),
+    quantization_annotation: Vec::new(),
+};
+
+/// Tensor type with a fully static shape such as the one of an initializer
+fn tensor_type(elem_type: i32, dims: &[i64]) -> TypeProto {
+    let dim = dims
+        .iter()
+        .map(|d| onnx_proto::tensor_shape_proto::Dimension {
+            value: Some(onnx_proto::tensor_shape_proto::dimension::Value::DimValue(
+                *d,
+            )),
+            denotation: String::new(),
+        })
+        .collect();
+
+    TypeProto {
+        value: Some(onnx_proto::type_proto::Value::TensorType(
+            onnx_proto::type_proto::Tensor {
+                elem_type,
+                shape: Some(onnx_proto::TensorShapeProto { dim }),
+            },
+        )),
+        denotation: String::new(),
     }
 }
 
@@ -107,48 +203,64 @@ impl fmt::Display for DataTypeDisplay {
     }
 }
 
+/// Display name of a raw `TensorProto.data_type` value
+pub fn data_type_name(data_type: i32) -> String {
+    match onnx_proto::tensor_proto::DataType::from_i32(data_type) {
+        Some(data_type) => DataTypeDisplay(data_type).to_string(),
+        None => format!("dtype({})", data_type),
+    }
+}
+
+/// Whether a node domain refers to the default `ai.onnx` operator set
+pub fn is_onnx_domain(domain: &str) -> bool {
+    domain.is_empty() || domain == "ai.onnx"
+}
+
+/// Dims of a tensor shape like `[batch,3,?]`, nothing for scalars and
+/// unknown shapes
+fn fmt_shape(
+    f: &mut fmt::Formatter<'_>,
+    shape: Option<&onnx_proto::TensorShapeProto>,
+) -> fmt::Result {
+    let Some(shape) = shape.filter(|shape| !shape.dim.is_empty()) else {
+        return Ok(());
+    };
+
+    write!(f, "[")?;
+
+    let mut dim_iter = shape.dim.iter().peekable();
+
+    while let Some(d) = dim_iter.next() {
+        match d.value.as_ref() {
+            Some(onnx_proto::tensor_shape_proto::dimension::Value::DimValue(val)) => {
+                write!(f, "{}", val)?
+            }
+            Some(onnx_proto::tensor_shape_proto::dimension::Value::DimParam(name)) => {
+                write!(f, "{}", name)?
+            }
+            None => write!(f, "?")?,
+        }
+
+        if dim_iter.peek().is_some() {
+            write!(f, ",")?;
+        }
+    }
+
+    write!(f, "]")
+}
+
 pub struct TypeInfo<'a>(&'a TypeProto);
 
 impl<'a> fmt::Display for TypeInfo<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0.value.as_ref().unwrap() {
-            onnx_proto::type_proto::Value::TensorType(tensor) => {
-                let elem_type =
-                    onnx_proto::tensor_proto::DataType::from_i32(tensor.elem_type).unwrap();
-
-                write!(f, "{}", DataTypeDisplay(elem_type))?;
-
-                let empty_shape = Vec::new();
-                let shape = tensor
-                    .shape
-                    .as_ref()
-                    .map_or(&empty_shape, |shape| &shape.dim);
-
-                if !shape.is_empty() {
-                    write!(f, "[")?;
-
-                    let mut dim_iter = tensor.shape.as_ref().unwrap().dim.iter().peekable();
-
-                    while let Some(d) = dim_iter.next() {
-                        match d.value.as_ref() {
-                            Some(onnx_proto::tensor_shape_proto::dimension::Value::DimValue(
-                                val,
-                            )) => write!(f, "{}", val)?,
-                            Some(onnx_proto::tensor_shape_proto::dimension::Value::DimParam(
-                                name,
-                            )) => write!(f, "{}", name)?,
-                            None => write!(f, "?")?,
-                        }
-
-                        if dim_iter.peek().is_some() {
-                            write!(f, ",")?;
-                        }
-                    }
-
-                    write!(f, "]")?;
-                }
+        let Some(value) = self.0.value.as_ref() else {
+            return write!(f, "?");
+        };
 
-                Ok(())
+        match value {
+            onnx_proto::type_proto::Value::TensorType(tensor) => {
+                write!(f, "{}", data_type_name(tensor.elem_type))?;
+                fmt_shape(f, tensor.shape.as_ref())
             }
             onnx_proto::type_proto::Value::SequenceType(seq) => {
                 write!(f, "sequence<")?;
@@ -162,11 +274,7 @@ impl<'a> fmt::Display for TypeInfo<'a> {
                 write!(f, ">")
             }
             onnx_proto::type_proto::Value::MapType(map) => {
-                let key = DataTypeDisplay(
-                    onnx_proto::tensor_proto::DataType::from_i32(map.key_type).unwrap(),
-                );
-
-                write!(f, "map<{},", key)?;
+                write!(f, "map<{},", data_type_name(map.key_type))?;
 
                 if let Some(elem) = map.value_type.as_deref() {
                     write!(f, "{}", TypeInfo(elem))?;
@@ -185,7 +293,11 @@ impl<'a> fmt::Display for TypeInfo<'a> {
                 }
                 write!(f, ">")
             }
-            onnx_proto::type_proto::Value::SparseTensorType(_) => todo!(),
+            onnx_proto::type_proto::Value::SparseTensorType(tensor) => {
+                write!(f, "sparse<{}", data_type_name(tensor.elem_type))?;
+                fmt_shape(f, tensor.shape.as_ref())?;
+                write!(f, ">")
+            }
         }
     }
 }
@@ -198,10 +310,15 @@ pub struct OnnxModel {
     pub proto: onnx_proto::ModelProto,
     values: IdMapper<ValueInfo>,
     nodes: Vec<NodeInfo>,
-    #[allow(dead_code)]
     node_graph: DiGraphMap<usize, usize>,
     inputs: Vec<ValueId>,
     outputs: Vec<ValueId>,
+    subgraphs: Vec<Subgraph>,
+    /// Calls to local functions replaced by their bodies
+    inlined_calls: FunctionCalls,
+    external_data: HashMap<InitId, ExternalData>,
+    /// Problems found while loading
+    diagnostics: Vec<Finding>,
 }
 
 impl<'a> From<&'a ValueInfo> for summary::Value<'a> {
@@ -215,7 +332,7 @@ impl<'a> From<&'a ValueInfo> for summary::Value<'a> {
 
 impl OnnxModel {
     pub fn graph_proto(&self) -> &onnx_proto::GraphProto {
-        self.proto.graph.as_ref().unwrap()
+        self.proto.graph.as_ref().unwrap_or(&EMPTY_GRAPH)
     }
 
     pub fn inputs(&self) -> impl Iterator<Item = &ValueInfo> {
@@ -232,6 +349,58 @@ impl OnnxModel {
             .map(|value_id| self.values.get_by_id(value_id))
     }
 
+    /// How many values have a declared, inferred or unknown type
+    pub fn value_types(&self) -> summary::ValueTypeSummary {
+        let mut value_types = summary::ValueTypeSummary::default();
+
+        for value in self.values.values.iter() {
+            if value.inferred.is_some() {
+                value_types.inferred += 1;
+            } else if value.proto.r#type.is_some() {
+                value_types.declared += 1;
+            } else {
+                value_types.unknown += 1;
+            }
+        }
+
+        value_types
+    }
+
+    /// Operator counts over the main graph and, separately, all subgraphs
+    pub fn operator_usage(&self) -> OperatorUsageSummary<'_> {
+        // Counts of main graph and nested subgraph nodes per operator
+        let mut node_counts: HashMap<(&str, &str), (usize, usize)> = HashMap::new();
+
+        for node in self.nodes.iter() {
+            let count = node_counts
+                .entry((&node.proto.domain, node.proto.op_type.as_str()))
+                .or_default();
+            count.0 += 1;
+        }
+
+        for node in self.subgraphs.iter().flat_map(|s| s.nodes()) {
+            let count = node_counts
+                .entry((&node.domain, node.op_type.as_str()))
+                .or_default();
+            count.1 += 1;
+        }
+
+        let mut operators: Vec<OperatorUsage> = node_counts
+            .into_iter()
+            .map(|((domain, name), (count, nested_count))| OperatorUsage {
+                domain: if domain.is_empty() { "ai.onnx" } else { domain },
+                name,
+                count,
+                nested_count,
+                function: functions::is_function(&self.proto.functions, domain, name),
+            })
+            .collect();
+
+        operators.sort_by_key(|op| Reverse(op.count + op.nested_count));
+
+        OperatorUsageSummary { operators }
+    }
+
     pub fn from_bytes<B>(model_bytes: B) -> anyhow::Result<Self>
     where
         B: prost::bytes::Buf,
@@ -244,7 +413,8 @@ impl OnnxModel {
     }
 
     fn from_proto(proto: onnx_proto::ModelProto) -> Self {
-        let model_graph = proto.graph.as_ref().expect("Model must have graph");
+        let diagnostics = diagnostics::load_diagnostics(&proto);
+        let model_graph = proto.graph.as_ref().unwrap_or(&EMPTY_GRAPH);
 
         let mut values = IdMapper::new();
         let mut nodes = Vec::<NodeInfo>::new();
@@ -252,6 +422,10 @@ impl OnnxModel {
         let mut outputs = Vec::new();
 
         let mut node_graph: DiGraphMap<usize, usize> = DiGraphMap::new();
+        let mut subgraphs = SubgraphCollector {
+            subgraphs: Vec::new(),
+        };
+        let mut implicit_inputs = Vec::new();
 
         let init_map: HashMap<&str, InitId> = model_graph
             .initializer
@@ -266,6 +440,7 @@ impl OnnxModel {
                 ValueInfo {
                     proto: value_info.clone(),
                     source: None,
+                    inferred: None,
                 },
             );
         }
@@ -276,25 +451,29 @@ impl OnnxModel {
                 .copied()
                 .map(ValueSource::Initializer);
 
-            let value_id = values.insert(
-                &graph_input.name,
-                ValueInfo {
-                    proto: graph_input.clone(),
-                    source,
-                },
-            );
+            let value_id = values.get_or_insert_with(&graph_input.name, ValueInfo::unknown);
+            let value_info = values.get_by_id_mut(value_id);
+            value_info.proto = graph_input.clone();
+            value_info.source = source;
 
             inputs.push(value_id);
         }
 
+        // Since IR version 4 initializers do not need to be listed as graph inputs
+        for (init_id, init) in model_graph.initializer.iter().enumerate() {
+            let value_id = values.get_or_insert_with(&init.name, ValueInfo::unknown);
+            let value_info = values.get_by_id_mut(value_id);
+
+            if value_info.proto.r#type.is_none() {
+                value_info.proto.r#type = Some(tensor_type(init.data_type, &init.dims));
+            }
+
+            value_info.source = Some(ValueSource::Initializer(init_id));
+        }
+
         for graph_output in model_graph.output.iter() {
-            let value_id = values.insert(
-                &graph_output.name,
-                ValueInfo {
-                    proto: graph_output.clone(),
-                    source: None,
-                },
-            );
+            let value_id = values.get_or_insert_with(&graph_output.name, ValueInfo::unknown);
+            values.get_by_id_mut(value_id).proto = graph_output.clone();
 
             outputs.push(value_id);
         }
@@ -308,19 +487,28 @@ impl OnnxModel {
 
             node_graph.add_node(node_id);
 
-            // for input in node.input.iter() {
+            // Register every tensor the node touches, even without value_info,
+            // so connectivity does not depend on what the exporter declared
+            for input in node.input.iter().filter(|i| !i.is_empty()) {
+                values.get_or_insert_with(input, ValueInfo::unknown);
+            }
 
-            // }
+            for output in node.output.iter().filter(|o| !o.is_empty()) {
+                let value_id = values.get_or_insert_with(output, ValueInfo::unknown);
+                values.get_by_id_mut(value_id).source = Some(ValueSource::Node(node_index));
+            }
 
-            for input in node.output.iter() {
-                if let Some(value_info) = values.get_by_name_mut(input) {
-                    value_info.source = Some(ValueSource::Node(node_index));
-                }
+            // Values a control flow node's subgraphs capture from the main
+            // graph are inputs of that node as well
+            let outer_refs = subgraphs.add_node(None, node_index, node, 0);
+            for outer_ref in outer_refs.iter() {
+                values.get_or_insert_with(outer_ref, ValueInfo::unknown);
             }
+            implicit_inputs.push(outer_refs);
         }
 
         for (node_index, node) in model_graph.node.iter().enumerate() {
-            for input in node.input.iter() {
+            for input in node.input.iter().chain(implicit_inputs[node_index].iter()) {
                 if let Some(value_id) = values.get_id_by_name(input) {
                     let value_info = values.get_by_id(value_id);
 
@@ -336,65 +524,38 @@ impl OnnxModel {
             }
         }
 
-        OnnxModel {
+        let mut model = OnnxModel {
             proto,
             inputs,
             outputs,
             values,
             nodes,
             node_graph,
-        }
-    }
+            subgraphs: subgraphs.subgraphs,
+            inlined_calls: HashMap::new(),
+            external_data: HashMap::new(),
+            diagnostics,
+        };
 
-    // TODO: Add back dot visualization
-    #[allow(dead_code)]
-    fn to_dot(&self) -> impl fmt::Display {
-        let dot_config = [dot::Config::NodeNoLabel];
+        model.infer_shapes();
 
-        let node_attr_getter =
-            |_g, n: (usize, &usize)| format!("label = \"{}\"", &self.nodes[*n.1].proto.name);
-
-        format!(
-            "{}",
-            Dot::with_attr_getters(
-                &self.node_graph,
-                &dot_config,
-                &|_g, _e| String::new(),
-                &node_attr_getter,
-            )
-        )
+        model
     }
 }
 
 impl Model for OnnxModel {
-    fn summary<'a>(&'a self, _filename: Option<&'a str>) -> Box<dyn Summary + 'a> {
-        let mut node_counts = HashMap::new();
-
-        for node in self.nodes.iter() {
-            let count = node_counts
-                .entry((&node.proto.domain, node.proto.op_type.as_str()))
-                .or_default();
-            *count += 1;
-        }
-
-        let mut operators: Vec<OperatorUsage> = node_counts
-            .into_iter()
-            .map(|((domain, name), count)| OperatorUsage {
-                domain: if domain.is_empty() { "ai.onnx" } else { domain },
-                name,
-                count,
-            })
-            .collect();
-
-        operators.sort_by_key(|op| Reverse(op.count));
-
-        let operator_summary = OperatorUsageSummary { operators };
-
+    fn summary<'a>(
+        &'a self,
+        _filename: Option<&'a str>,
+        options: &SummaryOptions,
+    ) -> Box<dyn Summary + 'a> {
         Box::new(OnnxSummary {
             domain: &self.proto.domain,
             name: &self.graph_proto().name,
             version: self.proto.model_version,
             doc_string: &self.proto.doc_string,
+            graph_doc_string: &self.graph_proto().doc_string,
+            metadata: self.metadata(),
             producer_name: &self.proto.producer_name,
             producer_version: &self.proto.producer_version,
             ir_version: self.proto.ir_version,
@@ -418,7 +579,120 @@ impl Model for OnnxModel {
                 .map(summary::Value::from)
                 .collect(),
             outputs: self.outputs().map(summary::Value::from).collect(),
-            operator_summary,
+            operator_summary: self.operator_usage(),
+            initializers: self.initializer_weights(options.top),
+            constants: self.constant_weights(options.top),
+            external_files: self.external_files(),
+            value_types: self.value_types(),
+            compute: self.compute_summary(&options.dims, options.top),
+            subgraphs: self.subgraph_summary(),
+            functions: self.function_summaries(),
+            node_doc_strings: self.node_doc_strings(),
+            training_info: self.training_info(),
+            quantization: self.quantization_summary(),
+            blocks: self.repeated_blocks(),
+            modules: self.module_tree(),
+            module_depth: options.module_depth,
+            top: options.top,
+            diagnostics: &self.diagnostics,
+        })
+    }
+
+    fn dot(&self, options: &SummaryOptions) -> Option<Box<dyn fmt::Display + '_>> {
+        Some(Box::new(OnnxDot::new(self, options.collapse_depth)))
+    }
+
+    fn format_name(&self) -> &'static str {
+        "onnx"
+    }
+
+    fn check(&self) -> Option<Vec<Finding>> {
+        Some(OnnxModel::check(self))
+    }
+
+    fn diff_sections(&self) -> Vec<DiffSection> {
+        OnnxModel::diff_sections(self)
+    }
+
+    fn tensor_names(&self) -> Vec<&str> {
+        OnnxModel::tensor_names(self)
+    }
+
+    fn tensor_data(&self, name: &str) -> anyhow::Result<TensorData<'_>> {
+        OnnxModel::tensor_data(self, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::{Location, Severity};
+    use fixtures::{ints, load, node, value};
+    use onnx_proto::GraphProto;
+
+    /// Model whose graph is a single node over the given inputs
+    fn single_node_model(node: NodeProto, inputs: Vec<ValueInfoProto>) -> OnnxModel {
+        load(GraphProto {
+            node: vec![node],
+            input: inputs,
+            ..Default::default()
         })
     }
+
+    #[test]
+    fn conv_with_zero_strides_loads_with_diagnostic() {
+        let model = single_node_model(
+            NodeProto {
+                attribute: vec![ints("strides", &[0, 0])],
+                ..node("Conv", &["x", "w"], &["y"])
+            },
+            vec![value("x", &[1, 3, 8, 8]), value("w", &[4, 3, 3, 3])],
+        );
+
+        let finding = model
+            .check()
+            .into_iter()
+            .find(|f| matches!(f.location, Location::Node { index: 0, .. }))
+            .expect("stride finding");
+        assert_eq!(finding.severity, Severity::Error);
+        assert!(finding.message.contains("Strides"));
+    }
+
+    #[test]
+    fn split_without_outputs_loads() {
+        let model = single_node_model(node("Split", &["x"], &[]), vec![value("x", &[6])]);
+
+        assert_eq!(model.nodes.len(), 1);
+    }
+
+    #[test]
+    fn slice_with_missing_starts_loads() {
+        let model = single_node_model(
+            NodeProto {
+                attribute: vec![
+                    ints("axes", &[0, 1]),
+                    ints("starts", &[0]),
+                    ints("ends", &[1, 1]),
+                ],
+                ..node("Slice", &["x"], &["y"])
+            },
+            vec![value("x", &[4, 4])],
+        );
+
+        let y = model.values.get_id_by_name("y").unwrap();
+        assert!(model.values.get_by_id(y).inferred.is_none());
+    }
+
+    #[test]
+    fn crafted_dims_load() {
+        let model = single_node_model(
+            NodeProto {
+                attribute: vec![ints("kernel_shape", &[i64::MIN])],
+                ..node("MaxPool", &["x"], &["y"])
+            },
+            vec![value("x", &[1, 1, i64::MAX])],
+        );
+
+        assert_eq!(model.nodes.len(), 1);
+    }
 }
@@ -0,0 +1,154 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::onnx::blocks::operator_counts;
+use crate::onnx::summary::ModuleNode;
+use crate::onnx::{NodeId, OnnxModel, ValueSource};
+
+/// Module path of an exporter node name, `/encoder/layer.0/attn/MatMul` is
+/// in `encoder`, `layer.0`, `attn`
+pub fn scope_path(node_name: &str) -> Vec<&str> {
+    let mut components: Vec<&str> = node_name.split('/').collect();
+    // The last component names the node itself
+    components.pop();
+    components.retain(|c| !c.is_empty());
+    components
+}
+
+/// Modules before aggregation, nodes are only those directly in the module
+struct Scope<'a> {
+    name: &'a str,
+    nodes: Vec<NodeId>,
+    children: Vec<Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn insert(&mut self, path: &[&'a str], node_id: NodeId) {
+        let Some((first, rest)) = path.split_first() else {
+            self.nodes.push(node_id);
+            return;
+        };
+
+        let position = match self.children.iter().position(|c| c.name == *first) {
+            Some(position) => position,
+            None => {
+                self.children.push(Scope {
+                    name: first,
+                    nodes: Vec::new(),
+                    children: Vec::new(),
+                });
+                self.children.len() - 1
+            }
+        };
+
+        self.children[position].insert(rest, node_id);
+    }
+}
+
+/// Where tensors are used, to find the outputs leaving a module
+struct Uses<'a> {
+    consumers: HashMap<&'a str, Vec<NodeId>>,
+    graph_outputs: HashSet<&'a str>,
+}
+
+impl<'a> Uses<'a> {
+    fn used_outside(&self, tensor: &str, nodes: &HashSet<NodeId>) -> bool {
+        self.graph_outputs.contains(tensor)
+            || self
+                .consumers
+                .get(tensor)
+                .is_some_and(|c| c.iter().any(|node_id| !nodes.contains(node_id)))
+    }
+}
+
+impl OnnxModel {
+    fn module_node<'a>(&'a self, scope: Scope<'a>, path: String, uses: &Uses) -> ModuleNode<'a> {
+        let children: Vec<ModuleNode> = scope
+            .children
+            .into_iter()
+            .map(|child| {
+                let child_path = format!("{}/{}", path.trim_end_matches('/'), child.name);
+                self.module_node(child, child_path, uses)
+            })
+            .collect();
+
+        let mut nodes: Vec<NodeId> = scope.nodes;
+        nodes.extend(children.iter().flat_map(|c| c.node_ids.iter().copied()));
+        nodes.sort();
+
+        let node_set: HashSet<NodeId> = nodes.iter().copied().collect();
+        let produced: HashSet<&str> = nodes
+            .iter()
+            .flat_map(|node_id| self.nodes[*node_id].proto.output.iter())
+            .map(|o| o.as_str())
+            .collect();
+
+        let mut initializers = BTreeSet::new();
+        let mut inputs: Vec<&str> = Vec::new();
+        let mut outputs: Vec<&str> = Vec::new();
+
+        for node_id in nodes.iter() {
+            let node = &self.nodes[*node_id].proto;
+
+            for input in node.input.iter().filter(|i| !i.is_empty()) {
+                let source = self
+                    .values
+                    .get_id_by_name(input)
+                    .and_then(|value_id| self.values.get_by_id(value_id).source.as_ref());
+
+                match source {
+                    Some(ValueSource::Initializer(init_id)) => {
+                        initializers.insert(*init_id);
+                    }
+                    _ if produced.contains(input.as_str()) => {}
+                    _ if !inputs.contains(&input.as_str()) => inputs.push(input),
+                    _ => {}
+                }
+            }
+
+            outputs.extend(
+                node.output
+                    .iter()
+                    .filter(|o| !o.is_empty() && uses.used_outside(o, &node_set))
+                    .map(|o| o.as_str()),
+            );
+        }
+
+        ModuleNode {
+            name: scope.name,
+            path,
+            nodes: nodes.len(),
+            operators: operator_counts(self, &nodes),
+            parameters: self.initializer_elements(initializers),
+            inputs,
+            outputs,
+            children,
+            node_ids: nodes,
+        }
+    }
+
+    /// Modules named by exporter name scopes, with the nodes outside of any
+    /// scope in the root
+    pub fn module_tree(&self) -> ModuleNode<'_> {
+        let mut root = Scope {
+            name: "",
+            nodes: Vec::new(),
+            children: Vec::new(),
+        };
+
+        for (node_id, node) in self.nodes.iter().enumerate() {
+            root.insert(&scope_path(&node.proto.name), node_id);
+        }
+
+        let mut uses = Uses {
+            consumers: HashMap::new(),
+            graph_outputs: self.outputs().map(|value| value.name()).collect(),
+        };
+        for (node_id, node) in self.nodes.iter().enumerate() {
+            for input in node.proto.input.iter().filter(|i| !i.is_empty()) {
+                uses.consumers.entry(input).or_default().push(node_id);
+            }
+        }
+
+        self.module_node(root, "/".to_string(), &uses)
+    }
+}
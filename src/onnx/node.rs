@@ -0,0 +1,247 @@
+use anyhow::bail;
+use petgraph::Direction;
+
+use crate::onnx::onnx_proto::attribute_proto::AttributeType;
+use crate::onnx::onnx_proto::{AttributeProto, GraphProto, SparseTensorProto, TensorProto};
+use crate::onnx::summary::{AttributeValue, NodeAttribute, NodeDetails, NodeRef, NodeTensor};
+use crate::onnx::weights::tensor_type_name;
+use crate::onnx::{NodeId, OnnxModel, TypeInfo, ValueSource};
+
+fn tensor_value(tensor: &TensorProto) -> String {
+    tensor_type_name(tensor.data_type, &tensor.dims)
+}
+
+fn sparse_tensor_value(tensor: &SparseTensorProto) -> String {
+    let data_type = tensor.values.as_ref().map_or(0, |v| v.data_type);
+    format!("sparse {}", tensor_type_name(data_type, &tensor.dims))
+}
+
+fn graph_value(graph: &GraphProto) -> String {
+    format!("graph {} ({} nodes)", graph.name, graph.node.len())
+}
+
+fn attribute_value(attr: &AttributeProto) -> AttributeValue {
+    let string = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+
+    match AttributeType::from_i32(attr.r#type) {
+        Some(AttributeType::Float) => AttributeValue::Float(attr.f),
+        Some(AttributeType::Int) => AttributeValue::Int(attr.i),
+        Some(AttributeType::String) => AttributeValue::String(string(&attr.s)),
+        Some(AttributeType::Tensor) => {
+            AttributeValue::Tensor(attr.t.as_ref().map(tensor_value).unwrap_or_default())
+        }
+        Some(AttributeType::Graph) => {
+            AttributeValue::Graph(attr.g.as_ref().map(graph_value).unwrap_or_default())
+        }
+        Some(AttributeType::SparseTensor) => AttributeValue::Tensor(
+            attr.sparse_tensor
+                .as_ref()
+                .map(sparse_tensor_value)
+                .unwrap_or_default(),
+        ),
+        Some(AttributeType::TypeProto) => AttributeValue::Type(
+            attr.tp
+                .as_ref()
+                .map(|tp| TypeInfo(tp).to_string())
+                .unwrap_or_default(),
+        ),
+        Some(AttributeType::Floats) => AttributeValue::Floats(attr.floats.clone()),
+        Some(AttributeType::Ints) => AttributeValue::Ints(attr.ints.clone()),
+        Some(AttributeType::Strings) => {
+            AttributeValue::Strings(attr.strings.iter().map(|s| string(s)).collect())
+        }
+        Some(AttributeType::Tensors) => {
+            AttributeValue::Tensors(attr.tensors.iter().map(tensor_value).collect())
+        }
+        Some(AttributeType::Graphs) => {
+            AttributeValue::Graphs(attr.graphs.iter().map(graph_value).collect())
+        }
+        Some(AttributeType::SparseTensors) => AttributeValue::Tensors(
+            attr.sparse_tensors
+                .iter()
+                .map(sparse_tensor_value)
+                .collect(),
+        ),
+        Some(AttributeType::TypeProtos) => AttributeValue::Types(
+            attr.type_protos
+                .iter()
+                .map(|tp| TypeInfo(tp).to_string())
+                .collect(),
+        ),
+        Some(AttributeType::Undefined) | None => AttributeValue::Undefined(attr.r#type),
+    }
+}
+
+impl OnnxModel {
+    /// Main graph node by exact name, or by index when no name matches
+    fn find_node(&self, query: &str) -> anyhow::Result<NodeId> {
+        if let Some(node_id) = self.nodes.iter().position(|n| n.proto.name == query) {
+            return Ok(node_id);
+        }
+
+        match query.parse::<NodeId>() {
+            Ok(node_id) if node_id < self.nodes.len() => Ok(node_id),
+            Ok(node_id) => bail!(
+                "Node index {} is out of range, the graph has {} nodes",
+                node_id,
+                self.nodes.len()
+            ),
+            Err(_) => bail!("No node is named {}", query),
+        }
+    }
+
+    fn node_ref(&self, node_id: NodeId) -> NodeRef<'_> {
+        let node = &self.nodes[node_id].proto;
+
+        NodeRef {
+            index: node_id,
+            name: &node.name,
+            op_type: &node.op_type,
+        }
+    }
+
+    /// Whether a node reads a tensor, directly or from one of its subgraphs
+    fn node_reads(&self, node_id: NodeId, name: &str) -> bool {
+        self.nodes[node_id].proto.input.iter().any(|i| i == name)
+            || self
+                .subgraphs
+                .iter()
+                .filter(|s| s.parent.is_none() && s.node == node_id)
+                .any(|s| s.outer_refs.iter().any(|r| r == name))
+    }
+
+    fn node_tensor<'a>(&'a self, name: &'a str) -> NodeTensor<'a> {
+        let value_id = self.values.get_id_by_name(name);
+        let value = value_id.map(|value_id| self.values.get_by_id(value_id));
+
+        let producer = match value.and_then(|v| v.source.as_ref()) {
+            Some(ValueSource::Node(node_id)) => Some(*node_id),
+            _ => None,
+        };
+
+        // Outputs of nodes reach their consumers along the node graph,
+        // other tensors are not part of it
+        let mut consumers: Vec<NodeId> = match producer {
+            Some(producer) => self
+                .node_graph
+                .neighbors_directed(producer, Direction::Outgoing)
+                .filter(|node_id| self.node_reads(*node_id, name))
+                .collect(),
+            None => (0..self.nodes.len())
+                .filter(|node_id| self.node_reads(*node_id, name))
+                .collect(),
+        };
+        consumers.sort();
+
+        NodeTensor {
+            name,
+            ty: value.map_or_else(|| "?".to_string(), |v| v.type_info().to_string()),
+            initializer: matches!(
+                value.and_then(|v| v.source.as_ref()),
+                Some(ValueSource::Initializer(_))
+            ),
+            graph_input: value_id.is_some_and(|id| self.inputs.contains(&id)),
+            graph_output: value_id.is_some_and(|id| self.outputs.contains(&id)),
+            producer: producer.map(|node_id| self.node_ref(node_id)),
+            consumers: consumers
+                .into_iter()
+                .map(|node_id| self.node_ref(node_id))
+                .collect(),
+        }
+    }
+
+    /// Attributes, tensors and neighbours of a node given by name or index
+    pub fn node_details(&self, query: &str) -> anyhow::Result<NodeDetails<'_>> {
+        let node_id = self.find_node(query)?;
+        let node = &self.nodes[node_id].proto;
+
+        let neighbours = |direction| {
+            let mut node_ids: Vec<NodeId> = self
+                .node_graph
+                .neighbors_directed(node_id, direction)
+                .collect();
+            node_ids.sort();
+            node_ids.into_iter().map(|id| self.node_ref(id)).collect()
+        };
+
+        Ok(NodeDetails {
+            index: node_id,
+            name: &node.name,
+            op_type: &node.op_type,
+            domain: if node.domain.is_empty() {
+                "ai.onnx"
+            } else {
+                &node.domain
+            },
+            doc_string: &node.doc_string,
+            attributes: node
+                .attribute
+                .iter()
+                .map(|attr| NodeAttribute {
+                    name: &attr.name,
+                    ref_attr_name: &attr.ref_attr_name,
+                    value: attribute_value(attr),
+                })
+                .collect(),
+            inputs: node
+                .input
+                .iter()
+                .filter(|i| !i.is_empty())
+                .map(|i| self.node_tensor(i))
+                .collect(),
+            outputs: node
+                .output
+                .iter()
+                .filter(|o| !o.is_empty())
+                .map(|o| self.node_tensor(o))
+                .collect(),
+            predecessors: neighbours(Direction::Incoming),
+            successors: neighbours(Direction::Outgoing),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{graph, load, node};
+    use crate::onnx::onnx_proto::{GraphProto, NodeProto};
+
+    #[test]
+    fn consumers_include_subgraph_references() {
+        let branch = GraphProto {
+            name: "then".to_string(),
+            node: vec![node("Identity", &["t"], &["r"])],
+            ..Default::default()
+        };
+        let if_node = NodeProto {
+            attribute: vec![graph("then_branch", branch)],
+            ..node("If", &["cond"], &["y"])
+        };
+        let model = load(GraphProto {
+            node: vec![
+                node("Relu", &["x"], &["t"]),
+                node("Sigmoid", &["t"], &["s"]),
+                if_node,
+                node("Add", &["x", "s"], &["z"]),
+            ],
+            ..Default::default()
+        });
+
+        let consumers = |details: &NodeDetails, output: usize| -> Vec<NodeId> {
+            details.outputs[output]
+                .consumers
+                .iter()
+                .map(|n| n.index)
+                .collect()
+        };
+
+        let relu = model.node_details("0").unwrap();
+        assert_eq!(consumers(&relu, 0), vec![1, 2]);
+
+        let add = model.node_details("3").unwrap();
+        let x: Vec<NodeId> = add.inputs[0].consumers.iter().map(|n| n.index).collect();
+        assert_eq!(x, vec![0, 3]);
+        assert_eq!(add.inputs[1].producer.as_ref().map(|n| n.index), Some(1));
+    }
+}
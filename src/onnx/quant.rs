@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::{type_proto, NodeProto};
+use crate::onnx::shape::Dim;
+use crate::onnx::summary::{
+    QuantizationSummary, QuantizedCompute, QuantizedTensor, QuantizedTensorKind,
+};
+use crate::onnx::{data_type_name, is_onnx_domain, NodeId, OnnxModel, ValueSource};
+
+const MICROSOFT_DOMAIN: &str = "com.microsoft";
+
+/// Float ops whose cost dominates and that quantizers target
+const FLOAT_COMPUTE_OPS: [&str; 4] = ["Conv", "ConvTranspose", "MatMul", "Gemm"];
+
+/// How a compute node runs after quantization
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ComputeKind {
+    /// Integer kernels, `QLinear*`, `ConvInteger` and `MatMulInteger`
+    Integer,
+    /// Float op with all data inputs dequantized, which runtimes fuse into an
+    /// integer kernel
+    Qdq,
+    /// Only the weights are quantized and dequantized on the fly
+    WeightOnly,
+    Float,
+}
+
+impl ComputeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ComputeKind::Integer => "integer",
+            ComputeKind::Qdq => "qdq",
+            ComputeKind::WeightOnly => "weight-only",
+            ComputeKind::Float => "float",
+        }
+    }
+}
+
+fn int_attribute(node: &NodeProto, name: &str) -> Option<i64> {
+    node.attribute.iter().find(|a| a.name == name).map(|a| a.i)
+}
+
+/// Data types of ONNX 1.16 and later, past the end of the bundled
+/// `onnx.proto`
+const UINT4: i32 = 21;
+const INT4: i32 = 22;
+const FLOAT4E2M1: i32 = 23;
+
+/// Bit width of a quantized data type
+fn bit_width(data_type: i32) -> Option<u32> {
+    if matches!(data_type, UINT4 | INT4 | FLOAT4E2M1) {
+        return Some(4);
+    }
+
+    match DataType::from_i32(data_type)? {
+        DataType::Int8
+        | DataType::Uint8
+        | DataType::Float8e4m3fn
+        | DataType::Float8e4m3fnuz
+        | DataType::Float8e5m2
+        | DataType::Float8e5m2fnuz => Some(8),
+        DataType::Int16 | DataType::Uint16 => Some(16),
+        DataType::Int32 => Some(32),
+        _ => None,
+    }
+}
+
+fn is_quantize(node: &NodeProto) -> bool {
+    node.op_type == "QuantizeLinear"
+        && (is_onnx_domain(&node.domain) || node.domain == MICROSOFT_DOMAIN)
+}
+
+fn is_dequantize(node: &NodeProto) -> bool {
+    node.op_type == "DequantizeLinear"
+        && (is_onnx_domain(&node.domain) || node.domain == MICROSOFT_DOMAIN)
+}
+
+fn optional_input(node: &NodeProto, index: Option<usize>) -> Option<&str> {
+    node.input
+        .get(index?)
+        .filter(|i| !i.is_empty())
+        .map(|i| i.as_str())
+}
+
+/// Input positions of an integer op's quantization parameters
+#[derive(Debug, Default, PartialEq, Eq)]
+struct QuantizedLayout {
+    /// `(tensor, scale, zero point)` of each quantized input
+    inputs: Vec<(usize, Option<usize>, Option<usize>)>,
+    /// `(scale, zero point)` the first output is quantized with
+    output: Option<(usize, usize)>,
+}
+
+/// Integer ops whose inputs are `(tensor, scale, zero point)` triples
+/// starting at the given positions, followed by the output's scale and zero
+/// point at the last position
+const QLINEAR_LAYOUTS: &[(&str, &[usize], usize)] = &[
+    ("QLinearConv", &[0, 3], 6),
+    ("QLinearMatMul", &[0, 3], 6),
+    ("QLinearAdd", &[0, 3], 6),
+    ("QLinearMul", &[0, 3], 6),
+    ("QLinearWhere", &[1, 4], 7),
+    ("QLinearSigmoid", &[0], 3),
+    ("QLinearLeakyRelu", &[0], 3),
+    ("QLinearSoftmax", &[0], 3),
+    ("QLinearAveragePool", &[0], 3),
+    ("QLinearGlobalAveragePool", &[0], 3),
+    ("QLinearReduceMean", &[0], 3),
+];
+
+fn quantized_layout(node: &NodeProto) -> QuantizedLayout {
+    let triple = |i: usize| (i, Some(i + 1), Some(i + 2));
+
+    match node.op_type.as_str() {
+        "ConvInteger" | "MatMulInteger" => QuantizedLayout {
+            inputs: vec![(0, None, Some(2)), (1, None, Some(3))],
+            output: None,
+        },
+        // The output's scale and zero point come before the inputs
+        "QLinearConcat" => QuantizedLayout {
+            inputs: (2..node.input.len()).step_by(3).map(triple).collect(),
+            output: Some((0, 1)),
+        },
+        op => QLINEAR_LAYOUTS
+            .iter()
+            .find(|(op_type, _, _)| *op_type == op)
+            .map(|(_, inputs, output)| QuantizedLayout {
+                inputs: inputs.iter().copied().map(triple).collect(),
+                output: Some((*output, output + 1)),
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn compute_kind(node: &NodeProto) -> Option<ComputeKind> {
+    let integer = node.op_type.starts_with("QLinear")
+        || node.op_type == "ConvInteger"
+        || node.op_type == "MatMulInteger";
+
+    if integer && (is_onnx_domain(&node.domain) || node.domain == MICROSOFT_DOMAIN) {
+        return Some(ComputeKind::Integer);
+    }
+
+    if node.op_type == "MatMulNBits" && node.domain == MICROSOFT_DOMAIN {
+        return Some(ComputeKind::WeightOnly);
+    }
+
+    if FLOAT_COMPUTE_OPS.contains(&node.op_type.as_str()) && is_onnx_domain(&node.domain) {
+        return Some(ComputeKind::Float);
+    }
+
+    None
+}
+
+impl OnnxModel {
+    fn producer(&self, name: &str) -> Option<NodeId> {
+        let value_id = self.values.get_id_by_name(name)?;
+
+        match self.values.get_by_id(value_id).source {
+            Some(ValueSource::Node(node_id)) => Some(node_id),
+            _ => None,
+        }
+    }
+
+    fn is_initializer(&self, name: &str) -> bool {
+        self.values.get_id_by_name(name).is_some_and(|value_id| {
+            matches!(
+                self.values.get_by_id(value_id).source,
+                Some(ValueSource::Initializer(_))
+            )
+        })
+    }
+
+    fn elem_type(&self, name: &str) -> i32 {
+        let value = self
+            .values
+            .get_id_by_name(name)
+            .map(|value_id| self.values.get_by_id(value_id));
+
+        match value
+            .and_then(|v| v.type_proto())
+            .and_then(|t| t.value.as_ref())
+        {
+            Some(type_proto::Value::TensorType(tensor)) => tensor.elem_type,
+            _ => DataType::Undefined as i32,
+        }
+    }
+
+    /// Per-tensor, per-channel or blocked, judged from the scale's shape
+    fn granularity(&self, scale: &str, axis: i64, block_size: i64) -> String {
+        let shape = self
+            .values
+            .get_id_by_name(scale)
+            .and_then(|value_id| self.values.get_by_id(value_id).shape());
+
+        if block_size > 0 {
+            return format!("blocked (axis {}, block size {})", axis, block_size);
+        }
+
+        match shape.as_deref() {
+            Some([]) | Some([Dim::Value(1)]) => "per-tensor".to_string(),
+            Some([_]) => format!("per-channel (axis {})", axis),
+            Some(_) => "blocked".to_string(),
+            None => "unknown".to_string(),
+        }
+    }
+
+    /// Tensors held in a quantized type, as found from the quantization ops
+    /// reading or producing them
+    fn quantized_tensors(&self) -> Vec<QuantizedTensor<'_>> {
+        let mut tensors: Vec<QuantizedTensor> = Vec::new();
+        let kind = |name: &str| {
+            if self.is_initializer(name) {
+                QuantizedTensorKind::Weight
+            } else {
+                QuantizedTensorKind::Activation
+            }
+        };
+
+        for node in self.nodes.iter().map(|n| &n.proto) {
+            let axis = int_attribute(node, "axis").unwrap_or(1);
+            let block_size = int_attribute(node, "block_size").unwrap_or(0);
+
+            if is_dequantize(node) || is_quantize(node) {
+                let (Some(tensor), Some(scale)) = (
+                    if is_dequantize(node) {
+                        node.input.first()
+                    } else {
+                        node.output.first()
+                    },
+                    node.input.get(1),
+                ) else {
+                    continue;
+                };
+                let zero_point = optional_input(node, Some(2));
+                let mut data_type = self.elem_type(tensor);
+
+                // Without a zero point QuantizeLinear produces uint8 unless
+                // output_dtype says otherwise
+                if data_type == DataType::Undefined as i32 {
+                    data_type = zero_point.map_or_else(
+                        || {
+                            int_attribute(node, "output_dtype")
+                                .filter(|t| *t != 0)
+                                .map_or(DataType::Uint8 as i32, |t| t as i32)
+                        },
+                        |zp| self.elem_type(zp),
+                    );
+                }
+
+                tensors.push(QuantizedTensor {
+                    name: tensor,
+                    kind: kind(tensor),
+                    data_type: data_type_name(data_type),
+                    bits: bit_width(data_type),
+                    granularity: self.granularity(scale, axis, block_size),
+                    scale: Some(scale),
+                    zero_point,
+                });
+            } else if node.op_type == "MatMulNBits" && node.domain == MICROSOFT_DOMAIN {
+                let (Some(weight), Some(scale)) = (node.input.get(1), node.input.get(2)) else {
+                    continue;
+                };
+
+                tensors.push(QuantizedTensor {
+                    name: weight,
+                    kind: kind(weight),
+                    data_type: data_type_name(self.elem_type(weight)),
+                    bits: Some(int_attribute(node, "bits").unwrap_or(4) as u32),
+                    granularity: format!(
+                        "blocked (block size {})",
+                        int_attribute(node, "block_size").unwrap_or(0)
+                    ),
+                    scale: Some(scale),
+                    zero_point: optional_input(node, Some(3)),
+                });
+            } else {
+                let layout = quantized_layout(node);
+
+                if let (Some(output), Some((scale, zero_point))) =
+                    (node.output.first().filter(|o| !o.is_empty()), layout.output)
+                {
+                    let zero_point = optional_input(node, Some(zero_point));
+                    let mut data_type = self.elem_type(output);
+
+                    if data_type == DataType::Undefined as i32 {
+                        data_type =
+                            zero_point.map_or(DataType::Uint8 as i32, |zp| self.elem_type(zp));
+                    }
+
+                    let scale = optional_input(node, Some(scale));
+                    tensors.push(QuantizedTensor {
+                        name: output,
+                        kind: kind(output),
+                        data_type: data_type_name(data_type),
+                        bits: bit_width(data_type),
+                        granularity: match scale {
+                            Some(scale) => self.granularity(scale, 0, 0),
+                            None => "unknown".to_string(),
+                        },
+                        scale,
+                        zero_point,
+                    });
+                }
+
+                for (tensor, scale, zero_point) in layout.inputs {
+                    let Some(tensor) = optional_input(node, Some(tensor)) else {
+                        continue;
+                    };
+                    let data_type = self.elem_type(tensor);
+                    let scale = optional_input(node, scale);
+
+                    tensors.push(QuantizedTensor {
+                        name: tensor,
+                        kind: kind(tensor),
+                        data_type: data_type_name(data_type),
+                        bits: bit_width(data_type),
+                        granularity: match scale {
+                            Some(scale) => self.granularity(scale, 0, 0),
+                            None => "zero point only".to_string(),
+                        },
+                        scale,
+                        zero_point: optional_input(node, zero_point),
+                    });
+                }
+            }
+        }
+
+        // A tensor is usually seen by both its producer and consumers
+        let mut seen = HashSet::new();
+        tensors.retain(|t| seen.insert(t.name));
+        tensors.sort_by_key(|t| (t.kind, t.name));
+        tensors
+    }
+
+    /// Float compute ops are classified by how their inputs are produced,
+    /// with the weight being an input coming from an initializer
+    fn float_compute_kind(&self, node: &NodeProto) -> ComputeKind {
+        // Bias stays in float or int32 and does not decide anything
+        let data_inputs = match node.op_type.as_str() {
+            "Conv" | "ConvTranspose" | "Gemm" => 2,
+            _ => node.input.len(),
+        };
+        let inputs: Vec<&str> = node
+            .input
+            .iter()
+            .take(data_inputs)
+            .filter(|i| !i.is_empty())
+            .map(|i| i.as_str())
+            .collect();
+
+        let dequantized = |name: &str| {
+            self.producer(name)
+                .is_some_and(|node_id| is_dequantize(&self.nodes[node_id].proto))
+        };
+        let from_weight = |name: &str| {
+            self.producer(name).is_some_and(|node_id| {
+                let dq = &self.nodes[node_id].proto;
+                dq.input.first().is_some_and(|i| self.is_initializer(i))
+            })
+        };
+
+        if !inputs.is_empty() && inputs.iter().all(|i| dequantized(i)) {
+            ComputeKind::Qdq
+        } else if inputs.iter().any(|i| dequantized(i) && from_weight(i)) {
+            ComputeKind::WeightOnly
+        } else {
+            ComputeKind::Float
+        }
+    }
+
+    /// Quantized tensors and how compute ops run, `None` for float models
+    pub fn quantization_summary(&self) -> Option<QuantizationSummary<'_>> {
+        let tensors = self.quantized_tensors();
+
+        if tensors.is_empty() {
+            return None;
+        }
+
+        let mut compute: BTreeMap<(&str, ComputeKind), usize> = BTreeMap::new();
+
+        for node in self.nodes.iter().map(|n| &n.proto) {
+            let kind = match compute_kind(node) {
+                Some(ComputeKind::Float) => self.float_compute_kind(node),
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            *compute.entry((&node.op_type, kind)).or_default() += 1;
+        }
+
+        let qdq_pairs = self
+            .nodes
+            .iter()
+            .map(|n| &n.proto)
+            .filter(|node| is_dequantize(node))
+            .filter(|node| {
+                node.input
+                    .first()
+                    .and_then(|i| self.producer(i))
+                    .is_some_and(|node_id| is_quantize(&self.nodes[node_id].proto))
+            })
+            .count();
+
+        Some(QuantizationSummary {
+            qdq_pairs,
+            tensors,
+            compute: compute
+                .into_iter()
+                .map(|((op_type, kind), nodes)| QuantizedCompute {
+                    op_type,
+                    kind: kind.name(),
+                    nodes,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{int, load, node, tensor, value};
+    use crate::onnx::onnx_proto::{GraphProto, TensorProto};
+
+    fn contrib(op_type: &str, inputs: &[&str], output: &str) -> NodeProto {
+        NodeProto {
+            domain: MICROSOFT_DOMAIN.to_string(),
+            ..node(op_type, inputs, &[output])
+        }
+    }
+
+    #[test]
+    fn layouts_of_contrib_ops() {
+        let add = contrib(
+            "QLinearAdd",
+            &["a", "as", "az", "b", "bs", "bz", "cs", "cz"],
+            "c",
+        );
+        assert_eq!(
+            quantized_layout(&add),
+            QuantizedLayout {
+                inputs: vec![(0, Some(1), Some(2)), (3, Some(4), Some(5))],
+                output: Some((6, 7)),
+            }
+        );
+
+        let sigmoid = contrib("QLinearSigmoid", &["x", "xs", "xz", "ys", "yz"], "y");
+        assert_eq!(
+            quantized_layout(&sigmoid).inputs,
+            vec![(0, Some(1), Some(2))]
+        );
+        assert_eq!(quantized_layout(&sigmoid).output, Some((3, 4)));
+
+        let concat = contrib(
+            "QLinearConcat",
+            &["ys", "yz", "a", "as", "az", "b", "bs", "bz"],
+            "y",
+        );
+        assert_eq!(
+            quantized_layout(&concat),
+            QuantizedLayout {
+                inputs: vec![(2, Some(3), Some(4)), (5, Some(6), Some(7))],
+                output: Some((0, 1)),
+            }
+        );
+
+        assert_eq!(
+            quantized_layout(&contrib("QLinearUnknown", &["x", "xs", "xz"], "y")),
+            QuantizedLayout::default()
+        );
+    }
+
+    #[test]
+    fn qlinear_add_output_is_not_a_weight() {
+        let mut initializer = vec![tensor("b", DataType::Uint8, &[])];
+        for (scale, zero_point) in [("as", "az"), ("bs", "bz"), ("cs", "cz")] {
+            initializer.push(tensor(scale, DataType::Float, &[]));
+            initializer.push(tensor(zero_point, DataType::Uint8, &[]));
+        }
+
+        let model = load(GraphProto {
+            node: vec![contrib(
+                "QLinearAdd",
+                &["a", "as", "az", "b", "bs", "bz", "cs", "cz"],
+                "c",
+            )],
+            initializer,
+            ..Default::default()
+        });
+
+        let summary = model.quantization_summary().unwrap();
+        let tensors: Vec<(&str, QuantizedTensorKind, Option<&str>, Option<&str>)> = summary
+            .tensors
+            .iter()
+            .map(|t| (t.name, t.kind, t.scale, t.zero_point))
+            .collect();
+
+        assert_eq!(
+            tensors,
+            vec![
+                ("b", QuantizedTensorKind::Weight, Some("bs"), Some("bz")),
+                ("a", QuantizedTensorKind::Activation, Some("as"), Some("az")),
+                ("c", QuantizedTensorKind::Activation, Some("cs"), Some("cz")),
+            ]
+        );
+        assert_eq!(
+            summary.tensors[2].data_type,
+            data_type_name(DataType::Uint8 as i32)
+        );
+    }
+
+    #[test]
+    fn blocked_int4_weight_of_qdq_matmul() {
+        let dequantize = NodeProto {
+            attribute: vec![int("axis", 0), int("block_size", 32)],
+            ..node("DequantizeLinear", &["w", "ws", "wz"], &["wf"])
+        };
+        let model = load(GraphProto {
+            node: vec![dequantize, node("MatMul", &["x", "wf"], &["y"])],
+            initializer: vec![
+                TensorProto {
+                    data_type: INT4,
+                    ..tensor("w", DataType::Undefined, &[64, 8])
+                },
+                tensor("ws", DataType::Float, &[2, 8]),
+                TensorProto {
+                    data_type: INT4,
+                    ..tensor("wz", DataType::Undefined, &[2, 8])
+                },
+            ],
+            input: vec![value("x", &[1, 64])],
+            ..Default::default()
+        });
+
+        let summary = model.quantization_summary().unwrap();
+        assert_eq!(summary.tensors.len(), 1);
+        assert_eq!(summary.tensors[0].name, "w");
+        assert_eq!(summary.tensors[0].bits, Some(4));
+        assert_eq!(
+            summary.tensors[0].granularity,
+            "blocked (axis 0, block size 32)"
+        );
+        assert_eq!(summary.compute[0].kind, "weight-only");
+        assert_eq!(bit_width(UINT4), Some(4));
+        assert_eq!(bit_width(FLOAT4E2M1), Some(4));
+    }
+}
@@ -0,0 +1,635 @@
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, bail, Context};
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+
+use crate::onnx::onnx_proto::attribute_proto::AttributeType;
+use crate::onnx::onnx_proto::{type_proto, NodeProto};
+use crate::onnx::shape::Dim;
+use crate::onnx::summary::{NodeRef, QueryMatches, Value};
+use crate::onnx::{data_type_name, OnnxModel, ValueInfo, ValueSource};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Cmp(CmpOp),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Glob,
+    Regex,
+}
+
+fn tokenize(expr: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if(|(_, c)| *c == expected).is_some();
+
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' if next_is('=') => Token::Cmp(CmpOp::Eq),
+            '=' if next_is('~') => Token::Cmp(CmpOp::Regex),
+            '!' if next_is('=') => Token::Cmp(CmpOp::Ne),
+            '<' if next_is('=') => Token::Cmp(CmpOp::Le),
+            '<' => Token::Cmp(CmpOp::Lt),
+            '>' if next_is('=') => Token::Cmp(CmpOp::Ge),
+            '>' => Token::Cmp(CmpOp::Gt),
+            '~' => Token::Cmp(CmpOp::Glob),
+            '"' | '\'' => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => quoted.push(escaped),
+                            None => bail!("Unterminated string at column {}", pos + 1),
+                        },
+                        Some((_, other)) => quoted.push(other),
+                        None => bail!("Unterminated string at column {}", pos + 1),
+                    }
+                }
+                Token::Quoted(quoted)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => bail!("Unexpected {:?} at column {}", c, pos + 1),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '(' | ')' | '=' | '!' | '<' | '>' | '~' | '"' | '\'')
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TensorField {
+    Name,
+    Dtype,
+    Dim,
+    Rank,
+    Kind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Op,
+    Domain,
+    Name,
+    Attr(String),
+    /// Field of the tensor itself when querying tensors
+    Tensor(TensorField),
+    Input(TensorField),
+    Output(TensorField),
+    /// Inputs of a node backed by initializers
+    Weight(TensorField),
+}
+
+impl Field {
+    fn parse(path: &str, target: QueryTarget) -> anyhow::Result<Self> {
+        let tensor_field = |name: &str| match name {
+            "name" => Ok(TensorField::Name),
+            "dtype" => Ok(TensorField::Dtype),
+            "dim" => Ok(TensorField::Dim),
+            "rank" => Ok(TensorField::Rank),
+            "kind" if target == QueryTarget::Tensors => Ok(TensorField::Kind),
+            _ => Err(anyhow!("Unknown tensor field {}", name)),
+        };
+
+        let field = match (target, path.split_once('.')) {
+            (QueryTarget::Nodes, None) => match path {
+                "op" => Field::Op,
+                "domain" => Field::Domain,
+                "name" => Field::Name,
+                _ => bail!("Unknown node field {}", path),
+            },
+            (QueryTarget::Nodes, Some(("attr", name))) => Field::Attr(name.to_string()),
+            (QueryTarget::Nodes, Some(("input", field))) => Field::Input(tensor_field(field)?),
+            (QueryTarget::Nodes, Some(("output", field))) => Field::Output(tensor_field(field)?),
+            (QueryTarget::Nodes, Some(("weight", field))) => Field::Weight(tensor_field(field)?),
+            (QueryTarget::Tensors, None) => Field::Tensor(tensor_field(path)?),
+            _ => bail!("Unknown field {}", path),
+        };
+
+        Ok(field)
+    }
+}
+
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+struct Predicate {
+    field: Field,
+    op: CmpOp,
+    value: String,
+    matcher: Option<Matcher>,
+}
+
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Predicate(Predicate),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    target: QueryTarget,
+}
+
+impl Parser {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Word(w)) if w == keyword)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.not()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Expr> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> anyhow::Result<Expr> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => bail!("Expected ) after expression"),
+                }
+            }
+            Some(Token::Word(path)) => {
+                let field = Field::parse(&path, self.target)?;
+                let op = match self.next() {
+                    Some(Token::Cmp(op)) => op,
+                    _ => bail!("Expected a comparison after {}", path),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value) | Token::Quoted(value)) => value,
+                    _ => bail!("Expected a value after {} comparison", path),
+                };
+                let matcher = match op {
+                    CmpOp::Glob => Some(Matcher::Glob(
+                        Glob::new(&value)
+                            .with_context(|| format!("Invalid glob {:?}", value))?
+                            .compile_matcher(),
+                    )),
+                    CmpOp::Regex => Some(Matcher::Regex(
+                        Regex::new(&value).with_context(|| format!("Invalid regex {:?}", value))?,
+                    )),
+                    _ => None,
+                };
+
+                Ok(Expr::Predicate(Predicate {
+                    field,
+                    op,
+                    value,
+                    matcher,
+                }))
+            }
+            Some(token) => bail!("Unexpected {:?}, expected a field", token),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+}
+
+/// Value of a field, numbers compare numerically and everything else as text
+enum FieldValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl FieldValue {
+    fn text(&self) -> String {
+        match self {
+            FieldValue::Int(v) => v.to_string(),
+            FieldValue::Float(v) => v.to_string(),
+            FieldValue::Str(s) => s.clone(),
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            FieldValue::Int(v) => Some(*v as f64),
+            FieldValue::Float(v) => Some(*v),
+            FieldValue::Str(s) => s.parse().ok(),
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, value: &FieldValue) -> bool {
+        let ordering = match (value.number(), self.value.parse::<f64>()) {
+            (Some(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(value.text().as_str().cmp(self.value.as_str())),
+        };
+
+        match (self.op, self.matcher.as_ref()) {
+            (CmpOp::Eq, _) => ordering == Some(Ordering::Equal),
+            (CmpOp::Ne, _) => ordering != Some(Ordering::Equal),
+            (CmpOp::Lt, _) => ordering == Some(Ordering::Less),
+            (CmpOp::Le, _) => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            (CmpOp::Gt, _) => ordering == Some(Ordering::Greater),
+            (CmpOp::Ge, _) => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            (CmpOp::Glob, Some(Matcher::Glob(glob))) => glob.is_match(value.text()),
+            (CmpOp::Regex, Some(Matcher::Regex(regex))) => regex.is_match(&value.text()),
+            _ => false,
+        }
+    }
+}
+
+/// What a query selects
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QueryTarget {
+    Nodes,
+    Tensors,
+}
+
+/// Parsed filter expression.
+///
+/// An expression combines comparisons with `and`, `or`, `not` and
+/// parentheses, e.g. `op == Conv and attr.group > 1`. Comparisons are `==`,
+/// `!=`, `<`, `<=`, `>`, `>=`, `~` for globs and `=~` for regexes. Values are
+/// bare words or quoted strings, numbers compare numerically.
+///
+/// Node fields are `op`, `domain`, `name`, `attr.<name>` and `input.<f>`,
+/// `output.<f>` or `weight.<f>` where `<f>` is one of the tensor fields
+/// `name`, `dtype`, `dim` and `rank`. Tensors also have `kind`, one of
+/// `input`, `output`, `initializer` or `intermediate`. A field with several
+/// values, such as the dims of a shape, matches if any value does.
+pub struct Query {
+    expr: Expr,
+    target: QueryTarget,
+}
+
+impl Query {
+    pub fn parse(expr: &str, target: QueryTarget) -> anyhow::Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+            target,
+        };
+
+        let parsed = parser.or()?;
+
+        if let Some(token) = parser.next() {
+            bail!("Unexpected {:?} after expression", token);
+        }
+
+        Ok(Query {
+            expr: parsed,
+            target,
+        })
+    }
+}
+
+fn eval<F>(expr: &Expr, values: &F) -> bool
+where
+    F: Fn(&Field) -> Vec<FieldValue>,
+{
+    match expr {
+        Expr::And(a, b) => eval(a, values) && eval(b, values),
+        Expr::Or(a, b) => eval(a, values) || eval(b, values),
+        Expr::Not(a) => !eval(a, values),
+        Expr::Predicate(predicate) => values(&predicate.field)
+            .iter()
+            .any(|value| predicate.matches(value)),
+    }
+}
+
+fn attribute_values(node: &NodeProto, name: &str) -> Vec<FieldValue> {
+    let Some(attr) = node.attribute.iter().find(|a| a.name == name) else {
+        return Vec::new();
+    };
+    let string = |s: &[u8]| FieldValue::Str(String::from_utf8_lossy(s).into_owned());
+
+    match AttributeType::from_i32(attr.r#type) {
+        Some(AttributeType::Int) => vec![FieldValue::Int(attr.i)],
+        Some(AttributeType::Float) => vec![FieldValue::Float(attr.f as f64)],
+        Some(AttributeType::String) => vec![string(&attr.s)],
+        Some(AttributeType::Ints) => attr.ints.iter().map(|i| FieldValue::Int(*i)).collect(),
+        Some(AttributeType::Floats) => attr
+            .floats
+            .iter()
+            .map(|f| FieldValue::Float(*f as f64))
+            .collect(),
+        Some(AttributeType::Strings) => attr.strings.iter().map(|s| string(s)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl OnnxModel {
+    fn tensor_kind(&self, value: &ValueInfo) -> &'static str {
+        let value_id = self.values.get_id_by_name(value.name());
+
+        match value.source {
+            Some(ValueSource::Initializer(_)) => "initializer",
+            _ if value_id.is_some_and(|id| self.outputs.contains(&id)) => "output",
+            Some(ValueSource::Node(_)) => "intermediate",
+            None => "input",
+        }
+    }
+
+    fn tensor_values(&self, value: &ValueInfo, field: TensorField) -> Vec<FieldValue> {
+        match field {
+            TensorField::Name => vec![FieldValue::Str(value.name().to_string())],
+            TensorField::Dtype => match value.type_proto().and_then(|t| t.value.as_ref()) {
+                Some(type_proto::Value::TensorType(tensor)) => {
+                    vec![FieldValue::Str(data_type_name(tensor.elem_type))]
+                }
+                _ => Vec::new(),
+            },
+            TensorField::Dim => value
+                .shape()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|dim| match dim {
+                    Dim::Value(v) => FieldValue::Int(v),
+                    dim => FieldValue::Str(dim.to_string()),
+                })
+                .collect(),
+            TensorField::Rank => value
+                .shape()
+                .map(|shape| vec![FieldValue::Int(shape.len() as i64)])
+                .unwrap_or_default(),
+            TensorField::Kind => vec![FieldValue::Str(self.tensor_kind(value).to_string())],
+        }
+    }
+
+    fn named_values<'a>(&'a self, names: &'a [String]) -> impl Iterator<Item = &'a ValueInfo> {
+        names
+            .iter()
+            .filter_map(|name| self.values.get_id_by_name(name))
+            .map(|value_id| self.values.get_by_id(value_id))
+    }
+
+    fn node_values(&self, node: &NodeProto, field: &Field) -> Vec<FieldValue> {
+        match field {
+            Field::Op => vec![FieldValue::Str(node.op_type.clone())],
+            Field::Domain => vec![FieldValue::Str(if node.domain.is_empty() {
+                "ai.onnx".to_string()
+            } else {
+                node.domain.clone()
+            })],
+            Field::Name => vec![FieldValue::Str(node.name.clone())],
+            Field::Tensor(_) => Vec::new(),
+            Field::Attr(name) => attribute_values(node, name),
+            Field::Input(field) => self
+                .named_values(&node.input)
+                .flat_map(|v| self.tensor_values(v, *field))
+                .collect(),
+            Field::Output(field) => self
+                .named_values(&node.output)
+                .flat_map(|v| self.tensor_values(v, *field))
+                .collect(),
+            Field::Weight(field) => self
+                .named_values(&node.input)
+                .filter(|v| matches!(v.source, Some(ValueSource::Initializer(_))))
+                .flat_map(|v| self.tensor_values(v, *field))
+                .collect(),
+        }
+    }
+
+    /// Main graph nodes or tensors matching the query
+    pub fn query<'a>(&'a self, query: &Query, expr: &'a str) -> QueryMatches<'a> {
+        let mut matches = QueryMatches {
+            query: expr,
+            nodes: Vec::new(),
+            tensors: Vec::new(),
+        };
+
+        match query.target {
+            QueryTarget::Nodes => {
+                for (node_id, node) in self.nodes.iter().enumerate() {
+                    if eval(&query.expr, &|field: &Field| {
+                        self.node_values(&node.proto, field)
+                    }) {
+                        matches.nodes.push(NodeRef {
+                            index: node_id,
+                            name: &node.proto.name,
+                            op_type: &node.proto.op_type,
+                        });
+                    }
+                }
+            }
+            QueryTarget::Tensors => {
+                for value in self.values.values.iter() {
+                    let field_values = |field: &Field| match field {
+                        Field::Tensor(field) => self.tensor_values(value, *field),
+                        _ => Vec::new(),
+                    };
+
+                    if eval(&query.expr, &field_values) {
+                        matches.tensors.push(Value::from(value));
+                    }
+                }
+            }
+        }
+
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{int, load, node};
+    use crate::onnx::onnx_proto::GraphProto;
+
+    /// Expression in prefix notation, to compare parse trees
+    fn render(expr: &Expr) -> String {
+        match expr {
+            Expr::And(a, b) => format!("(and {} {})", render(a), render(b)),
+            Expr::Or(a, b) => format!("(or {} {})", render(a), render(b)),
+            Expr::Not(a) => format!("(not {})", render(a)),
+            Expr::Predicate(p) => format!("({:?} {:?} {})", p.op, p.field, p.value),
+        }
+    }
+
+    fn parse(expr: &str) -> anyhow::Result<String> {
+        Ok(render(&Query::parse(expr, QueryTarget::Nodes)?.expr))
+    }
+
+    fn parse_error(expr: &str, target: QueryTarget) -> String {
+        match Query::parse(expr, target) {
+            Ok(query) => panic!("{} parsed as {}", expr, render(&query.expr)),
+            Err(err) => format!("{:#}", err),
+        }
+    }
+
+    /// Whether the predicate of a single comparison matches a value
+    fn matches(expr: &str, value: FieldValue) -> bool {
+        match Query::parse(expr, QueryTarget::Nodes).unwrap().expr {
+            Expr::Predicate(predicate) => predicate.matches(&value),
+            _ => panic!("{} is not a comparison", expr),
+        }
+    }
+
+    #[test]
+    fn tokenize_operators_words_and_strings() {
+        assert_eq!(
+            tokenize(r#"(attr.group>=2)!=x~'a b'=~"q\"r""#).unwrap(),
+            vec![
+                Token::Open,
+                Token::Word("attr.group".to_string()),
+                Token::Cmp(CmpOp::Ge),
+                Token::Word("2".to_string()),
+                Token::Close,
+                Token::Cmp(CmpOp::Ne),
+                Token::Word("x".to_string()),
+                Token::Cmp(CmpOp::Glob),
+                Token::Quoted("a b".to_string()),
+                Token::Cmp(CmpOp::Regex),
+                Token::Quoted("q\"r".to_string()),
+            ]
+        );
+
+        assert!(tokenize("name == 'open")
+            .unwrap_err()
+            .to_string()
+            .contains("Unterminated string at column 9"));
+        assert!(tokenize("op = Conv")
+            .unwrap_err()
+            .to_string()
+            .contains("Unexpected '=' at column 4"));
+    }
+
+    #[test]
+    fn precedence_of_not_and_or() {
+        assert_eq!(
+            parse("op == Add or op == Mul and not name ~ '*skip*'").unwrap(),
+            "(or (Eq Op Add) (and (Eq Op Mul) (not (Glob Name *skip*))))"
+        );
+        assert_eq!(
+            parse("(op == Add or op == Mul) and input.rank > 2").unwrap(),
+            "(and (or (Eq Op Add) (Eq Op Mul)) (Gt Input(Rank) 2))"
+        );
+        assert_eq!(
+            parse("not not weight.dtype == f16").unwrap(),
+            "(not (not (Eq Weight(Dtype) f16)))"
+        );
+        assert_eq!(
+            parse("attr.kernel_shape == 3").unwrap(),
+            "(Eq Attr(\"kernel_shape\") 3)"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let nodes = QueryTarget::Nodes;
+        let tensors = QueryTarget::Tensors;
+
+        assert!(parse_error("opp == Conv", nodes).contains("Unknown node field opp"));
+        assert!(parse_error("input.kind == x", nodes).contains("Unknown tensor field kind"));
+        assert!(parse_error("op == Conv", tensors).contains("Unknown tensor field op"));
+        assert!(parse_error("op Conv", nodes).contains("Expected a comparison after op"));
+        assert!(parse_error("op ==", nodes).contains("Expected a value after op"));
+        assert!(parse_error("(op == Conv", nodes).contains("Expected )"));
+        assert!(parse_error("op == Conv op", nodes).contains("after expression"));
+        assert!(parse_error("", nodes).contains("Unexpected end of expression"));
+        assert!(parse_error("name =~ '('", nodes).contains("Invalid regex"));
+
+        assert!(Query::parse("kind == initializer and dim == 768", tensors).is_ok());
+    }
+
+    #[test]
+    fn comparisons() {
+        let int = FieldValue::Int;
+        let string = |s: &str| FieldValue::Str(s.to_string());
+
+        // Numbers compare numerically, text lexically
+        assert!(matches("attr.group > 9", int(10)));
+        assert!(matches("name > 9", string("10")));
+        assert!(!matches("name > b", string("a10")));
+        assert!(matches("attr.alpha <= 0.5", FieldValue::Float(0.25)));
+        assert!(matches("input.dim != 3", string("batch")));
+        assert!(matches("input.dim == batch", string("batch")));
+        assert!(matches(
+            "name ~ '/encoder/*/MatMul'",
+            string("/encoder/layer.0/MatMul")
+        ));
+        assert!(!matches("name ~ 'Mat*'", string("/MatMul")));
+        assert!(matches("name =~ 'layer\\.[0-3]/'", string("/layer.2/Add")));
+    }
+
+    #[test]
+    fn query_nodes_of_a_model() {
+        let conv = |name: &str, group: i64| NodeProto {
+            name: name.to_string(),
+            attribute: vec![int("group", group)],
+            ..node(
+                "Conv",
+                &["x", &format!("{}.w", name)],
+                &[&format!("{}.y", name)],
+            )
+        };
+        let model = load(GraphProto {
+            node: vec![conv("dense", 1), conv("depthwise", 32)],
+            ..Default::default()
+        });
+
+        let expr = "op == Conv and attr.group > 1";
+        let query = Query::parse(expr, QueryTarget::Nodes).unwrap();
+        let matches = model.query(&query, expr);
+        let names: Vec<&str> = matches.nodes.iter().map(|n| n.name).collect();
+        assert_eq!(names, vec!["depthwise"]);
+
+        let expr = "kind == intermediate and name ~ 'dense*'";
+        let query = Query::parse(expr, QueryTarget::Tensors).unwrap();
+        let matches = model.query(&query, expr);
+        let names: Vec<&str> = matches.tensors.iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["dense.y"]);
+    }
+}
@@ -0,0 +1,1787 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use petgraph::algo::toposort;
+
+use crate::check::{Finding, Location, Severity};
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::tensor_shape_proto::{dimension, Dimension};
+use crate::onnx::onnx_proto::{
+    attribute_proto, type_proto, AttributeProto, NodeProto, TensorProto, TensorShapeProto,
+    TypeProto,
+};
+use crate::onnx::{is_onnx_domain, OnnxModel, ValueInfo};
+
+/// Largest tensor whose values are tracked for constant propagation
+const MAX_CONST_ELEMENTS: usize = 64;
+
+/// Ops whose first output has the type and shape of their first input
+const ELEMENTWISE_OPS: &[&str] = &[
+    "Relu",
+    "LeakyRelu",
+    "PRelu",
+    "Elu",
+    "Selu",
+    "Celu",
+    "Gelu",
+    "Sigmoid",
+    "HardSigmoid",
+    "HardSwish",
+    "Tanh",
+    "Exp",
+    "Log",
+    "Sqrt",
+    "Neg",
+    "Abs",
+    "Erf",
+    "Reciprocal",
+    "Floor",
+    "Ceil",
+    "Round",
+    "Sin",
+    "Cos",
+    "Tan",
+    "Softplus",
+    "Softsign",
+    "Mish",
+    "Sign",
+    "Not",
+    "Softmax",
+    "LogSoftmax",
+    "Hardmax",
+    "Clip",
+    "Identity",
+    "Dropout",
+    "LayerNormalization",
+    "BatchNormalization",
+    "InstanceNormalization",
+    "GroupNormalization",
+    "LpNormalization",
+    "MeanVarianceNormalization",
+    "CumSum",
+    "Trilu",
+    "ThresholdedRelu",
+    "Shrink",
+];
+
+/// Ops broadcasting all of their inputs against each other
+const BROADCAST_OPS: &[&str] = &[
+    "Add",
+    "Sub",
+    "Mul",
+    "Div",
+    "Pow",
+    "Mod",
+    "Max",
+    "Min",
+    "Mean",
+    "Sum",
+    "And",
+    "Or",
+    "Xor",
+    "BitShift",
+    "Equal",
+    "Less",
+    "Greater",
+    "LessOrEqual",
+    "GreaterOrEqual",
+];
+
+/// Reductions taking `axes` and `keepdims`
+const REDUCE_OPS: &[&str] = &[
+    "ReduceMean",
+    "ReduceSum",
+    "ReduceMax",
+    "ReduceMin",
+    "ReduceProd",
+    "ReduceL1",
+    "ReduceL2",
+    "ReduceLogSum",
+    "ReduceLogSumExp",
+    "ReduceSumSquare",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dim {
+    Value(i64),
+    Param(String),
+    Unknown,
+}
+
+impl fmt::Display for Dim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dim::Value(v) => write!(f, "{}", v),
+            Dim::Param(p) => write!(f, "{}", p),
+            Dim::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+impl Dim {
+    pub fn from_proto(dim: &Dimension) -> Self {
+        match dim.value.as_ref() {
+            Some(dimension::Value::DimValue(v)) => Dim::Value(*v),
+            Some(dimension::Value::DimParam(p)) if !p.is_empty() => Dim::Param(p.clone()),
+            _ => Dim::Unknown,
+        }
+    }
+
+    pub fn to_proto(&self) -> Dimension {
+        let value = match self {
+            Dim::Value(v) => Some(dimension::Value::DimValue(*v)),
+            Dim::Param(p) => Some(dimension::Value::DimParam(p.clone())),
+            Dim::Unknown => None,
+        };
+
+        Dimension {
+            value,
+            denotation: String::new(),
+        }
+    }
+
+    fn value(&self) -> Option<i64> {
+        match self {
+            Dim::Value(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// Product of dimensions as a constant coefficient times symbolic factors
+#[derive(Debug, Clone)]
+struct Product {
+    coefficient: i64,
+    symbols: Vec<String>,
+}
+
+impl Product {
+    fn of(dims: &[Dim]) -> Option<Self> {
+        let mut product = Product {
+            coefficient: 1,
+            symbols: Vec::new(),
+        };
+
+        for dim in dims {
+            match dim {
+                Dim::Value(v) => product.coefficient = product.coefficient.checked_mul(*v)?,
+                // Params may themselves be products produced by earlier reshapes
+                Dim::Param(p) => {
+                    for factor in p.split('*') {
+                        match factor.parse::<i64>() {
+                            Ok(v) => product.coefficient = product.coefficient.checked_mul(v)?,
+                            Err(_) => product.symbols.push(factor.to_string()),
+                        }
+                    }
+                }
+                Dim::Unknown => return None,
+            }
+        }
+
+        Some(product)
+    }
+
+    fn divide(mut self, divisor: Product) -> Option<Self> {
+        if self.coefficient.checked_rem(divisor.coefficient)? != 0 {
+            return None;
+        }
+
+        self.coefficient = self.coefficient.checked_div(divisor.coefficient)?;
+
+        for symbol in divisor.symbols {
+            let index = self.symbols.iter().position(|s| *s == symbol)?;
+            self.symbols.remove(index);
+        }
+
+        Some(self)
+    }
+
+    fn into_dim(self) -> Dim {
+        if self.symbols.is_empty() {
+            return Dim::Value(self.coefficient);
+        }
+
+        let mut factors = Vec::new();
+        if self.coefficient != 1 {
+            factors.push(self.coefficient.to_string());
+        }
+        factors.extend(self.symbols);
+
+        Dim::Param(factors.join("*"))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tensor {
+    elem_type: i32,
+    shape: Option<Vec<Dim>>,
+}
+
+impl Tensor {
+    fn new(elem_type: i32, shape: Vec<Dim>) -> Self {
+        Tensor {
+            elem_type,
+            shape: Some(shape),
+        }
+    }
+
+    fn from_type(ty: &TypeProto) -> Option<Self> {
+        match ty.value.as_ref()? {
+            type_proto::Value::TensorType(tensor) => Some(Tensor {
+                elem_type: tensor.elem_type,
+                shape: tensor
+                    .shape
+                    .as_ref()
+                    .map(|shape| shape.dim.iter().map(Dim::from_proto).collect()),
+            }),
+            _ => None,
+        }
+    }
+
+    fn to_type(&self) -> TypeProto {
+        TypeProto {
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: self.elem_type,
+                shape: self.shape.as_ref().map(|shape| TensorShapeProto {
+                    dim: shape.iter().map(Dim::to_proto).collect(),
+                }),
+            })),
+            denotation: String::new(),
+        }
+    }
+
+    fn with_elem_type(&self, elem_type: i32) -> Self {
+        Tensor {
+            elem_type,
+            shape: self.shape.clone(),
+        }
+    }
+}
+
+/// Integer tensor of rank 0 or 1 whose values are known statically
+#[derive(Debug, Clone)]
+struct Const {
+    scalar: bool,
+    values: Vec<Dim>,
+}
+
+impl Const {
+    fn from_tensor(tensor: &TensorProto) -> Option<Self> {
+        let elements = match tensor.dims.as_slice() {
+            [] => 1,
+            [len] => usize::try_from(*len).ok()?,
+            _ => return None,
+        };
+        if elements > MAX_CONST_ELEMENTS {
+            return None;
+        }
+
+        // Check the length of the data before decoding any of it
+        let values: Vec<i64> = match DataType::from_i32(tensor.data_type)? {
+            DataType::Int64 if !tensor.int64_data.is_empty() => {
+                if tensor.int64_data.len() != elements {
+                    return None;
+                }
+                tensor.int64_data.clone()
+            }
+            DataType::Int64 if tensor.raw_data.len() == elements * 8 => tensor
+                .raw_data
+                .chunks_exact(8)
+                .map(|b| i64::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+            DataType::Int32 if !tensor.int32_data.is_empty() => {
+                if tensor.int32_data.len() != elements {
+                    return None;
+                }
+                tensor.int32_data.iter().map(|v| *v as i64).collect()
+            }
+            DataType::Int32 if tensor.raw_data.len() == elements * 4 => tensor
+                .raw_data
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes(b.try_into().unwrap()) as i64)
+                .collect(),
+            _ => return None,
+        };
+
+        Some(Const {
+            scalar: tensor.dims.is_empty(),
+            values: values.into_iter().map(Dim::Value).collect(),
+        })
+    }
+
+    fn vector(values: Vec<Dim>) -> Self {
+        Const {
+            scalar: false,
+            values,
+        }
+    }
+
+    fn shape(&self) -> Vec<Dim> {
+        if self.scalar {
+            Vec::new()
+        } else {
+            vec![Dim::Value(self.values.len() as i64)]
+        }
+    }
+
+    fn ints(&self) -> Option<Vec<i64>> {
+        self.values.iter().map(Dim::value).collect()
+    }
+}
+
+/// Normalize a possibly negative axis against a rank
+fn axis(axis: i64, rank: usize) -> Option<usize> {
+    let axis = if axis < 0 { axis + rank as i64 } else { axis };
+    (0..rank as i64).contains(&axis).then_some(axis as usize)
+}
+
+/// Multidirectional (numpy style) broadcast of two shapes
+fn broadcast(a: &[Dim], b: &[Dim]) -> Vec<Dim> {
+    let rank = a.len().max(b.len());
+    let pad = |shape: &[Dim], i: usize| -> Dim {
+        let offset = rank - shape.len();
+        if i < offset {
+            Dim::Value(1)
+        } else {
+            shape[i - offset].clone()
+        }
+    };
+
+    (0..rank)
+        .map(|i| match (pad(a, i), pad(b, i)) {
+            (Dim::Value(1), d) | (d, Dim::Value(1)) => d,
+            (a, b) if a == b => a,
+            // A static size other than one decides the broadcast
+            (Dim::Value(v), _) | (_, Dim::Value(v)) => Dim::Value(v),
+            _ => Dim::Unknown,
+        })
+        .collect()
+}
+
+/// Output size of a sliding window along one spatial axis
+fn window_dim(
+    input: &Dim,
+    kernel: i64,
+    stride: i64,
+    dilation: i64,
+    pads: (i64, i64),
+    auto_pad: &str,
+    ceil_mode: bool,
+) -> Dim {
+    // Non-positive strides are reported by `infer_shapes`
+    if stride <= 0 {
+        return Dim::Unknown;
+    }
+
+    match auto_pad {
+        "SAME_UPPER" | "SAME_LOWER" => match input {
+            Dim::Value(v) => v
+                .checked_add(stride - 1)
+                .map_or(Dim::Unknown, |v| Dim::Value(v / stride)),
+            d if stride == 1 => d.clone(),
+            _ => Dim::Unknown,
+        },
+        _ => {
+            let pads = if auto_pad == "VALID" { (0, 0) } else { pads };
+            let Some(effective_kernel) = kernel
+                .checked_sub(1)
+                .and_then(|k| k.checked_mul(dilation))
+                .and_then(|k| k.checked_add(1))
+            else {
+                return Dim::Unknown;
+            };
+            let padding = pads.0.checked_add(pads.1);
+
+            match input {
+                Dim::Value(v) => padding
+                    .and_then(|p| v.checked_add(p))
+                    .and_then(|v| v.checked_sub(effective_kernel))
+                    .and_then(|span| {
+                        if ceil_mode {
+                            span.checked_add(stride - 1)
+                        } else {
+                            Some(span)
+                        }
+                    })
+                    .and_then(|span| (span / stride).checked_add(1))
+                    .map_or(Dim::Unknown, Dim::Value),
+                d if stride == 1 && padding == Some(effective_kernel - 1) => d.clone(),
+                _ => Dim::Unknown,
+            }
+        }
+    }
+}
+
+/// Inputs and attributes of a node during inference
+struct NodeContext<'a> {
+    node: &'a NodeProto,
+    inputs: Vec<Option<&'a Tensor>>,
+    consts: Vec<Option<&'a Const>>,
+}
+
+impl<'a> NodeContext<'a> {
+    fn input(&self, index: usize) -> Option<&'a Tensor> {
+        self.inputs.get(index).copied().flatten()
+    }
+
+    fn shape(&self, index: usize) -> Option<&'a [Dim]> {
+        self.input(index)?.shape.as_deref()
+    }
+
+    fn elem_type(&self, index: usize) -> i32 {
+        self.input(index).map_or(0, |t| t.elem_type)
+    }
+
+    fn constant(&self, index: usize) -> Option<&'a Const> {
+        self.consts.get(index).copied().flatten()
+    }
+
+    fn const_ints(&self, index: usize) -> Option<Vec<i64>> {
+        self.constant(index)?.ints()
+    }
+
+    fn has_input(&self, index: usize) -> bool {
+        self.node.input.get(index).is_some_and(|i| !i.is_empty())
+    }
+
+    fn attr(&self, name: &str) -> Option<&'a AttributeProto> {
+        self.node.attribute.iter().find(|a| a.name == name)
+    }
+
+    fn attr_int(&self, name: &str) -> Option<i64> {
+        self.attr(name).map(|a| a.i)
+    }
+
+    fn attr_ints(&self, name: &str) -> Option<&'a [i64]> {
+        self.attr(name).map(|a| a.ints.as_slice())
+    }
+
+    fn attr_str(&self, name: &str) -> Option<&'a str> {
+        self.attr(name).and_then(|a| std::str::from_utf8(&a.s).ok())
+    }
+
+    /// Axes given either as an attribute (older opsets) or as an input
+    fn axes(&self, input: usize) -> Option<Vec<i64>> {
+        match self.attr_ints("axes") {
+            Some(axes) => Some(axes.to_vec()),
+            None if self.has_input(input) => self.const_ints(input),
+            None => None,
+        }
+    }
+}
+
+/// Inferred outputs of a node
+#[derive(Default)]
+struct NodeOutputs {
+    tensors: Vec<Option<Tensor>>,
+    consts: Vec<Option<Const>>,
+}
+
+impl NodeOutputs {
+    fn tensor(tensor: Tensor) -> Self {
+        NodeOutputs {
+            tensors: vec![Some(tensor)],
+            consts: Vec::new(),
+        }
+    }
+
+    fn constant(elem_type: i32, constant: Const) -> Self {
+        NodeOutputs {
+            tensors: vec![Some(Tensor::new(elem_type, constant.shape()))],
+            consts: vec![Some(constant)],
+        }
+    }
+}
+
+fn infer_node(ctx: &NodeContext) -> Option<NodeOutputs> {
+    let op = ctx.node.op_type.as_str();
+
+    match op {
+        op if ELEMENTWISE_OPS.contains(&op) => {
+            let input = ctx.input(0)?;
+            let mut outputs = NodeOutputs::tensor(input.clone());
+
+            if op == "Identity" {
+                outputs.consts = vec![ctx.constant(0).cloned()];
+            }
+
+            Some(outputs)
+        }
+        "IsNaN" | "IsInf" => Some(NodeOutputs::tensor(
+            ctx.input(0)?.with_elem_type(DataType::Bool as i32),
+        )),
+        op if BROADCAST_OPS.contains(&op) => {
+            let mut shape = ctx.shape(0)?.to_vec();
+            for index in 1..ctx.node.input.len() {
+                shape = broadcast(&shape, ctx.shape(index)?);
+            }
+
+            let elem_type = match op {
+                "Equal" | "Less" | "Greater" | "LessOrEqual" | "GreaterOrEqual" => {
+                    DataType::Bool as i32
+                }
+                _ => ctx.elem_type(0),
+            };
+
+            let mut outputs = NodeOutputs::tensor(Tensor::new(elem_type, shape));
+            outputs.consts = vec![const_arithmetic(ctx, op)];
+            Some(outputs)
+        }
+        "Where" => {
+            let shape = broadcast(&broadcast(ctx.shape(0)?, ctx.shape(1)?), ctx.shape(2)?);
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(1), shape)))
+        }
+        "Cast" => {
+            let to = ctx.attr_int("to")? as i32;
+            let mut outputs = NodeOutputs::tensor(ctx.input(0)?.with_elem_type(to));
+
+            if to == DataType::Int64 as i32 || to == DataType::Int32 as i32 {
+                outputs.consts = vec![ctx.constant(0).cloned()];
+            }
+
+            Some(outputs)
+        }
+        "CastLike" => Some(NodeOutputs::tensor(
+            ctx.input(0)?.with_elem_type(ctx.elem_type(1)),
+        )),
+        "Constant" => infer_constant(ctx),
+        "Shape" => {
+            let shape = ctx.shape(0)?;
+            let rank = shape.len() as i64;
+            let clamp = |v: i64| (if v < 0 { v + rank } else { v }).clamp(0, rank) as usize;
+            let start = clamp(ctx.attr_int("start").unwrap_or(0));
+            let end = clamp(ctx.attr_int("end").unwrap_or(rank));
+            let values = shape.get(start..end).unwrap_or_default().to_vec();
+
+            Some(NodeOutputs::constant(
+                DataType::Int64 as i32,
+                Const::vector(values),
+            ))
+        }
+        "Size" => {
+            let size = Product::of(ctx.shape(0)?).map_or(Dim::Unknown, Product::into_dim);
+            Some(NodeOutputs::constant(
+                DataType::Int64 as i32,
+                Const {
+                    scalar: true,
+                    values: vec![size],
+                },
+            ))
+        }
+        "MatMul" | "MatMulInteger" | "QLinearMatMul" => {
+            let (a, b) = if op == "QLinearMatMul" {
+                (ctx.shape(0)?, ctx.shape(3)?)
+            } else {
+                (ctx.shape(0)?, ctx.shape(1)?)
+            };
+            let elem_type = match op {
+                "MatMulInteger" => DataType::Int32 as i32,
+                "QLinearMatMul" => ctx.elem_type(7),
+                _ => ctx.elem_type(0),
+            };
+
+            Some(NodeOutputs::tensor(Tensor::new(elem_type, matmul(a, b)?)))
+        }
+        "Gemm" => {
+            let (a, b) = (ctx.shape(0)?, ctx.shape(1)?);
+            if a.len() != 2 || b.len() != 2 {
+                return None;
+            }
+
+            let m = if ctx.attr_int("transA") == Some(1) {
+                &a[1]
+            } else {
+                &a[0]
+            };
+            let n = if ctx.attr_int("transB") == Some(1) {
+                &b[0]
+            } else {
+                &b[1]
+            };
+
+            Some(NodeOutputs::tensor(Tensor::new(
+                ctx.elem_type(0),
+                vec![m.clone(), n.clone()],
+            )))
+        }
+        "Conv" | "ConvInteger" | "QLinearConv" => {
+            let (x, w) = if op == "QLinearConv" {
+                (ctx.shape(0)?, ctx.shape(3)?)
+            } else {
+                (ctx.shape(0)?, ctx.shape(1)?)
+            };
+            let elem_type = match op {
+                "ConvInteger" => DataType::Int32 as i32,
+                "QLinearConv" => ctx.elem_type(7),
+                _ => ctx.elem_type(0),
+            };
+            let spatial = window_shape(ctx, x.get(2..)?, w.get(2..)?)?;
+
+            let mut shape = vec![x[0].clone(), w[0].clone()];
+            shape.extend(spatial);
+            Some(NodeOutputs::tensor(Tensor::new(elem_type, shape)))
+        }
+        "ConvTranspose" => infer_conv_transpose(ctx),
+        "MaxPool" | "AveragePool" | "LpPool" => {
+            let x = ctx.shape(0)?;
+            let kernel: Vec<Dim> = ctx
+                .attr_ints("kernel_shape")?
+                .iter()
+                .map(|k| Dim::Value(*k))
+                .collect();
+            let spatial = window_shape(ctx, x.get(2..)?, &kernel)?;
+
+            let mut shape = x[..2].to_vec();
+            shape.extend(spatial);
+            let output = Tensor::new(ctx.elem_type(0), shape);
+
+            let mut outputs = NodeOutputs::tensor(output.clone());
+            if op == "MaxPool" {
+                outputs
+                    .tensors
+                    .push(Some(output.with_elem_type(DataType::Int64 as i32)));
+            }
+            Some(outputs)
+        }
+        "GlobalAveragePool" | "GlobalMaxPool" | "GlobalLpPool" => {
+            let x = ctx.shape(0)?;
+            let mut shape = x.get(..2)?.to_vec();
+            shape.extend(x[2..].iter().map(|_| Dim::Value(1)));
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        "Reshape" => {
+            let input = ctx.shape(0)?;
+            let target = ctx.constant(1)?;
+            let allow_zero = ctx.attr_int("allowzero") == Some(1);
+            let shape = reshape(input, &target.values, allow_zero)?;
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        "Flatten" => {
+            let input = ctx.shape(0)?;
+            let axis = axis(ctx.attr_int("axis").unwrap_or(1), input.len() + 1)?;
+            let outer = Product::of(&input[..axis]).map_or(Dim::Unknown, Product::into_dim);
+            let inner = Product::of(&input[axis..]).map_or(Dim::Unknown, Product::into_dim);
+            Some(NodeOutputs::tensor(Tensor::new(
+                ctx.elem_type(0),
+                vec![outer, inner],
+            )))
+        }
+        "Transpose" => {
+            let input = ctx.shape(0)?;
+            let shape = match ctx.attr_ints("perm") {
+                Some(perm) => perm
+                    .iter()
+                    .map(|p| input.get(*p as usize).cloned())
+                    .collect::<Option<Vec<_>>>()?,
+                None => input.iter().rev().cloned().collect(),
+            };
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        "Squeeze" => {
+            let input = ctx.shape(0)?;
+            let shape = match ctx.axes(1) {
+                Some(axes) => {
+                    let axes: Vec<usize> = axes
+                        .iter()
+                        .map(|a| axis(*a, input.len()))
+                        .collect::<Option<_>>()?;
+                    input
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !axes.contains(i))
+                        .map(|(_, d)| d.clone())
+                        .collect()
+                }
+                None if ctx.has_input(1) => return None,
+                None => input
+                    .iter()
+                    .filter(|d| **d != Dim::Value(1))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            };
+
+            let mut outputs = NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape));
+            if let Some(constant) = ctx.constant(0).filter(|c| c.values.len() == 1) {
+                outputs.consts = vec![Some(Const {
+                    scalar: true,
+                    values: constant.values.clone(),
+                })];
+            }
+            Some(outputs)
+        }
+        "Unsqueeze" => {
+            let input = ctx.shape(0)?;
+            let axes = ctx.axes(1)?;
+            let rank = input.len() + axes.len();
+            let mut axes: Vec<usize> =
+                axes.iter().map(|a| axis(*a, rank)).collect::<Option<_>>()?;
+            axes.sort();
+
+            let mut shape = input.to_vec();
+            for a in axes {
+                shape.insert(a.min(shape.len()), Dim::Value(1));
+            }
+
+            let mut outputs = NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape));
+            if let Some(constant) = ctx.constant(0).filter(|c| c.scalar) {
+                outputs.consts = vec![Some(Const::vector(constant.values.clone()))];
+            }
+            Some(outputs)
+        }
+        "Concat" => infer_concat(ctx),
+        "Gather" => infer_gather(ctx),
+        "Slice" => infer_slice(ctx),
+        "Split" => infer_split(ctx),
+        "Expand" => {
+            let target: Vec<Dim> = ctx.constant(1)?.values.clone();
+            let shape = broadcast(ctx.shape(0)?, &target);
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        "Tile" => {
+            let input = ctx.shape(0)?;
+            let repeats = ctx.const_ints(1)?;
+            let shape = input
+                .iter()
+                .zip(repeats.iter())
+                .map(|(d, r)| match (d, r) {
+                    (d, 1) => d.clone(),
+                    (Dim::Value(v), r) => v.checked_mul(*r).map_or(Dim::Unknown, Dim::Value),
+                    _ => Dim::Unknown,
+                })
+                .collect();
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        "ConstantOfShape" => {
+            let shape = ctx.constant(0)?.values.clone();
+            let elem_type = ctx
+                .attr("value")
+                .and_then(|a| a.t.as_ref())
+                .map_or(DataType::Float as i32, |t| t.data_type);
+            Some(NodeOutputs::tensor(Tensor::new(elem_type, shape)))
+        }
+        "Pad" => {
+            let input = ctx.shape(0)?;
+            let pads = match ctx.attr_ints("pads") {
+                Some(pads) => pads.to_vec(),
+                None => ctx.const_ints(1)?,
+            };
+            if pads.len() != input.len() * 2 {
+                return None;
+            }
+
+            let shape = input
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, d)| match (d, pads[i].checked_add(pads[i + input.len()])) {
+                        (d, Some(0)) => d.clone(),
+                        (Dim::Value(v), Some(p)) => {
+                            v.checked_add(p).map_or(Dim::Unknown, Dim::Value)
+                        }
+                        _ => Dim::Unknown,
+                    },
+                )
+                .collect();
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        op if REDUCE_OPS.contains(&op) => {
+            let input = ctx.shape(0)?;
+            let keep_dims = ctx.attr_int("keepdims").unwrap_or(1) == 1;
+            let axes = ctx.axes(1);
+            let noop = ctx.attr_int("noop_with_empty_axes") == Some(1);
+
+            let axes: Vec<usize> = match axes {
+                Some(axes) if !axes.is_empty() => axes
+                    .iter()
+                    .map(|a| axis(*a, input.len()))
+                    .collect::<Option<_>>()?,
+                None if ctx.has_input(1) => return None,
+                _ if noop => Vec::new(),
+                _ => (0..input.len()).collect(),
+            };
+
+            Some(NodeOutputs::tensor(Tensor::new(
+                ctx.elem_type(0),
+                reduce(input, &axes, keep_dims),
+            )))
+        }
+        "ArgMax" | "ArgMin" => {
+            let input = ctx.shape(0)?;
+            let axis = axis(ctx.attr_int("axis").unwrap_or(0), input.len())?;
+            let keep_dims = ctx.attr_int("keepdims").unwrap_or(1) == 1;
+            Some(NodeOutputs::tensor(Tensor::new(
+                DataType::Int64 as i32,
+                reduce(input, &[axis], keep_dims),
+            )))
+        }
+        "TopK" => {
+            let input = ctx.shape(0)?;
+            let axis = axis(ctx.attr_int("axis").unwrap_or(-1), input.len())?;
+            let k = ctx
+                .constant(1)
+                .and_then(|c| c.values.first().cloned())
+                .unwrap_or(Dim::Unknown);
+
+            let mut shape = input.to_vec();
+            shape[axis] = k;
+            Some(NodeOutputs {
+                tensors: vec![
+                    Some(Tensor::new(ctx.elem_type(0), shape.clone())),
+                    Some(Tensor::new(DataType::Int64 as i32, shape)),
+                ],
+                consts: Vec::new(),
+            })
+        }
+        "Range" => {
+            let length = match (ctx.const_ints(0), ctx.const_ints(1), ctx.const_ints(2)) {
+                (Some(start), Some(limit), Some(delta))
+                    if start.len() == 1
+                        && limit.len() == 1
+                        && delta.len() == 1
+                        && delta[0] != 0 =>
+                {
+                    limit[0]
+                        .checked_sub(start[0])
+                        .and_then(|span| span.checked_add(delta[0] - delta[0].signum()))
+                        .and_then(|span| span.checked_div(delta[0]))
+                        .map_or(Dim::Unknown, |length| Dim::Value(length.max(0)))
+                }
+                _ => Dim::Unknown,
+            };
+            Some(NodeOutputs::tensor(Tensor::new(
+                ctx.elem_type(0),
+                vec![length],
+            )))
+        }
+        "NonZero" => {
+            let rank = ctx.shape(0)?.len() as i64;
+            Some(NodeOutputs::tensor(Tensor::new(
+                DataType::Int64 as i32,
+                vec![Dim::Value(rank), Dim::Unknown],
+            )))
+        }
+        "Resize" | "Upsample" => {
+            let input = ctx.shape(0)?;
+            let sizes = if ctx.has_input(3) {
+                ctx.constant(3).map(|c| c.values.clone())
+            } else {
+                None
+            };
+            let shape = sizes.unwrap_or_else(|| vec![Dim::Unknown; input.len()]);
+            Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+        }
+        "QuantizeLinear" => {
+            let elem_type = if ctx.has_input(2) {
+                ctx.elem_type(2)
+            } else {
+                DataType::Uint8 as i32
+            };
+            Some(NodeOutputs::tensor(ctx.input(0)?.with_elem_type(elem_type)))
+        }
+        "DequantizeLinear" => Some(NodeOutputs::tensor(
+            ctx.input(0)?.with_elem_type(ctx.elem_type(1)),
+        )),
+        _ => None,
+    }
+}
+
+fn matmul(a: &[Dim], b: &[Dim]) -> Option<Vec<Dim>> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Rank 1 operands are promoted and the added dimension removed again
+    let a_vector = a.len() == 1;
+    let b_vector = b.len() == 1;
+    let a: Vec<Dim> = if a_vector {
+        vec![Dim::Value(1), a[0].clone()]
+    } else {
+        a.to_vec()
+    };
+    let b: Vec<Dim> = if b_vector {
+        vec![b[0].clone(), Dim::Value(1)]
+    } else {
+        b.to_vec()
+    };
+
+    let mut shape = broadcast(&a[..a.len() - 2], &b[..b.len() - 2]);
+    if !a_vector {
+        shape.push(a[a.len() - 2].clone());
+    }
+    if !b_vector {
+        shape.push(b[b.len() - 1].clone());
+    }
+
+    Some(shape)
+}
+
+/// Spatial output dims of Conv and the pooling ops
+fn window_shape(ctx: &NodeContext, input: &[Dim], kernel: &[Dim]) -> Option<Vec<Dim>> {
+    let rank = input.len();
+    let kernel: Vec<i64> = match ctx.attr_ints("kernel_shape") {
+        Some(kernel) => kernel.to_vec(),
+        None => kernel.iter().map(Dim::value).collect::<Option<_>>()?,
+    };
+    if kernel.len() != rank {
+        return None;
+    }
+
+    let strides = ctx.attr_ints("strides").unwrap_or_default();
+    let dilations = ctx.attr_ints("dilations").unwrap_or_default();
+    let pads = ctx.attr_ints("pads").unwrap_or_default();
+    let auto_pad = ctx.attr_str("auto_pad").unwrap_or("NOTSET");
+    let ceil_mode = ctx.attr_int("ceil_mode") == Some(1);
+
+    Some(
+        (0..rank)
+            .map(|i| {
+                window_dim(
+                    &input[i],
+                    kernel[i],
+                    strides.get(i).copied().unwrap_or(1),
+                    dilations.get(i).copied().unwrap_or(1),
+                    (
+                        pads.get(i).copied().unwrap_or(0),
+                        pads.get(i + rank).copied().unwrap_or(0),
+                    ),
+                    auto_pad,
+                    ceil_mode,
+                )
+            })
+            .collect(),
+    )
+}
+
+fn infer_conv_transpose(ctx: &NodeContext) -> Option<NodeOutputs> {
+    let x = ctx.shape(0)?;
+    let w = ctx.shape(1)?;
+    let group = ctx.attr_int("group").unwrap_or(1);
+
+    let channels = match &w.get(1)? {
+        Dim::Value(c) => c.checked_mul(group).map_or(Dim::Unknown, Dim::Value),
+        _ => Dim::Unknown,
+    };
+
+    let mut shape = vec![x.first()?.clone(), channels];
+
+    if let Some(output_shape) = ctx.attr_ints("output_shape") {
+        shape.extend(output_shape.iter().map(|d| Dim::Value(*d)));
+        return Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)));
+    }
+
+    let spatial = x.get(2..)?;
+    let rank = spatial.len();
+    let kernel: Vec<i64> = match ctx.attr_ints("kernel_shape") {
+        Some(kernel) => kernel.to_vec(),
+        None => w.get(2..)?.iter().map(Dim::value).collect::<Option<_>>()?,
+    };
+    let strides = ctx.attr_ints("strides").unwrap_or_default();
+    let dilations = ctx.attr_ints("dilations").unwrap_or_default();
+    let pads = ctx.attr_ints("pads").unwrap_or_default();
+    let output_padding = ctx.attr_ints("output_padding").unwrap_or_default();
+
+    for (i, input) in spatial.iter().enumerate() {
+        let stride = strides.get(i).copied().unwrap_or(1);
+        let dilation = dilations.get(i).copied().unwrap_or(1);
+        let pad = pads.get(i).copied().unwrap_or(0);
+        let pad_end = pads.get(i + rank).copied().unwrap_or(0);
+        let extra = output_padding.get(i).copied().unwrap_or(0);
+        let kernel = *kernel.get(i)?;
+
+        shape.push(match input {
+            Dim::Value(v) if stride > 0 => v
+                .checked_sub(1)
+                .and_then(|v| v.checked_mul(stride))
+                .and_then(|v| v.checked_add(extra))
+                .zip(kernel.checked_sub(1).and_then(|k| k.checked_mul(dilation)))
+                .and_then(|(v, k)| v.checked_add(k))
+                .and_then(|v| v.checked_add(1))
+                .and_then(|v| v.checked_sub(pad))
+                .and_then(|v| v.checked_sub(pad_end))
+                .map_or(Dim::Unknown, Dim::Value),
+            _ => Dim::Unknown,
+        });
+    }
+
+    Some(NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape)))
+}
+
+fn reshape(input: &[Dim], target: &[Dim], allow_zero: bool) -> Option<Vec<Dim>> {
+    let mut shape: Vec<Dim> = target
+        .iter()
+        .enumerate()
+        .map(|(i, d)| match d {
+            Dim::Value(0) if !allow_zero => input.get(i).cloned().unwrap_or(Dim::Unknown),
+            d => d.clone(),
+        })
+        .collect();
+
+    if let Some(infer_index) = shape.iter().position(|d| *d == Dim::Value(-1)) {
+        let known: Vec<Dim> = shape
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != infer_index)
+            .map(|(_, d)| d.clone())
+            .collect();
+
+        shape[infer_index] = Product::of(input)
+            .zip(Product::of(&known))
+            .and_then(|(total, known)| total.divide(known))
+            .map_or(Dim::Unknown, Product::into_dim);
+    }
+
+    Some(shape)
+}
+
+fn reduce(input: &[Dim], axes: &[usize], keep_dims: bool) -> Vec<Dim> {
+    input
+        .iter()
+        .enumerate()
+        .filter_map(|(i, d)| match (axes.contains(&i), keep_dims) {
+            (false, _) => Some(d.clone()),
+            (true, true) => Some(Dim::Value(1)),
+            (true, false) => None,
+        })
+        .collect()
+}
+
+fn infer_constant(ctx: &NodeContext) -> Option<NodeOutputs> {
+    use attribute_proto::AttributeType;
+
+    let attr = ctx.node.attribute.first()?;
+
+    match AttributeType::from_i32(attr.r#type)? {
+        AttributeType::Tensor => {
+            let tensor = attr.t.as_ref()?;
+            let shape = tensor.dims.iter().map(|d| Dim::Value(*d)).collect();
+            Some(NodeOutputs {
+                tensors: vec![Some(Tensor::new(tensor.data_type, shape))],
+                consts: vec![Const::from_tensor(tensor)],
+            })
+        }
+        AttributeType::Int => Some(NodeOutputs::constant(
+            DataType::Int64 as i32,
+            Const {
+                scalar: true,
+                values: vec![Dim::Value(attr.i)],
+            },
+        )),
+        AttributeType::Ints => Some(NodeOutputs::constant(
+            DataType::Int64 as i32,
+            Const::vector(attr.ints.iter().map(|i| Dim::Value(*i)).collect()),
+        )),
+        AttributeType::Float => Some(NodeOutputs::tensor(Tensor::new(
+            DataType::Float as i32,
+            Vec::new(),
+        ))),
+        AttributeType::Floats => Some(NodeOutputs::tensor(Tensor::new(
+            DataType::Float as i32,
+            vec![Dim::Value(attr.floats.len() as i64)],
+        ))),
+        _ => None,
+    }
+}
+
+/// Arithmetic on statically known shape values, e.g. `batch * 12`
+fn const_arithmetic(ctx: &NodeContext, op: &str) -> Option<Const> {
+    let a = ctx.constant(0)?;
+    let b = ctx.constant(1)?;
+    let len = a.values.len().max(b.values.len());
+    let get = |c: &Const, i: usize| -> Option<Dim> {
+        match c.values.len() {
+            1 => Some(c.values[0].clone()),
+            _ => c.values.get(i).cloned(),
+        }
+    };
+
+    let values = (0..len)
+        .map(|i| {
+            let (a, b) = (get(a, i)?, get(b, i)?);
+            Some(match (op, a, b) {
+                ("Add", Dim::Value(a), Dim::Value(b)) => {
+                    a.checked_add(b).map_or(Dim::Unknown, Dim::Value)
+                }
+                ("Sub", Dim::Value(a), Dim::Value(b)) => {
+                    a.checked_sub(b).map_or(Dim::Unknown, Dim::Value)
+                }
+                ("Div", Dim::Value(a), Dim::Value(b)) => {
+                    a.checked_div_euclid(b).map_or(Dim::Unknown, Dim::Value)
+                }
+                ("Mul", a, b) => Product::of(&[a, b]).map_or(Dim::Unknown, Product::into_dim),
+                ("Div", a, b) => Product::of(&[a])
+                    .zip(Product::of(&[b]))
+                    .and_then(|(a, b)| a.divide(b))
+                    .map_or(Dim::Unknown, Product::into_dim),
+                _ => Dim::Unknown,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Const {
+        scalar: a.scalar && b.scalar,
+        values,
+    })
+}
+
+fn infer_concat(ctx: &NodeContext) -> Option<NodeOutputs> {
+    let first = ctx.shape(0)?;
+    let axis = axis(ctx.attr_int("axis")?, first.len())?;
+    let mut shape = first.to_vec();
+
+    for index in 1..ctx.node.input.len() {
+        let other = ctx.shape(index)?;
+        shape[axis] = match (&shape[axis], other.get(axis)?) {
+            (Dim::Value(a), Dim::Value(b)) => a.checked_add(*b).map_or(Dim::Unknown, Dim::Value),
+            _ => Dim::Unknown,
+        };
+    }
+
+    let mut outputs = NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape));
+
+    let consts: Option<Vec<&Const>> = (0..ctx.node.input.len()).map(|i| ctx.constant(i)).collect();
+
+    if let Some(consts) = consts {
+        outputs.consts = vec![Some(Const::vector(
+            consts
+                .into_iter()
+                .flat_map(|c| c.values.iter().cloned())
+                .collect(),
+        ))];
+    }
+
+    Some(outputs)
+}
+
+fn infer_gather(ctx: &NodeContext) -> Option<NodeOutputs> {
+    let data = ctx.shape(0)?;
+    let indices = ctx.shape(1)?;
+    let axis = axis(ctx.attr_int("axis").unwrap_or(0), data.len())?;
+
+    let mut shape = data[..axis].to_vec();
+    shape.extend(indices.iter().cloned());
+    shape.extend(data[axis + 1..].iter().cloned());
+
+    let mut outputs = NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape));
+
+    if let (Some(values), Some(indices)) = (ctx.constant(0), ctx.constant(1)) {
+        let len = values.values.len() as i64;
+        let gathered: Option<Vec<Dim>> = indices
+            .values
+            .iter()
+            .map(|i| {
+                let i = i.value()?;
+                let i = if i < 0 { i + len } else { i };
+                values.values.get(i as usize).cloned()
+            })
+            .collect();
+
+        outputs.consts = vec![gathered.map(|values| Const {
+            scalar: indices.scalar,
+            values,
+        })];
+    }
+
+    Some(outputs)
+}
+
+fn infer_slice(ctx: &NodeContext) -> Option<NodeOutputs> {
+    let input = ctx.shape(0)?;
+    let rank = input.len();
+
+    let (starts, ends, axes, steps) = match ctx.attr_ints("starts") {
+        Some(starts) => (
+            starts.to_vec(),
+            ctx.attr_ints("ends")?.to_vec(),
+            ctx.attr_ints("axes").map(|a| a.to_vec()),
+            None,
+        ),
+        None => (
+            ctx.const_ints(1)?,
+            ctx.const_ints(2)?,
+            if ctx.has_input(3) {
+                Some(ctx.const_ints(3)?)
+            } else {
+                None
+            },
+            if ctx.has_input(4) {
+                Some(ctx.const_ints(4)?)
+            } else {
+                None
+            },
+        ),
+    };
+
+    let axes = axes.unwrap_or_else(|| (0..starts.len() as i64).collect());
+    let steps = steps.unwrap_or_else(|| vec![1; starts.len()]);
+
+    let mut shape = input.to_vec();
+    let mut ranges = Vec::new();
+
+    for (i, a) in axes.iter().enumerate() {
+        let a = axis(*a, rank)?;
+        let (start, end, step) = (*starts.get(i)?, *ends.get(i)?, *steps.get(i)?);
+
+        let dim = match &input[a] {
+            Dim::Value(len) => match slice_range(*len, start, end, step) {
+                Some(range) => {
+                    ranges.push((a, range));
+                    Dim::Value(range.2)
+                }
+                None => Dim::Unknown,
+            },
+            d if start == 0 && end >= i32::MAX as i64 && step == 1 => d.clone(),
+            _ => Dim::Unknown,
+        };
+
+        shape[a] = dim;
+    }
+
+    let mut outputs = NodeOutputs::tensor(Tensor::new(ctx.elem_type(0), shape));
+
+    if let Some(constant) = ctx.constant(0).filter(|c| !c.scalar) {
+        if let [(0, (start, step, len))] = ranges.as_slice() {
+            // The declared shape may disagree with the values actually held
+            let values: Option<Vec<Dim>> = (0..*len)
+                .map(|i| {
+                    let index = usize::try_from(start + i * step).ok()?;
+                    constant.values.get(index).cloned()
+                })
+                .collect();
+            outputs.consts = vec![values.map(Const::vector)];
+        }
+    }
+
+    Some(outputs)
+}
+
+/// Clamped `(start, step, length)` of a slice along a dimension of size `len`
+fn slice_range(len: i64, start: i64, end: i64, step: i64) -> Option<(i64, i64, i64)> {
+    if len < 0 || step == 0 {
+        return None;
+    }
+
+    let clamp = |v: i64, low: i64, high: i64| {
+        let v = if v < 0 { v + len } else { v };
+        v.clamp(low, high)
+    };
+
+    let (start, span, stride) = if step > 0 {
+        let start = clamp(start, 0, len);
+        let end = clamp(end, 0, len);
+        (start, end - start, step)
+    } else {
+        let start = clamp(start, -1, len - 1);
+        let end = clamp(end, -1, len - 1);
+        // Any stride beyond the dimension takes a single element
+        (start, start - end, step.checked_neg().unwrap_or(i64::MAX))
+    };
+
+    let length = if span > 0 { (span - 1) / stride + 1 } else { 0 };
+    Some((start, step, length))
+}
+
+fn infer_split(ctx: &NodeContext) -> Option<NodeOutputs> {
+    let input = ctx.shape(0)?;
+    let axis = axis(ctx.attr_int("axis").unwrap_or(0), input.len())?;
+    let outputs = ctx.node.output.len();
+    if outputs == 0 {
+        return None;
+    }
+
+    let split = match ctx.attr_ints("split") {
+        Some(split) => Some(split.to_vec()),
+        None if ctx.has_input(1) => ctx.const_ints(1),
+        None => None,
+    };
+
+    let sizes: Vec<Dim> = match (split, &input[axis]) {
+        (Some(split), _) => split.into_iter().map(Dim::Value).collect(),
+        (None, Dim::Value(len)) if *len >= 0 => {
+            let outputs = outputs as i64;
+            let chunk = len / outputs + i64::from(len % outputs != 0);
+            (0..outputs)
+                .map(|i| Dim::Value((len - i * chunk).clamp(0, chunk)))
+                .collect()
+        }
+        (None, _) => vec![Dim::Unknown; outputs],
+    };
+
+    Some(NodeOutputs {
+        tensors: sizes
+            .into_iter()
+            .map(|size| {
+                let mut shape = input.to_vec();
+                shape[axis] = size;
+                Some(Tensor::new(ctx.elem_type(0), shape))
+            })
+            .collect(),
+        consts: Vec::new(),
+    })
+}
+
+impl ValueInfo {
+    /// Shape of the value as declared or inferred, `None` if the rank is unknown
+    pub fn shape(&self) -> Option<Vec<Dim>> {
+        Tensor::from_type(self.type_proto()?)?.shape
+    }
+}
+
+/// Strides inference cannot divide by, reported instead of inferring through
+fn invalid_strides(node_id: usize, node: &NodeProto) -> Option<Finding> {
+    let strides = node.attribute.iter().find(|a| a.name == "strides")?;
+
+    strides.ints.iter().any(|s| *s <= 0).then(|| Finding {
+        severity: Severity::Error,
+        location: Location::Node {
+            index: node_id,
+            name: node.name.clone(),
+            op_type: node.op_type.clone(),
+        },
+        message: format!("Strides {:?} must be positive", strides.ints),
+    })
+}
+
+fn has_shape(ty: &TypeProto) -> bool {
+    matches!(
+        ty.value.as_ref(),
+        Some(type_proto::Value::TensorType(type_proto::Tensor {
+            shape: Some(_),
+            ..
+        }))
+    )
+}
+
+impl OnnxModel {
+    /// Infer types of values in the graph that do not have a declared shape.
+    ///
+    /// Shapes are propagated in topological order and keep symbolic dims such
+    /// as `batch`, combining them into products like `batch*seq` when a
+    /// reshape folds them together. Small integer tensors with statically
+    /// known values, mostly `Shape` outputs and the ops slicing them, are
+    /// tracked alongside so that `Reshape` and `Expand` resolve on exporter
+    /// output.
+    pub(crate) fn infer_shapes(&mut self) {
+        let mut tensors: HashMap<String, Tensor> = HashMap::new();
+        let mut consts: HashMap<String, Const> = HashMap::new();
+        let mut findings = Vec::new();
+
+        for value in self.values.values.iter() {
+            if let Some(tensor) = value.proto.r#type.as_ref().and_then(Tensor::from_type) {
+                tensors.insert(value.name().to_string(), tensor);
+            }
+        }
+
+        for init in self.graph_proto().initializer.iter() {
+            if let Some(constant) = Const::from_tensor(init) {
+                consts.insert(init.name.clone(), constant);
+            }
+        }
+
+        // Fall back to file order when the graph has cycles
+        let order: Vec<usize> =
+            toposort(&self.node_graph, None).unwrap_or_else(|_| (0..self.nodes.len()).collect());
+
+        for node_id in order {
+            let node = &self.nodes[node_id].proto;
+
+            if !is_onnx_domain(&node.domain) {
+                continue;
+            }
+
+            if let Some(finding) = invalid_strides(node_id, node) {
+                findings.push(finding);
+            }
+
+            let ctx = NodeContext {
+                node,
+                inputs: node.input.iter().map(|i| tensors.get(i)).collect(),
+                consts: node.input.iter().map(|i| consts.get(i)).collect(),
+            };
+
+            let Some(outputs) = infer_node(&ctx) else {
+                continue;
+            };
+
+            for (name, tensor) in node.output.iter().zip(outputs.tensors) {
+                match tensor {
+                    // Declared types take precedence unless they lack a shape
+                    Some(tensor) if !name.is_empty() => {
+                        let declared = tensors.get(name);
+                        if declared.is_none_or(|d| d.shape.is_none()) {
+                            tensors.insert(name.clone(), tensor);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            for (name, constant) in node.output.iter().zip(outputs.consts) {
+                if let Some(constant) = constant {
+                    consts.insert(name.clone(), constant);
+                }
+            }
+        }
+
+        for value in self.values.values.iter_mut() {
+            if value.proto.r#type.as_ref().is_some_and(has_shape) {
+                continue;
+            }
+
+            if let Some(tensor) = tensors.get(value.name()) {
+                if tensor.shape.is_some() {
+                    value.inferred = Some(tensor.to_type());
+                }
+            }
+        }
+
+        self.diagnostics.extend(findings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{int, ints, node, tensor};
+
+    fn dims(values: &[i64]) -> Vec<Dim> {
+        values.iter().map(|v| Dim::Value(*v)).collect()
+    }
+
+    fn param(name: &str) -> Dim {
+        Dim::Param(name.to_string())
+    }
+
+    fn infer(
+        node: &NodeProto,
+        inputs: &[Option<Tensor>],
+        consts: &[Option<Const>],
+    ) -> Option<NodeOutputs> {
+        infer_node(&NodeContext {
+            node,
+            inputs: inputs.iter().map(Option::as_ref).collect(),
+            consts: consts.iter().map(Option::as_ref).collect(),
+        })
+    }
+
+    fn float(shape: Vec<Dim>) -> Option<Tensor> {
+        Some(Tensor::new(DataType::Float as i32, shape))
+    }
+
+    fn output_shape(outputs: &NodeOutputs, index: usize) -> Option<Vec<Dim>> {
+        outputs.tensors.get(index)?.as_ref()?.shape.clone()
+    }
+
+    #[test]
+    fn window_dim_explicit_padding() {
+        let input = Dim::Value(224);
+        assert_eq!(
+            window_dim(&input, 7, 2, 1, (3, 3), "NOTSET", false),
+            Dim::Value(112)
+        );
+        assert_eq!(
+            window_dim(&input, 3, 2, 1, (0, 0), "VALID", false),
+            Dim::Value(111)
+        );
+        assert_eq!(
+            window_dim(&input, 3, 2, 1, (0, 0), "VALID", true),
+            Dim::Value(112)
+        );
+        assert_eq!(
+            window_dim(&input, 3, 1, 2, (0, 0), "NOTSET", false),
+            Dim::Value(220)
+        );
+    }
+
+    #[test]
+    fn window_dim_same_padding() {
+        assert_eq!(
+            window_dim(&Dim::Value(15), 3, 2, 1, (0, 0), "SAME_UPPER", false),
+            Dim::Value(8)
+        );
+        assert_eq!(
+            window_dim(&param("h"), 3, 1, 1, (0, 0), "SAME_LOWER", false),
+            param("h")
+        );
+        assert_eq!(
+            window_dim(&param("h"), 3, 2, 1, (0, 0), "SAME_LOWER", false),
+            Dim::Unknown
+        );
+    }
+
+    #[test]
+    fn window_dim_keeps_symbolic_size_preserving_windows() {
+        assert_eq!(
+            window_dim(&param("w"), 3, 1, 1, (1, 1), "NOTSET", false),
+            param("w")
+        );
+        assert_eq!(
+            window_dim(&param("w"), 3, 1, 1, (0, 0), "NOTSET", false),
+            Dim::Unknown
+        );
+    }
+
+    #[test]
+    fn window_dim_rejects_bad_strides_and_overflow() {
+        for stride in [0, -1] {
+            assert_eq!(
+                window_dim(&Dim::Value(8), 3, stride, 1, (0, 0), "NOTSET", false),
+                Dim::Unknown
+            );
+            assert_eq!(
+                window_dim(&Dim::Value(8), 3, stride, 1, (0, 0), "SAME_UPPER", false),
+                Dim::Unknown
+            );
+        }
+
+        assert_eq!(
+            window_dim(&Dim::Value(i64::MAX), 1, 1, 1, (1, 1), "NOTSET", false),
+            Dim::Unknown
+        );
+        assert_eq!(
+            window_dim(&Dim::Value(8), i64::MIN, 1, 2, (0, 0), "NOTSET", false),
+            Dim::Unknown
+        );
+    }
+
+    #[test]
+    fn broadcast_shapes() {
+        assert_eq!(
+            broadcast(&dims(&[8, 1, 6, 1]), &dims(&[7, 1, 5])),
+            dims(&[8, 7, 6, 5])
+        );
+        assert_eq!(
+            broadcast(&[param("batch"), Dim::Value(1)], &dims(&[4])),
+            vec![param("batch"), Dim::Value(4)]
+        );
+        assert_eq!(broadcast(&[param("a")], &[param("b")]), vec![Dim::Unknown]);
+        assert_eq!(broadcast(&[param("a")], &dims(&[3])), dims(&[3]));
+    }
+
+    #[test]
+    fn reshape_infers_missing_dim() {
+        assert_eq!(
+            reshape(&dims(&[2, 3, 4]), &dims(&[0, -1]), false),
+            Some(dims(&[2, 12]))
+        );
+        assert_eq!(
+            reshape(&dims(&[2, 3, 4]), &dims(&[0, -1]), true),
+            Some(vec![Dim::Value(0), Dim::Unknown])
+        );
+        assert_eq!(
+            reshape(&dims(&[5, 7]), &dims(&[3, -1]), false),
+            Some(vec![Dim::Value(3), Dim::Unknown])
+        );
+    }
+
+    #[test]
+    fn reshape_folds_symbolic_dims() {
+        let input = vec![param("batch"), param("seq"), Dim::Value(768)];
+        assert_eq!(
+            reshape(&input, &dims(&[-1, 768]), false),
+            Some(vec![param("batch*seq"), Dim::Value(768)])
+        );
+        assert_eq!(
+            reshape(
+                &input,
+                &[param("batch"), param("seq"), Dim::Value(12), Dim::Value(-1)],
+                false
+            ),
+            Some(vec![
+                param("batch"),
+                param("seq"),
+                Dim::Value(12),
+                Dim::Value(64)
+            ])
+        );
+    }
+
+    #[test]
+    fn product_divide_does_not_overflow() {
+        let total = Product::of(&[Dim::Value(i64::MIN)]).unwrap();
+        let divisor = Product::of(&[Dim::Value(-1)]).unwrap();
+        assert!(total.divide(divisor).is_none());
+
+        assert!(Product::of(&[Dim::Value(i64::MAX), Dim::Value(2)]).is_none());
+    }
+
+    #[test]
+    fn conv_with_zero_strides_is_unknown() {
+        let conv = NodeProto {
+            attribute: vec![ints("strides", &[0, 0])],
+            ..node("Conv", &["x", "w"], &["y"])
+        };
+        let outputs = infer(
+            &conv,
+            &[float(dims(&[1, 3, 8, 8])), float(dims(&[4, 3, 3, 3]))],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            output_shape(&outputs, 0),
+            Some(vec![
+                Dim::Value(1),
+                Dim::Value(4),
+                Dim::Unknown,
+                Dim::Unknown
+            ])
+        );
+        assert!(invalid_strides(0, &conv).is_some());
+    }
+
+    #[test]
+    fn split_without_outputs() {
+        let split = node("Split", &["x"], &[]);
+        assert!(infer(&split, &[float(dims(&[6]))], &[]).is_none());
+    }
+
+    #[test]
+    fn split_into_uneven_chunks() {
+        let split = node("Split", &["x"], &["a", "b", "c"]);
+        let outputs = infer(&split, &[float(dims(&[7, 2]))], &[]).unwrap();
+
+        let sizes: Vec<Option<Vec<Dim>>> = (0..3).map(|i| output_shape(&outputs, i)).collect();
+        assert_eq!(
+            sizes,
+            vec![
+                Some(dims(&[3, 2])),
+                Some(dims(&[3, 2])),
+                Some(dims(&[1, 2]))
+            ]
+        );
+    }
+
+    #[test]
+    fn slice_with_fewer_starts_than_axes() {
+        let slice = NodeProto {
+            attribute: vec![
+                ints("axes", &[0, 1]),
+                ints("starts", &[0]),
+                ints("ends", &[1, 1]),
+            ],
+            ..node("Slice", &["x"], &["y"])
+        };
+        assert!(infer(&slice, &[float(dims(&[4, 4]))], &[]).is_none());
+    }
+
+    #[test]
+    fn slice_of_constant_shorter_than_declared() {
+        let slice = NodeProto {
+            attribute: vec![ints("starts", &[1]), ints("ends", &[4])],
+            ..node("Slice", &["x"], &["y"])
+        };
+        let data = Some(Tensor::new(DataType::Int64 as i32, dims(&[4])));
+        let constant = Some(Const::vector(dims(&[1, 2])));
+
+        let outputs = infer(&slice, &[data], &[constant]).unwrap();
+        assert_eq!(output_shape(&outputs, 0), Some(dims(&[3])));
+        assert!(outputs.consts[0].is_none());
+    }
+
+    #[test]
+    fn slice_of_shape_constant() {
+        let slice = NodeProto {
+            attribute: vec![ints("starts", &[-2]), ints("ends", &[i64::MAX])],
+            ..node("Slice", &["x"], &["y"])
+        };
+        let data = Some(Tensor::new(DataType::Int64 as i32, dims(&[3])));
+        let constant = Some(Const::vector(vec![
+            param("batch"),
+            Dim::Value(4),
+            Dim::Value(5),
+        ]));
+
+        let outputs = infer(&slice, &[data], &[constant]).unwrap();
+        assert_eq!(outputs.consts[0].as_ref().unwrap().values, dims(&[4, 5]));
+    }
+
+    #[test]
+    fn constants_from_initializers() {
+        let int64 = |dims: &[i64], values: &[i64]| TensorProto {
+            int64_data: values.to_vec(),
+            ..tensor("c", DataType::Int64, dims)
+        };
+
+        let shape = Const::from_tensor(&int64(&[3], &[1, -1, 4])).unwrap();
+        assert_eq!(shape.values, dims(&[1, -1, 4]));
+        assert!(Const::from_tensor(&int64(&[], &[7])).unwrap().scalar);
+
+        let raw = TensorProto {
+            raw_data: [2i32.to_le_bytes(), 5i32.to_le_bytes()].concat().into(),
+            ..tensor("c", DataType::Int32, &[2])
+        };
+        assert_eq!(Const::from_tensor(&raw).unwrap().values, dims(&[2, 5]));
+
+        // Too large, mismatched or negative lengths are not decoded
+        let large = TensorProto {
+            raw_data: vec![0; 8 * 1000].into(),
+            ..tensor("c", DataType::Int64, &[1000])
+        };
+        assert!(Const::from_tensor(&large).is_none());
+        assert!(Const::from_tensor(&int64(&[2], &[1, 2, 3])).is_none());
+        assert!(Const::from_tensor(&int64(&[-1], &[])).is_none());
+        assert!(Const::from_tensor(&int64(&[1, 1], &[1])).is_none());
+    }
+
+    #[test]
+    fn slice_range_extreme_steps() {
+        assert_eq!(slice_range(10, 0, 10, 3), Some((0, 3, 4)));
+        assert_eq!(slice_range(10, -1, i64::MIN, -1), Some((9, -1, 10)));
+        assert_eq!(slice_range(10, 0, 10, i64::MAX), Some((0, i64::MAX, 1)));
+        assert_eq!(slice_range(10, 9, 0, i64::MIN), Some((9, i64::MIN, 1)));
+        assert_eq!(slice_range(10, 0, 10, 0), None);
+    }
+
+    #[test]
+    fn arithmetic_on_crafted_dims_is_unknown() {
+        let tile = node("Tile", &["x", "repeats"], &["y"]);
+        let outputs = infer(
+            &tile,
+            &[float(dims(&[i64::MAX, 2])), None],
+            &[None, Some(Const::vector(dims(&[2, 3])))],
+        )
+        .unwrap();
+        assert_eq!(
+            output_shape(&outputs, 0),
+            Some(vec![Dim::Unknown, Dim::Value(6)])
+        );
+
+        let concat = NodeProto {
+            attribute: vec![int("axis", 0)],
+            ..node("Concat", &["a", "b"], &["c"])
+        };
+        let outputs = infer(&concat, &[float(dims(&[i64::MAX])), float(dims(&[1]))], &[]).unwrap();
+        assert_eq!(output_shape(&outputs, 0), Some(vec![Dim::Unknown]));
+
+        let add = node("Add", &["a", "b"], &["c"]);
+        let int64 = Some(Tensor::new(DataType::Int64 as i32, dims(&[1])));
+        let outputs = infer(
+            &add,
+            &[int64.clone(), int64],
+            &[
+                Some(Const::vector(dims(&[i64::MAX]))),
+                Some(Const::vector(dims(&[1]))),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            outputs.consts[0].as_ref().unwrap().values,
+            vec![Dim::Unknown]
+        );
+
+        let conv_transpose = NodeProto {
+            attribute: vec![ints("strides", &[i64::MAX])],
+            ..node("ConvTranspose", &["x", "w"], &["y"])
+        };
+        let outputs = infer(
+            &conv_transpose,
+            &[float(dims(&[1, 2, 8])), float(dims(&[2, 4, 3]))],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            output_shape(&outputs, 0),
+            Some(vec![Dim::Value(1), Dim::Value(4), Dim::Unknown])
+        );
+    }
+}
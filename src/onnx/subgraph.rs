@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use crate::onnx::onnx_proto::{GraphProto, NodeProto};
+use crate::onnx::summary::{SubgraphEntry, SubgraphSummary};
+use crate::onnx::{NodeId, NodeInfo, OnnxModel};
+
+pub type SubgraphId = usize;
+
+/// Graph held in a graph attribute of a control flow node such as `If`,
+/// `Loop` or `Scan`
+pub struct Subgraph {
+    /// Subgraph holding the owning node, `None` when it is in the main graph
+    pub parent: Option<SubgraphId>,
+    /// Index of the owning node within its graph
+    pub node: NodeId,
+    pub attribute: String,
+    pub name: String,
+    /// Nesting level, 1 for subgraphs of main graph nodes
+    pub depth: usize,
+    nodes: Vec<NodeInfo>,
+    /// Values captured from enclosing graphs, in order of first use
+    pub outer_refs: Vec<String>,
+}
+
+/// Graphs held by a node's attributes along with the attribute names
+pub fn node_subgraphs(node: &NodeProto) -> impl Iterator<Item = (&str, &GraphProto)> {
+    node.attribute.iter().flat_map(|attr| {
+        attr.g
+            .iter()
+            .chain(attr.graphs.iter())
+            .map(move |g| (attr.name.as_str(), g))
+    })
+}
+
+/// Collects the subgraphs of a graph and all of their descendants
+pub struct SubgraphCollector {
+    pub subgraphs: Vec<Subgraph>,
+}
+
+impl SubgraphCollector {
+    /// Add the subgraphs held by `node` and return the outer-scope values
+    /// they reference, which are implicit inputs of `node`
+    pub fn add_node(
+        &mut self,
+        parent: Option<SubgraphId>,
+        node_id: NodeId,
+        node: &NodeProto,
+        depth: usize,
+    ) -> Vec<String> {
+        let mut refs = Vec::new();
+
+        for (attribute, graph) in node_subgraphs(node) {
+            for name in self.add_graph(parent, node_id, attribute, graph, depth + 1) {
+                if !refs.contains(&name) {
+                    refs.push(name);
+                }
+            }
+        }
+
+        refs
+    }
+
+    fn add_graph(
+        &mut self,
+        parent: Option<SubgraphId>,
+        node: NodeId,
+        attribute: &str,
+        graph: &GraphProto,
+        depth: usize,
+    ) -> Vec<String> {
+        let subgraph_id = self.subgraphs.len();
+        self.subgraphs.push(Subgraph {
+            parent,
+            node,
+            attribute: attribute.to_string(),
+            name: graph.name.clone(),
+            depth,
+            nodes: graph
+                .node
+                .iter()
+                .map(|n| NodeInfo { proto: n.clone() })
+                .collect(),
+            outer_refs: Vec::new(),
+        });
+
+        let mut local: HashSet<&str> = graph
+            .input
+            .iter()
+            .map(|i| i.name.as_str())
+            .chain(graph.initializer.iter().map(|i| i.name.as_str()))
+            .chain(
+                graph
+                    .sparse_initializer
+                    .iter()
+                    .filter_map(|i| i.values.as_ref().map(|v| v.name.as_str())),
+            )
+            .collect();
+
+        let mut outer_refs: Vec<String> = Vec::new();
+        let mut add_ref = |local: &HashSet<&str>, name: &str| {
+            if !name.is_empty() && !local.contains(name) && !outer_refs.iter().any(|r| r == name) {
+                outer_refs.push(name.to_string());
+            }
+        };
+
+        // Nodes are topologically sorted so a value is always produced before
+        // it is used within the graph
+        for (node_id, node) in graph.node.iter().enumerate() {
+            for input in node.input.iter() {
+                add_ref(&local, input);
+            }
+
+            for name in self.add_node(Some(subgraph_id), node_id, node, depth) {
+                add_ref(&local, &name);
+            }
+
+            local.extend(node.output.iter().map(|o| o.as_str()));
+        }
+
+        self.subgraphs[subgraph_id].outer_refs = outer_refs.clone();
+
+        outer_refs
+    }
+}
+
+impl Subgraph {
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeProto> {
+        self.nodes.iter().map(|n| &n.proto)
+    }
+}
+
+impl OnnxModel {
+    fn owner_node(&self, subgraph: &Subgraph) -> &NodeProto {
+        match subgraph.parent {
+            None => &self.nodes[subgraph.node].proto,
+            Some(parent) => &self.subgraphs[parent].nodes[subgraph.node].proto,
+        }
+    }
+
+    pub fn subgraph_summary(&self) -> SubgraphSummary<'_> {
+        SubgraphSummary {
+            nested_nodes: self.subgraphs.iter().map(|s| s.nodes.len()).sum(),
+            max_depth: self.subgraphs.iter().map(|s| s.depth).max().unwrap_or(0),
+            subgraphs: self
+                .subgraphs
+                .iter()
+                .map(|subgraph| {
+                    let owner = self.owner_node(subgraph);
+
+                    SubgraphEntry {
+                        owner: if owner.name.is_empty() {
+                            &owner.op_type
+                        } else {
+                            &owner.name
+                        },
+                        op_type: &owner.op_type,
+                        attribute: &subgraph.attribute,
+                        name: &subgraph.name,
+                        depth: subgraph.depth,
+                        nodes: subgraph.nodes.len(),
+                        outer_refs: subgraph.outer_refs.iter().map(|r| r.as_str()).collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
@@ -3,8 +3,9 @@ use std::{fmt, io};
 use console::Style;
 use serde::{Serialize, Serializer};
 
-use crate::onnx::TypeInfo;
-use crate::summary::Summary;
+use crate::check::Finding;
+use crate::onnx::{NodeId, TypeInfo};
+use crate::summary::{ByteSize, Count, Summary};
 
 #[derive(Serialize)]
 pub struct OnnxOpset<'a> {
@@ -33,6 +34,8 @@ pub struct OnnxSummary<'a> {
     pub name: &'a str,
     pub version: i64,
     pub doc_string: &'a str,
+    pub graph_doc_string: &'a str,
+    pub metadata: Vec<MetadataEntry<'a>>,
     pub producer_name: &'a str,
     pub producer_version: &'a str,
     pub ir_version: i64,
@@ -40,6 +43,26 @@ pub struct OnnxSummary<'a> {
     pub inputs: Vec<Value<'a>>,
     pub outputs: Vec<Value<'a>>,
     pub operator_summary: OperatorUsageSummary<'a>,
+    pub initializers: WeightSummary<'a>,
+    pub constants: WeightSummary<'a>,
+    pub external_files: Vec<ExternalFile<'a>>,
+    pub value_types: ValueTypeSummary,
+    pub compute: ComputeSummary<'a>,
+    pub subgraphs: SubgraphSummary<'a>,
+    pub functions: Vec<FunctionSummary<'a>>,
+    pub node_doc_strings: Vec<NodeDocString<'a>>,
+    pub training_info: Vec<TrainingInfo<'a>>,
+    pub quantization: Option<QuantizationSummary<'a>>,
+    pub blocks: Vec<RepeatedBlock<'a>>,
+    pub modules: ModuleNode<'a>,
+    /// Levels of the module tree shown in text output
+    #[serde(skip)]
+    pub module_depth: usize,
+    /// Node doc strings shown in text output
+    #[serde(skip)]
+    pub top: usize,
+    /// Problems found while loading the model
+    pub diagnostics: &'a [Finding],
 }
 
 impl<'a> Summary for OnnxSummary<'a> {
@@ -53,6 +76,59 @@ pub struct OperatorUsage<'a> {
     pub domain: &'a str,
     pub name: &'a str,
     pub count: usize,
+    /// Nodes inside control flow subgraphs
+    pub nested_count: usize,
+    /// Whether the operator is a model-local function
+    pub function: bool,
+}
+
+/// Model-local function and the operators its body uses
+#[derive(Serialize)]
+pub struct FunctionSummary<'a> {
+    pub domain: &'a str,
+    pub name: &'a str,
+    pub inputs: Vec<&'a str>,
+    pub outputs: Vec<&'a str>,
+    pub attributes: Vec<&'a str>,
+    pub calls: usize,
+    pub inlined_calls: usize,
+    pub operators: Vec<OperatorUsage<'a>>,
+}
+
+impl<'a> fmt::Display for FunctionSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "    {}.{}({}) -> ({}): {} calls",
+            self.domain,
+            self.name,
+            self.inputs.join(", "),
+            self.outputs.join(", "),
+            self.calls
+        )?;
+
+        if self.inlined_calls > 0 {
+            write!(f, ", {} inlined", self.inlined_calls)?;
+        }
+
+        writeln!(f)?;
+
+        if !self.attributes.is_empty() {
+            writeln!(f, "        attributes: {}", self.attributes.join(", "))?;
+        }
+
+        for oper in self.operators.iter() {
+            write!(f, "        {}.{}: {}", oper.domain, oper.name, oper.count)?;
+
+            if oper.function {
+                write!(f, " (function)")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Serialize)]
@@ -60,6 +136,793 @@ pub struct OperatorUsageSummary<'a> {
     pub operators: Vec<OperatorUsage<'a>>,
 }
 
+#[derive(Serialize)]
+pub struct DataTypeWeights {
+    pub data_type: String,
+    pub parameters: u64,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct Weight<'a> {
+    pub name: &'a str,
+    pub ty: String,
+    pub parameters: u64,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct WeightSummary<'a> {
+    pub count: usize,
+    pub parameters: u64,
+    pub bytes: u64,
+    pub data_types: Vec<DataTypeWeights>,
+    pub largest: Vec<Weight<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct OpTypeCompute<'a> {
+    pub op_type: &'a str,
+    pub nodes: usize,
+    pub macs: u64,
+}
+
+#[derive(Serialize)]
+pub struct NodeCompute<'a> {
+    pub name: &'a str,
+    pub op_type: &'a str,
+    pub macs: u64,
+}
+
+/// Estimated multiply-accumulate counts of the compute heavy nodes
+#[derive(Serialize)]
+pub struct ComputeSummary<'a> {
+    pub macs: u64,
+    pub flops: u64,
+    /// Nodes that could not be estimated because of unknown shapes
+    pub unresolved_nodes: usize,
+    /// Symbolic dims that need a `--dim` binding to resolve more nodes
+    pub unbound_dims: Vec<String>,
+    pub op_types: Vec<OpTypeCompute<'a>>,
+    pub nodes: Vec<NodeCompute<'a>>,
+}
+
+impl<'a> fmt::Display for ComputeSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        writeln!(
+            f,
+            "{} {} MACs, {} FLOPs",
+            bold.apply_to("Compute:"),
+            Count(self.macs),
+            Count(self.flops)
+        )?;
+
+        if self.unresolved_nodes > 0 {
+            write!(f, "    {} nodes not estimated", self.unresolved_nodes)?;
+
+            if self.unbound_dims.is_empty() {
+                writeln!(f, " (unknown shapes)")?;
+            } else {
+                writeln!(f, ", bind {} with --dim", self.unbound_dims.join(", "))?;
+            }
+        }
+
+        if !self.op_types.is_empty() {
+            writeln!(f, "  By op type:")?;
+        }
+
+        for usage in self.op_types.iter() {
+            writeln!(
+                f,
+                "    {}: {} MACs ({} nodes)",
+                usage.op_type,
+                Count(usage.macs),
+                usage.nodes
+            )?;
+        }
+
+        if !self.nodes.is_empty() {
+            writeln!(f, "  Top nodes:")?;
+        }
+
+        for node in self.nodes.iter() {
+            writeln!(
+                f,
+                "    {} ({}): {} MACs",
+                node.name,
+                node.op_type,
+                Count(node.macs)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct OperatorCount<'a> {
+    pub op_type: &'a str,
+    pub count: usize,
+}
+
+impl<'a> fmt::Display for OperatorCount<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count > 1 {
+            write!(f, "{}×{}", self.op_type, self.count)
+        } else {
+            write!(f, "{}", self.op_type)
+        }
+    }
+}
+
+/// Block of nodes repeated `count` times, e.g. the layers of a transformer
+#[derive(Serialize)]
+pub struct RepeatedBlock<'a> {
+    pub name: String,
+    /// `name scope` or `structure`
+    pub detection: &'static str,
+    pub count: usize,
+    /// Instances whose op counts differ from the repeated block
+    pub differing: usize,
+    pub nodes: usize,
+    pub operators: Vec<OperatorCount<'a>>,
+    /// Parameters of each repeated instance
+    pub parameters: Vec<u64>,
+}
+
+impl<'a> fmt::Display for RepeatedBlock<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operators: Vec<String> = self.operators.iter().map(|o| o.to_string()).collect();
+        let min = self.parameters.iter().min().copied().unwrap_or(0);
+        let max = self.parameters.iter().max().copied().unwrap_or(0);
+
+        write!(
+            f,
+            "{} × {} ({}): block of {{{}}}, {} nodes, ",
+            self.count,
+            self.name,
+            self.detection,
+            operators.join(", "),
+            self.nodes
+        )?;
+
+        if min == max {
+            write!(f, "{} params each", Count(min))?;
+        } else {
+            write!(f, "{}-{} params", Count(min), Count(max))?;
+        }
+
+        if self.differing > 0 {
+            write!(f, ", {} differing instances", self.differing)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Module of the exporter's name scopes, with everything below it aggregated
+#[derive(Serialize)]
+pub struct ModuleNode<'a> {
+    pub name: &'a str,
+    pub path: String,
+    pub nodes: usize,
+    pub operators: Vec<OperatorCount<'a>>,
+    pub parameters: u64,
+    /// Tensors used by the module but computed outside of it
+    pub inputs: Vec<&'a str>,
+    /// Tensors computed by the module and used outside of it
+    pub outputs: Vec<&'a str>,
+    pub children: Vec<ModuleNode<'a>>,
+    #[serde(skip)]
+    pub node_ids: Vec<NodeId>,
+}
+
+/// Boundary tensors are listed up to this many, otherwise counted
+const MAX_LISTED_BOUNDARIES: usize = 3;
+
+impl<'a> ModuleNode<'a> {
+    fn fmt_tree(&self, f: &mut fmt::Formatter<'_>, level: usize, depth: usize) -> fmt::Result {
+        let boundary = |tensors: &[&str]| {
+            if tensors.len() <= MAX_LISTED_BOUNDARIES {
+                tensors.join(", ")
+            } else {
+                format!("{} tensors", tensors.len())
+            }
+        };
+
+        write!(
+            f,
+            "{:indent$}{}: {} nodes, {} params",
+            "",
+            self.name,
+            self.nodes,
+            Count(self.parameters),
+            indent = 4 * level
+        )?;
+
+        if !self.inputs.is_empty() {
+            write!(f, ", in: {}", boundary(&self.inputs))?;
+        }
+
+        if !self.outputs.is_empty() {
+            write!(f, ", out: {}", boundary(&self.outputs))?;
+        }
+
+        if level == depth && !self.children.is_empty() {
+            write!(f, " (+{} submodules)", self.children.len())?;
+        }
+
+        writeln!(f)?;
+
+        if level < depth {
+            for child in self.children.iter() {
+                child.fmt_tree(f, level + 1, depth)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantizedTensorKind {
+    Weight,
+    Activation,
+}
+
+#[derive(Serialize)]
+pub struct QuantizedTensor<'a> {
+    pub name: &'a str,
+    pub kind: QuantizedTensorKind,
+    pub data_type: String,
+    pub bits: Option<u32>,
+    /// Per-tensor, per-channel or blocked scales
+    pub granularity: String,
+    pub scale: Option<&'a str>,
+    /// No zero point means symmetric quantization
+    pub zero_point: Option<&'a str>,
+}
+
+/// Compute nodes of one op type running the same way
+#[derive(Serialize)]
+pub struct QuantizedCompute<'a> {
+    pub op_type: &'a str,
+    /// `integer`, `qdq`, `weight-only` or `float`
+    pub kind: &'static str,
+    pub nodes: usize,
+}
+
+#[derive(Serialize)]
+pub struct QuantizationSummary<'a> {
+    /// QuantizeLinear outputs directly dequantized again
+    pub qdq_pairs: usize,
+    pub tensors: Vec<QuantizedTensor<'a>>,
+    pub compute: Vec<QuantizedCompute<'a>>,
+}
+
+impl<'a> fmt::Display for QuantizationSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+        let weights = self
+            .tensors
+            .iter()
+            .filter(|t| t.kind == QuantizedTensorKind::Weight)
+            .count();
+
+        writeln!(
+            f,
+            "{} {} weights, {} activations, {} QDQ pairs",
+            bold.apply_to("Quantization:"),
+            weights,
+            self.tensors.len() - weights,
+            self.qdq_pairs
+        )?;
+
+        for kind in [QuantizedTensorKind::Weight, QuantizedTensorKind::Activation] {
+            let mut tensors = self.tensors.iter().filter(|t| t.kind == kind).peekable();
+
+            if tensors.peek().is_none() {
+                continue;
+            }
+
+            match kind {
+                QuantizedTensorKind::Weight => writeln!(f, "  Weights:")?,
+                QuantizedTensorKind::Activation => writeln!(f, "  Activations:")?,
+            }
+
+            for tensor in tensors {
+                write!(f, "    {}: {}", tensor.name, tensor.data_type)?;
+
+                if let Some(bits) = tensor.bits {
+                    write!(f, " ({} bit)", bits)?;
+                }
+
+                write!(f, ", {}", tensor.granularity)?;
+
+                if tensor.zero_point.is_none() {
+                    write!(f, ", symmetric")?;
+                }
+
+                writeln!(f)?;
+            }
+        }
+
+        if !self.compute.is_empty() {
+            writeln!(f, "  Compute:")?;
+        }
+
+        for compute in self.compute.iter() {
+            writeln!(
+                f,
+                "    {}: {} {}",
+                compute.op_type, compute.nodes, compute.kind
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct SubgraphEntry<'a> {
+    /// Name of the control flow node holding the subgraph
+    pub owner: &'a str,
+    pub op_type: &'a str,
+    pub attribute: &'a str,
+    pub name: &'a str,
+    pub depth: usize,
+    pub nodes: usize,
+    pub outer_refs: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+pub struct SubgraphSummary<'a> {
+    pub nested_nodes: usize,
+    pub max_depth: usize,
+    pub subgraphs: Vec<SubgraphEntry<'a>>,
+}
+
+impl<'a> fmt::Display for SubgraphSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        writeln!(
+            f,
+            "{} {} graphs, {} nodes, max depth {}",
+            bold.apply_to("Subgraphs:"),
+            self.subgraphs.len(),
+            self.nested_nodes,
+            self.max_depth
+        )?;
+
+        for subgraph in self.subgraphs.iter() {
+            write!(
+                f,
+                "{:indent$}{}.{} ({}): {} nodes",
+                "",
+                subgraph.owner,
+                subgraph.attribute,
+                subgraph.op_type,
+                subgraph.nodes,
+                indent = subgraph.depth * 4
+            )?;
+
+            if !subgraph.outer_refs.is_empty() {
+                write!(f, ", captures {}", subgraph.outer_refs.join(", "))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decoded attribute value, tensors and graphs are described rather than
+/// dumped
+#[derive(Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum AttributeValue {
+    Float(f32),
+    Int(i64),
+    String(String),
+    Tensor(String),
+    Graph(String),
+    Type(String),
+    Floats(Vec<f32>),
+    Ints(Vec<i64>),
+    Strings(Vec<String>),
+    Tensors(Vec<String>),
+    Graphs(Vec<String>),
+    Types(Vec<String>),
+    /// Raw type of an attribute with an unknown type
+    Undefined(i32),
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+            let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+            write!(f, "[{}]", items.join(", "))
+        }
+
+        match self {
+            AttributeValue::Float(v) => write!(f, "{}", v),
+            AttributeValue::Int(v) => write!(f, "{}", v),
+            AttributeValue::String(v) => write!(f, "{:?}", v),
+            AttributeValue::Tensor(v) | AttributeValue::Graph(v) | AttributeValue::Type(v) => {
+                write!(f, "{}", v)
+            }
+            AttributeValue::Floats(v) => list(f, v),
+            AttributeValue::Ints(v) => list(f, v),
+            AttributeValue::Strings(v) => {
+                let quoted: Vec<String> = v.iter().map(|s| format!("{:?}", s)).collect();
+                list(f, &quoted)
+            }
+            AttributeValue::Tensors(v) | AttributeValue::Graphs(v) | AttributeValue::Types(v) => {
+                list(f, v)
+            }
+            AttributeValue::Undefined(ty) => write!(f, "<undefined type {}>", ty),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct NodeAttribute<'a> {
+    pub name: &'a str,
+    /// Function attribute this attribute refers to, inside function bodies
+    pub ref_attr_name: &'a str,
+    #[serde(flatten)]
+    pub value: AttributeValue,
+}
+
+#[derive(Serialize)]
+pub struct NodeRef<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub op_type: &'a str,
+}
+
+impl<'a> fmt::Display for NodeRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "#{} ({})", self.index, self.op_type)
+        } else {
+            write!(f, "#{} {} ({})", self.index, self.name, self.op_type)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct NodeTensor<'a> {
+    pub name: &'a str,
+    pub ty: String,
+    pub initializer: bool,
+    pub graph_input: bool,
+    pub graph_output: bool,
+    pub producer: Option<NodeRef<'a>>,
+    pub consumers: Vec<NodeRef<'a>>,
+}
+
+impl<'a> fmt::Display for NodeTensor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "    {}: {}", self.name, self.ty)?;
+
+        let mut kinds = Vec::new();
+        if self.initializer {
+            kinds.push("initializer");
+        }
+        if self.graph_input {
+            kinds.push("graph input");
+        }
+        if self.graph_output {
+            kinds.push("graph output");
+        }
+
+        if !kinds.is_empty() {
+            write!(f, " ({})", kinds.join(", "))?;
+        }
+
+        writeln!(f)?;
+
+        if let Some(producer) = self.producer.as_ref() {
+            writeln!(f, "        from {}", producer)?;
+        }
+
+        for consumer in self.consumers.iter() {
+            writeln!(f, "        to {}", consumer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything about a single node of the main graph
+#[derive(Serialize)]
+pub struct NodeDetails<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub op_type: &'a str,
+    pub domain: &'a str,
+    pub doc_string: &'a str,
+    pub attributes: Vec<NodeAttribute<'a>>,
+    pub inputs: Vec<NodeTensor<'a>>,
+    pub outputs: Vec<NodeTensor<'a>>,
+    /// Nodes producing this node's inputs
+    pub predecessors: Vec<NodeRef<'a>>,
+    /// Nodes consuming this node's outputs
+    pub successors: Vec<NodeRef<'a>>,
+}
+
+impl<'a> Summary for NodeDetails<'a> {
+    fn dump_json(&self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, &self)?)
+    }
+}
+
+impl<'a> fmt::Display for NodeDetails<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        writeln!(
+            f,
+            "{} #{} {}",
+            bold.apply_to("Node:"),
+            self.index,
+            self.name
+        )?;
+        writeln!(f, "Operator: {}.{}", self.domain, self.op_type)?;
+
+        if !self.doc_string.is_empty() {
+            writeln!(f, "{}", self.doc_string)?;
+        }
+
+        if !self.attributes.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Attributes:"))?;
+        }
+
+        for attr in self.attributes.iter() {
+            if attr.ref_attr_name.is_empty() {
+                writeln!(f, "    {}: {}", attr.name, attr.value)?;
+            } else {
+                writeln!(f, "    {}: @{}", attr.name, attr.ref_attr_name)?;
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{}", bold.apply_to("Inputs:"))?;
+
+        for input in self.inputs.iter() {
+            write!(f, "{}", input)?;
+        }
+
+        writeln!(f, "{}", bold.apply_to("Outputs:"))?;
+
+        for output in self.outputs.iter() {
+            write!(f, "{}", output)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{}", bold.apply_to("Predecessors:"))?;
+
+        for node in self.predecessors.iter() {
+            writeln!(f, "    {}", node)?;
+        }
+
+        writeln!(f, "{}", bold.apply_to("Successors:"))?;
+
+        for node in self.successors.iter() {
+            writeln!(f, "    {}", node)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Nodes or tensors selected by a query
+#[derive(Serialize)]
+pub struct QueryMatches<'a> {
+    pub query: &'a str,
+    pub nodes: Vec<NodeRef<'a>>,
+    pub tensors: Vec<Value<'a>>,
+}
+
+impl<'a> Summary for QueryMatches<'a> {
+    fn dump_json(&self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, &self)?)
+    }
+}
+
+impl<'a> fmt::Display for QueryMatches<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        writeln!(f, "{} {}", bold.apply_to("Query:"), self.query)?;
+        writeln!(f)?;
+
+        for node in self.nodes.iter() {
+            writeln!(f, "    {}", node)?;
+        }
+
+        for tensor in self.tensors.iter() {
+            writeln!(f, "    {}: {}", tensor.name, tensor.ty)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{} matches", self.nodes.len() + self.tensors.len())
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct ValueTypeSummary {
+    pub declared: usize,
+    pub inferred: usize,
+    pub unknown: usize,
+}
+
+#[derive(Serialize)]
+pub struct ExternalFile<'a> {
+    pub location: &'a str,
+    pub tensors: usize,
+    pub bytes: u64,
+}
+
+impl<'a> WeightSummary<'a> {
+    fn fmt_section(&self, f: &mut fmt::Formatter<'_>, title: &str) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        writeln!(
+            f,
+            "{} {} tensors, {} parameters ({})",
+            bold.apply_to(title),
+            self.count,
+            self.parameters,
+            ByteSize(self.bytes)
+        )?;
+
+        for usage in self.data_types.iter() {
+            writeln!(
+                f,
+                "    {}: {} ({})",
+                usage.data_type,
+                usage.parameters,
+                ByteSize(usage.bytes)
+            )?;
+        }
+
+        if !self.largest.is_empty() {
+            writeln!(f, "  Largest:")?;
+        }
+
+        for weight in self.largest.iter() {
+            writeln!(
+                f,
+                "    {}: {} ({})",
+                weight.name,
+                weight.ty,
+                ByteSize(weight.bytes)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Entry of `metadata_props` or of a training binding
+#[derive(Serialize)]
+pub struct MetadataEntry<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+/// Main graph node with a doc string, such as the source location exporters
+/// record
+#[derive(Serialize)]
+pub struct NodeDocString<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub op_type: &'a str,
+    pub doc_string: &'a str,
+}
+
+impl<'a> fmt::Display for NodeDocString<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "#{} ({}): ", self.index, self.op_type)?;
+        } else {
+            write!(f, "{} ({}): ", self.name, self.op_type)?;
+        }
+
+        write_indented(f, self.doc_string, 8)
+    }
+}
+
+/// Graph of a training step, either the algorithm or its initialization
+#[derive(Serialize)]
+pub struct TrainingGraph<'a> {
+    pub name: &'a str,
+    pub doc_string: &'a str,
+    pub nodes: usize,
+    pub initializers: usize,
+    pub inputs: Vec<&'a str>,
+    pub outputs: Vec<&'a str>,
+    pub operators: Vec<OperatorCount<'a>>,
+}
+
+impl<'a> fmt::Display for TrainingGraph<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} nodes, {} initializers, {} inputs, {} outputs",
+            self.name,
+            self.nodes,
+            self.initializers,
+            self.inputs.len(),
+            self.outputs.len()
+        )?;
+
+        if !self.operators.is_empty() {
+            let operators: Vec<String> = self.operators.iter().map(|o| o.to_string()).collect();
+            write!(f, " {{{}}}", operators.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `TrainingInfoProto` of a model, bindings map initializers to the graph
+/// outputs assigned to them
+#[derive(Serialize)]
+pub struct TrainingInfo<'a> {
+    pub algorithm: Option<TrainingGraph<'a>>,
+    pub initialization: Option<TrainingGraph<'a>>,
+    pub initialization_binding: Vec<MetadataEntry<'a>>,
+    pub update_binding: Vec<MetadataEntry<'a>>,
+}
+
+impl<'a> fmt::Display for TrainingInfo<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(algorithm) = self.algorithm.as_ref() {
+            writeln!(f, "    Algorithm {}", algorithm)?;
+        }
+
+        if let Some(initialization) = self.initialization.as_ref() {
+            writeln!(f, "    Initialization {}", initialization)?;
+        }
+
+        for (title, binding) in [
+            ("Initialization binding", &self.initialization_binding),
+            ("Update binding", &self.update_binding),
+        ] {
+            if !binding.is_empty() {
+                writeln!(f, "    {}:", title)?;
+            }
+
+            for entry in binding.iter() {
+                writeln!(f, "        {} <- {}", entry.key, entry.value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Write text spanning several lines with the continuation lines indented
+fn write_indented(f: &mut fmt::Formatter<'_>, text: &str, indent: usize) -> fmt::Result {
+    for (i, line) in text.trim_end().lines().enumerate() {
+        if i > 0 {
+            write!(f, "\n{:indent$}", "", indent = indent)?;
+        }
+        write!(f, "{}", line)?;
+    }
+
+    Ok(())
+}
+
 impl<'a> fmt::Display for OnnxSummary<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         //println!("This is {} neat", style("quite").bold());
@@ -75,6 +938,9 @@ impl<'a> fmt::Display for OnnxSummary<'a> {
         if !self.doc_string.is_empty() {
             writeln!(f, "{}", self.doc_string)?;
         }
+        if !self.graph_doc_string.is_empty() {
+            writeln!(f, "{}", self.graph_doc_string)?;
+        }
         writeln!(f)?;
 
         writeln!(
@@ -84,6 +950,18 @@ impl<'a> fmt::Display for OnnxSummary<'a> {
         )?;
         writeln!(f)?;
 
+        if !self.metadata.is_empty() {
+            writeln!(f, "{}", bold.apply_to("Metadata:"))?;
+
+            for entry in self.metadata.iter() {
+                write!(f, "    {}: ", entry.key)?;
+                write_indented(f, entry.value, 8)?;
+                writeln!(f)?;
+            }
+
+            writeln!(f)?;
+        }
+
         writeln!(f, "IR Version: {}", self.ir_version)?;
         if self.opsets.len() == 1 {
             write!(f, "Opset: ")?;
@@ -106,10 +984,128 @@ impl<'a> fmt::Display for OnnxSummary<'a> {
             writeln!(f, "    {}: {}", output.name, output.ty)?;
         }
 
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Value types: {} declared, {} inferred, {} unknown",
+            self.value_types.declared, self.value_types.inferred, self.value_types.unknown
+        )?;
+
         writeln!(f)?;
         writeln!(f, "Operators:")?;
         for oper in self.operator_summary.operators.iter() {
-            writeln!(f, "    {}.{}: {}", oper.domain, oper.name, oper.count)?;
+            write!(f, "    {}.{}: {}", oper.domain, oper.name, oper.count)?;
+
+            if oper.nested_count > 0 {
+                write!(f, " (+{} nested)", oper.nested_count)?;
+            }
+
+            if oper.function {
+                write!(f, " (function)")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if !self.blocks.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Repeated blocks:"))?;
+        }
+
+        for block in self.blocks.iter() {
+            writeln!(f, "    {}", block)?;
+        }
+
+        if !self.modules.children.is_empty() && self.module_depth > 0 {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Modules:"))?;
+        }
+
+        if self.module_depth > 0 {
+            for module in self.modules.children.iter() {
+                module.fmt_tree(f, 1, self.module_depth)?;
+            }
+        }
+
+        if !self.functions.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Functions:"))?;
+        }
+
+        for function in self.functions.iter() {
+            write!(f, "{}", function)?;
+        }
+
+        if !self.subgraphs.subgraphs.is_empty() {
+            writeln!(f)?;
+            write!(f, "{}", self.subgraphs)?;
+        }
+
+        if !self.node_doc_strings.is_empty() {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{} {} nodes",
+                bold.apply_to("Node doc strings:"),
+                self.node_doc_strings.len()
+            )?;
+        }
+
+        for node in self.node_doc_strings.iter().take(self.top) {
+            writeln!(f, "    {}", node)?;
+        }
+
+        if self.node_doc_strings.len() > self.top {
+            writeln!(f, "    (+{} more)", self.node_doc_strings.len() - self.top)?;
+        }
+
+        if !self.training_info.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Training:"))?;
+        }
+
+        for info in self.training_info.iter() {
+            write!(f, "{}", info)?;
+        }
+
+        writeln!(f)?;
+        self.initializers.fmt_section(f, "Initializers:")?;
+
+        if self.constants.count > 0 {
+            writeln!(f)?;
+            self.constants.fmt_section(f, "Constants:")?;
+        }
+
+        writeln!(f)?;
+        write!(f, "{}", self.compute)?;
+
+        if let Some(quantization) = self.quantization.as_ref() {
+            writeln!(f)?;
+            write!(f, "{}", quantization)?;
+        }
+
+        if !self.external_files.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("External data:"))?;
+        }
+
+        for file in self.external_files.iter() {
+            writeln!(
+                f,
+                "    {}: {} tensors ({})",
+                file.location,
+                file.tensors,
+                ByteSize(file.bytes)
+            )?;
+        }
+
+        if !self.diagnostics.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Diagnostics:"))?;
+        }
+
+        for diagnostic in self.diagnostics.iter() {
+            writeln!(f, "    {}", diagnostic)?;
         }
 
         Ok(())
@@ -0,0 +1,241 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::onnx::onnx_proto::tensor_proto::DataType;
+use crate::onnx::onnx_proto::{attribute_proto, NodeProto, TensorProto};
+use crate::onnx::summary::{DataTypeWeights, Weight, WeightSummary};
+use crate::onnx::{data_type_name, is_onnx_domain, InitId, OnnxModel};
+
+/// Size in bytes of a single element, `None` for variable sized types
+pub fn element_size(data_type: DataType) -> Option<u64> {
+    match data_type {
+        DataType::Undefined | DataType::String => None,
+        DataType::Uint8
+        | DataType::Int8
+        | DataType::Bool
+        | DataType::Float8e4m3fn
+        | DataType::Float8e4m3fnuz
+        | DataType::Float8e5m2
+        | DataType::Float8e5m2fnuz => Some(1),
+        DataType::Uint16 | DataType::Int16 | DataType::Float16 | DataType::Bfloat16 => Some(2),
+        DataType::Float | DataType::Int32 | DataType::Uint32 => Some(4),
+        DataType::Double | DataType::Int64 | DataType::Uint64 | DataType::Complex64 => Some(8),
+        DataType::Complex128 => Some(16),
+    }
+}
+
+/// Number of elements in a tensor, scalars have no dims and one element.
+///
+/// Saturates rather than overflowing on dims no real tensor has.
+pub fn tensor_elements(tensor: &TensorProto) -> u64 {
+    tensor
+        .dims
+        .iter()
+        .fold(1u64, |acc, d| acc.saturating_mul((*d).max(0) as u64))
+}
+
+/// Size of the tensor's data, independent of where the data is stored
+pub fn tensor_bytes(tensor: &TensorProto) -> u64 {
+    match DataType::from_i32(tensor.data_type).and_then(element_size) {
+        Some(size) => tensor_elements(tensor).saturating_mul(size),
+        None => tensor.string_data.iter().map(|s| s.len() as u64).sum(),
+    }
+}
+
+/// Tensor type in the same notation `TypeInfo` uses, e.g. `f32[4,3,3,3]`
+pub fn tensor_type_name(data_type: i32, dims: &[i64]) -> String {
+    if dims.is_empty() {
+        return data_type_name(data_type);
+    }
+
+    let dims: Vec<String> = dims.iter().map(|d| d.to_string()).collect();
+    format!("{}[{}]", data_type_name(data_type), dims.join(","))
+}
+
+/// A single weight held by the model before aggregation
+struct WeightEntry<'a> {
+    name: &'a str,
+    data_type: i32,
+    dims: Vec<i64>,
+    parameters: u64,
+    bytes: u64,
+}
+
+impl<'a> WeightEntry<'a> {
+    fn from_tensor(name: &'a str, tensor: &'a TensorProto) -> Self {
+        WeightEntry {
+            name,
+            data_type: tensor.data_type,
+            dims: tensor.dims.clone(),
+            parameters: tensor_elements(tensor),
+            bytes: tensor_bytes(tensor),
+        }
+    }
+
+    fn from_list(name: &'a str, data_type: DataType, len: usize) -> Self {
+        let parameters = len as u64;
+
+        WeightEntry {
+            name,
+            data_type: data_type as i32,
+            dims: vec![len as i64],
+            parameters,
+            bytes: parameters.saturating_mul(element_size(data_type).unwrap_or(0)),
+        }
+    }
+}
+
+/// Weight embedded in a `Constant` node, if the node holds one
+fn constant_weight(node: &NodeProto) -> Option<WeightEntry<'_>> {
+    use attribute_proto::AttributeType;
+
+    let name = node
+        .output
+        .first()
+        .map_or(node.name.as_str(), |o| o.as_str());
+    let attr = node.attribute.first()?;
+
+    let entry = match AttributeType::from_i32(attr.r#type)? {
+        AttributeType::Tensor => WeightEntry::from_tensor(name, attr.t.as_ref()?),
+        AttributeType::SparseTensor => {
+            let values = attr.sparse_tensor.as_ref()?.values.as_ref()?;
+            WeightEntry::from_tensor(name, values)
+        }
+        AttributeType::Float => WeightEntry::from_list(name, DataType::Float, 1),
+        AttributeType::Floats => WeightEntry::from_list(name, DataType::Float, attr.floats.len()),
+        AttributeType::Int => WeightEntry::from_list(name, DataType::Int64, 1),
+        AttributeType::Ints => WeightEntry::from_list(name, DataType::Int64, attr.ints.len()),
+        AttributeType::String => WeightEntry {
+            bytes: attr.s.len() as u64,
+            ..WeightEntry::from_list(name, DataType::String, 1)
+        },
+        AttributeType::Strings => WeightEntry {
+            bytes: attr.strings.iter().map(|s| s.len() as u64).sum(),
+            ..WeightEntry::from_list(name, DataType::String, attr.strings.len())
+        },
+        _ => return None,
+    };
+
+    Some(entry)
+}
+
+fn weight_summary<'a>(entries: Vec<WeightEntry<'a>>, top: usize) -> WeightSummary<'a> {
+    let mut data_types: HashMap<i32, DataTypeWeights> = HashMap::new();
+
+    for entry in entries.iter() {
+        let usage = data_types
+            .entry(entry.data_type)
+            .or_insert_with(|| DataTypeWeights {
+                data_type: data_type_name(entry.data_type),
+                parameters: 0,
+                bytes: 0,
+            });
+
+        usage.parameters = usage.parameters.saturating_add(entry.parameters);
+        usage.bytes = usage.bytes.saturating_add(entry.bytes);
+    }
+
+    let mut data_types: Vec<DataTypeWeights> = data_types.into_values().collect();
+    data_types.sort_by_key(|usage| Reverse(usage.bytes));
+
+    let count = entries.len();
+    let parameters = entries
+        .iter()
+        .fold(0u64, |acc, e| acc.saturating_add(e.parameters));
+    let bytes = entries
+        .iter()
+        .fold(0u64, |acc, e| acc.saturating_add(e.bytes));
+
+    let mut largest = entries;
+    largest.sort_by_key(|e| Reverse(e.bytes));
+    largest.truncate(top);
+
+    WeightSummary {
+        count,
+        parameters,
+        bytes,
+        data_types,
+        largest: largest
+            .into_iter()
+            .map(|e| Weight {
+                name: e.name,
+                ty: tensor_type_name(e.data_type, &e.dims),
+                parameters: e.parameters,
+                bytes: e.bytes,
+            })
+            .collect(),
+    }
+}
+
+impl OnnxModel {
+    /// Elements of a set of initializers
+    pub fn initializer_elements(&self, init_ids: BTreeSet<InitId>) -> u64 {
+        init_ids
+            .into_iter()
+            .map(|init_id| tensor_elements(&self.graph_proto().initializer[init_id]))
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Weights stored as graph initializers, listing the `top` largest
+    pub fn initializer_weights(&self, top: usize) -> WeightSummary<'_> {
+        weight_summary(
+            self.graph_proto()
+                .initializer
+                .iter()
+                .map(|init| WeightEntry::from_tensor(&init.name, init))
+                .collect(),
+            top,
+        )
+    }
+
+    /// Weights embedded in `Constant` nodes, listing the `top` largest
+    pub fn constant_weights(&self, top: usize) -> WeightSummary<'_> {
+        weight_summary(
+            self.nodes
+                .iter()
+                .map(|node| &node.proto)
+                .filter(|node| node.op_type == "Constant" && is_onnx_domain(&node.domain))
+                .filter_map(constant_weight)
+                .collect(),
+            top,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{load, tensor};
+    use crate::onnx::onnx_proto::GraphProto;
+
+    #[test]
+    fn sizes_of_tensors() {
+        let weight = tensor("w", DataType::Float16, &[4, 3, 3, 3]);
+        assert_eq!(tensor_elements(&weight), 108);
+        assert_eq!(tensor_bytes(&weight), 216);
+
+        let scalar = tensor("s", DataType::Complex128, &[]);
+        assert_eq!(tensor_bytes(&scalar), 16);
+
+        let empty = tensor("e", DataType::Float, &[0, -1]);
+        assert_eq!(tensor_elements(&empty), 0);
+    }
+
+    #[test]
+    fn crafted_dims_saturate() {
+        let huge = tensor("h", DataType::Double, &[i64::MAX, i64::MAX]);
+        assert_eq!(tensor_elements(&huge), u64::MAX);
+        assert_eq!(tensor_bytes(&huge), u64::MAX);
+
+        let model = load(GraphProto {
+            initializer: vec![huge.clone(), tensor("h2", DataType::Double, &[i64::MAX])],
+            ..Default::default()
+        });
+
+        let weights = model.initializer_weights(10);
+        assert_eq!(weights.parameters, u64::MAX);
+        assert_eq!(weights.bytes, u64::MAX);
+        assert_eq!(weights.data_types[0].bytes, u64::MAX);
+        assert_eq!(model.initializer_elements(BTreeSet::from([0, 1])), u64::MAX);
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use crate::check::{Finding, Location, Severity};
+use crate::safetensors::{dtype_bits, TensorInfo};
+
+fn error(name: &str, message: String) -> Finding {
+    Finding {
+        severity: Severity::Error,
+        location: Location::Tensor {
+            name: name.to_string(),
+        },
+        message,
+    }
+}
+
+/// Dtype, size and offsets of a single tensor
+fn check_tensor(name: &str, info: &TensorInfo, data_len: u64, findings: &mut Vec<Finding>) {
+    let (begin, end) = info.data_offsets;
+
+    if begin > end {
+        findings.push(error(
+            name,
+            format!("data_offsets [{}, {}] end before they begin", begin, end),
+        ));
+    } else if end > data_len {
+        findings.push(error(
+            name,
+            format!(
+                "data_offsets [{}, {}] extend past the end of the {} data bytes, the file may be truncated",
+                begin, end, data_len
+            ),
+        ));
+    }
+
+    let Some(bits) = dtype_bits(&info.dtype) else {
+        findings.push(error(name, format!("Unknown dtype {}", info.dtype)));
+        return;
+    };
+
+    let bits = info
+        .shape
+        .iter()
+        .try_fold(bits, |bits, dim| bits.checked_mul(*dim));
+
+    match bits {
+        None => findings.push(error(
+            name,
+            format!("Shape {:?} has too many elements", info.shape),
+        )),
+        Some(bits) if bits % 8 != 0 => findings.push(error(
+            name,
+            format!(
+                "{} elements of {} do not fill whole bytes",
+                info.elements(),
+                info.dtype
+            ),
+        )),
+        Some(bits) if begin <= end && bits / 8 != end - begin => findings.push(error(
+            name,
+            format!(
+                "{} elements of {} take {} bytes but data_offsets [{}, {}] hold {}",
+                info.elements(),
+                info.dtype,
+                bits / 8,
+                begin,
+                end,
+                end - begin
+            ),
+        )),
+        Some(_) => {}
+    }
+}
+
+/// Check the header entries against the data section.
+///
+/// Tensors must use known dtypes, hold exactly their elements' bytes and
+/// together cover the data section without overlaps or gaps.
+pub fn check_tensors(tensors: &BTreeMap<String, TensorInfo>, data_len: u64) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (name, info) in tensors.iter() {
+        check_tensor(name, info, data_len, &mut findings);
+    }
+
+    let mut ranges: Vec<(u64, u64, &str)> = tensors
+        .iter()
+        .filter(|(_, info)| info.data_offsets.0 <= info.data_offsets.1)
+        .map(|(name, info)| (info.data_offsets.0, info.data_offsets.1, name.as_str()))
+        .collect();
+    ranges.sort();
+
+    // End of the data covered so far and the tensor reaching it
+    let mut covered: (u64, Option<&str>) = (0, None);
+
+    for (begin, end, name) in ranges {
+        match covered {
+            (covered_end, Some(previous)) if begin < covered_end => findings.push(error(
+                name,
+                format!(
+                    "data_offsets [{}, {}] overlap the data of {}",
+                    begin, end, previous
+                ),
+            )),
+            (covered_end, _) if begin > covered_end => findings.push(error(
+                name,
+                format!(
+                    "{} data bytes before data_offsets [{}, {}] belong to no tensor",
+                    begin - covered_end,
+                    begin,
+                    end
+                ),
+            )),
+            _ => {}
+        }
+
+        if end >= covered.0 {
+            covered = (end, Some(name));
+        }
+    }
+
+    if covered.0 < data_len {
+        findings.push(Finding {
+            severity: Severity::Error,
+            location: Location::Model,
+            message: format!(
+                "{} data bytes after the last tensor belong to no tensor",
+                data_len - covered.0
+            ),
+        });
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(dtype: &str, shape: &[u64], begin: u64, end: u64) -> TensorInfo {
+        TensorInfo {
+            dtype: dtype.to_string(),
+            shape: shape.to_vec(),
+            data_offsets: (begin, end),
+            shard: 0,
+        }
+    }
+
+    fn check(tensors: &[(&str, TensorInfo)], data_len: u64) -> Vec<String> {
+        let tensors: BTreeMap<String, TensorInfo> = tensors
+            .iter()
+            .map(|(name, info)| (name.to_string(), info.clone()))
+            .collect();
+
+        check_tensors(&tensors, data_len)
+            .into_iter()
+            .map(|finding| format!("{}: {}", finding.location, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn contiguous_tensors_are_valid() {
+        let findings = check(
+            &[
+                ("a", tensor("F32", &[2, 3], 0, 24)),
+                ("b", tensor("BF16", &[4], 24, 32)),
+                ("scalar", tensor("I64", &[], 32, 40)),
+                ("empty", tensor("F16", &[0, 8], 40, 40)),
+                ("packed", tensor("F4", &[4], 40, 42)),
+            ],
+            42,
+        );
+
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+
+    #[test]
+    fn sizes_and_dtypes() {
+        let findings = check(
+            &[
+                ("short", tensor("F32", &[4], 0, 12)),
+                ("odd", tensor("F4", &[3], 12, 14)),
+                ("unknown", tensor("Q4", &[2], 14, 15)),
+                ("huge", tensor("F64", &[u64::MAX, 2], 15, 16)),
+            ],
+            16,
+        );
+
+        assert_eq!(
+            findings,
+            vec![
+                "tensor huge: Shape [18446744073709551615, 2] has too many elements",
+                "tensor odd: 3 elements of F4 do not fill whole bytes",
+                "tensor short: 4 elements of F32 take 16 bytes but data_offsets [0, 12] hold 12",
+                "tensor unknown: Unknown dtype Q4",
+            ]
+        );
+    }
+
+    #[test]
+    fn overlaps_gaps_and_truncation() {
+        let findings = check(
+            &[
+                ("a", tensor("U8", &[8], 0, 8)),
+                ("b", tensor("U8", &[4], 4, 8)),
+                ("c", tensor("U8", &[4], 12, 16)),
+                ("d", tensor("U8", &[4], 16, 20)),
+            ],
+            18,
+        );
+
+        assert_eq!(
+            findings,
+            vec![
+                "tensor d: data_offsets [16, 20] extend past the end of the 18 data bytes, the file may be truncated",
+                "tensor b: data_offsets [4, 8] overlap the data of a",
+                "tensor c: 4 data bytes before data_offsets [12, 16] belong to no tensor",
+            ]
+        );
+    }
+
+    #[test]
+    fn reversed_offsets_and_trailing_bytes() {
+        let findings = check(&[("a", tensor("U8", &[4], 8, 4))], 10);
+
+        assert_eq!(
+            findings,
+            vec![
+                "tensor a: data_offsets [8, 4] end before they begin",
+                "model: 10 data bytes after the last tensor belong to no tensor",
+            ]
+        );
+    }
+}
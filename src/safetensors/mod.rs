@@ -1,49 +1,354 @@
-use anyhow::bail;
-use prost::bytes::Bytes;
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use memmap2::Mmap;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::from_value;
 
-use crate::model::Model;
-use crate::safetensors::summary::SafeTensorsSummary;
-use crate::summary::Summary;
+use crate::check::{Finding, Location, Severity};
+use crate::diff::DiffSection;
+use crate::model::{map_file, Model};
+use crate::safetensors::summary::{DataTypeTensors, SafeTensorsSummary, ShardEntry, TensorEntry};
+use crate::summary::{Summary, SummaryOptions};
+use crate::tensor::{ElementType, TensorData};
 
+mod check;
+mod modules;
+mod shards;
 mod summary;
 
+pub use shards::is_index;
+
+/// Bytes of the little-endian header length at the start of the file
+const HEADER_LENGTH_BYTES: usize = 8;
+
+/// Whether data starts like a safetensors file, with the header's opening
+/// brace after its length
+pub fn has_header(data: &[u8]) -> bool {
+    data.get(HEADER_LENGTH_BYTES) == Some(&b'{')
+}
+
 type Header = serde_json::value::Map<String, serde_json::Value>;
 
-pub struct Safetensors {
+/// Header entry of a tensor, `data_offsets` are the byte range in the data
+/// section
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TensorInfo {
+    pub dtype: String,
+    pub shape: Vec<u64>,
+    pub data_offsets: (u64, u64),
+    /// Position of the file holding the tensor in `Safetensors::shards`
+    #[serde(skip)]
+    pub shard: usize,
+}
+
+impl TensorInfo {
+    pub fn elements(&self) -> u64 {
+        self.shape
+            .iter()
+            .fold(1, |elements, dim| elements.saturating_mul(*dim))
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.data_offsets.1.saturating_sub(self.data_offsets.0)
+    }
+}
+
+/// Header entries in file order, keeping duplicate keys so they can be
+/// reported
+struct HeaderEntries(Vec<(String, serde_json::Value)>);
+
+impl<'de> Deserialize<'de> for HeaderEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = HeaderEntries;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+
+                Ok(HeaderEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+/// File of a checkpoint, one of several when it is sharded
+pub struct Shard {
+    /// Name relative to the index, empty for a checkpoint of one file
+    pub file: String,
+    path: PathBuf,
+    /// Size of the whole file
+    pub size: u64,
+    /// Start of the tensor data after the header, `data_offsets` are
+    /// relative to it
+    data_start: u64,
+    /// The file mapped on first access to tensor data, summaries only read
+    /// the header
+    mapping: OnceCell<Mmap>,
+}
+
+impl Shard {
+    /// Tensor data following the header
+    fn data(&self) -> anyhow::Result<&[u8]> {
+        let mapping = match self.mapping.get() {
+            Some(mapping) => mapping,
+            None => {
+                let mapping = map_file(&self.path)
+                    .with_context(|| format!("Cannot map {}", self.path.display()))?;
+                self.mapping.get_or_init(|| mapping)
+            }
+        };
+
+        mapping.get(self.data_start as usize..).ok_or_else(|| {
+            anyhow!(
+                "{} is shorter than its {} byte header",
+                self.path.display(),
+                self.data_start
+            )
+        })
+    }
+}
+
+/// Header of a single safetensors file
+struct ParsedFile {
     metadata: Header,
-    tensors: Header,
+    tensors: BTreeMap<String, TensorInfo>,
+    shard: Shard,
+    findings: Vec<Finding>,
 }
 
-impl Safetensors {
-    pub fn from_bytes(data: Bytes) -> anyhow::Result<Self> {
-        let header_size = u64::from_le_bytes(data[..8].try_into().unwrap());
+impl ParsedFile {
+    /// Read the header of a safetensors file, leaving the tensor data on
+    /// disk.
+    ///
+    /// Only a header that cannot be read fails, problems of its entries and
+    /// their offsets are kept as findings.
+    fn read(path: &Path, file: String) -> anyhow::Result<Self> {
+        let mut reader = File::open(path)?;
+        let size = reader.metadata()?.len();
+
+        if size < HEADER_LENGTH_BYTES as u64 {
+            bail!("File of {} bytes is too small for a header", size);
+        }
+
+        let mut header_length = [0; HEADER_LENGTH_BYTES];
+        reader.read_exact(&mut header_length)?;
+        let header_size = u64::from_le_bytes(header_length);
 
-        let header_end: usize = 8 + (header_size as usize);
+        let header_end = (HEADER_LENGTH_BYTES as u64)
+            .checked_add(header_size)
+            .filter(|end| *end <= size)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Header of {} bytes is larger than the remaining {} bytes of the file",
+                    header_size,
+                    size - HEADER_LENGTH_BYTES as u64
+                )
+            })?;
 
-        if header_end > data.len() {
-            bail!("Header larger remaining file length");
+        let mut header = vec![0; header_size as usize];
+        reader.read_exact(&mut header)?;
+
+        let header = std::str::from_utf8(&header).context("Header is not valid UTF-8")?;
+        let HeaderEntries(entries) =
+            serde_json::from_str(header).context("Header is not a valid JSON object")?;
+
+        let mut metadata = Header::new();
+        let mut tensors = BTreeMap::new();
+        let mut findings = Vec::new();
+        let mut names = HashSet::new();
+
+        for (name, value) in entries {
+            let location = || match name.as_str() {
+                "__metadata__" => Location::Model,
+                _ => Location::Tensor { name: name.clone() },
+            };
+
+            if !names.insert(name.clone()) {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    location: location(),
+                    message: format!(
+                        "{} is defined more than once in the header, the first entry is used",
+                        name
+                    ),
+                });
+                continue;
+            }
+
+            if name == "__metadata__" {
+                match from_value::<BTreeMap<String, String>>(value.clone()) {
+                    Ok(_) => metadata = from_value(value)?,
+                    Err(err) => findings.push(Finding {
+                        severity: Severity::Warning,
+                        location: location(),
+                        message: format!("__metadata__ is not a map of strings: {}", err),
+                    }),
+                }
+                continue;
+            }
+
+            match from_value::<TensorInfo>(value) {
+                Ok(info) => {
+                    tensors.insert(name, info);
+                }
+                Err(err) => findings.push(Finding {
+                    severity: Severity::Error,
+                    location: location(),
+                    message: format!("Invalid header entry: {}", err),
+                }),
+            }
         }
 
-        let header_bytes = data.slice(8..header_end);
+        findings.extend(check::check_tensors(&tensors, size - header_end));
+
+        Ok(ParsedFile {
+            metadata,
+            tensors,
+            shard: Shard {
+                file,
+                path: path.to_path_buf(),
+                size,
+                data_start: header_end,
+                mapping: OnceCell::new(),
+            },
+            findings,
+        })
+    }
+}
+
+pub struct Safetensors {
+    metadata: Header,
+    tensors: BTreeMap<String, TensorInfo>,
+    shards: Vec<Shard>,
+    /// Index the shards were found from, `None` for a single file
+    index: Option<String>,
+    /// Problems of the headers and their agreement with the data
+    findings: Vec<Finding>,
+}
 
-        let mut header: Header = serde_json::de::from_slice(&header_bytes)?;
+/// Bits per element of a safetensors dtype, `None` for unknown dtypes
+fn dtype_bits(dtype: &str) -> Option<u64> {
+    let bits = match dtype {
+        "F4" => 4,
+        "F6_E2M3" | "F6_E3M2" => 6,
+        "BOOL" | "U8" | "I8" | "F8_E4M3" | "F8_E5M2" | "F8_E8M0" => 8,
+        "I16" | "U16" | "F16" | "BF16" => 16,
+        "I32" | "U32" | "F32" => 32,
+        "I64" | "U64" | "F64" | "C64" => 64,
+        _ => return None,
+    };
 
-        let _data = data.slice(header_end..);
+    Some(bits)
+}
+
+/// Element type of a safetensors dtype, `None` for unknown dtypes
+fn element_type(dtype: &str) -> Option<ElementType> {
+    let element_type = match dtype {
+        "F64" => ElementType::F64,
+        "F32" => ElementType::F32,
+        "F16" => ElementType::F16,
+        "BF16" => ElementType::BF16,
+        "F8_E4M3" => ElementType::F8E4M3FN,
+        "F8_E5M2" => ElementType::F8E5M2,
+        "I64" => ElementType::I64,
+        "I32" => ElementType::I32,
+        "I16" => ElementType::I16,
+        "I8" => ElementType::I8,
+        "U64" => ElementType::U64,
+        "U32" => ElementType::U32,
+        "U16" => ElementType::U16,
+        "U8" => ElementType::U8,
+        "BOOL" => ElementType::Bool,
+        _ => return None,
+    };
 
-        let metadata = header
-            .remove("__metadata__")
-            .and_then(|v| from_value(v).ok())
-            .unwrap_or_default();
+    Some(element_type)
+}
 
-        let tensors = header;
+impl Safetensors {
+    /// Open a single safetensors file, reading only its header until tensor
+    /// data is needed
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = ParsedFile::read(path, String::new())?;
 
-        Ok(Safetensors { metadata, tensors })
+        Ok(Safetensors {
+            metadata: file.metadata,
+            tensors: file.tensors,
+            shards: vec![file.shard],
+            index: None,
+            findings: file.findings,
+        })
+    }
+
+    /// Data of a tensor, checking its offsets lie within the data section
+    fn tensor<'a>(&'a self, name: &'a str, info: &TensorInfo) -> anyhow::Result<TensorData<'a>> {
+        let data = self.shards[info.shard].data()?;
+        let (begin, end) = info.data_offsets;
+
+        if begin > end || end > data.len() as u64 {
+            bail!(
+                "{} data_offsets [{}, {}] are outside of the {} data bytes",
+                name,
+                begin,
+                end,
+                data.len()
+            );
+        }
+
+        let element_type = element_type(&info.dtype);
+
+        if let Some(element_type) = element_type {
+            let expected = info.elements().saturating_mul(element_type.size() as u64);
+
+            if expected != end - begin {
+                bail!(
+                    "{} holds {} bytes but its {} elements of {} take {}",
+                    name,
+                    end - begin,
+                    info.elements(),
+                    info.dtype,
+                    expected
+                );
+            }
+        }
+
+        Ok(TensorData {
+            name,
+            dtype: info.dtype.clone(),
+            element_type,
+            shape: info.shape.clone(),
+            data: Cow::Borrowed(&data[begin as usize..end as usize]),
+        })
     }
 }
 
 impl Model for Safetensors {
-    fn summary<'a>(&'a self, filename: Option<&'a str>) -> Box<dyn Summary + 'a> {
+    fn summary<'a>(
+        &'a self,
+        filename: Option<&'a str>,
+        options: &SummaryOptions,
+    ) -> Box<dyn Summary + 'a> {
         let architecture = self
             .metadata
             .get("modelspec.architecture")
@@ -53,14 +358,159 @@ impl Model for Safetensors {
             .get("modelspec.implementation")
             .and_then(|v| v.as_str());
 
-        // let data_types =
+        let mut data_types: HashMap<&str, DataTypeTensors> = HashMap::new();
+
+        for info in self.tensors.values() {
+            let usage = data_types
+                .entry(&info.dtype)
+                .or_insert_with(|| DataTypeTensors {
+                    dtype: &info.dtype,
+                    tensors: 0,
+                    parameters: 0,
+                    bytes: 0,
+                });
+
+            usage.tensors += 1;
+            usage.parameters = usage.parameters.saturating_add(info.elements());
+            usage.bytes = usage.bytes.saturating_add(info.bytes());
+        }
+
+        let mut data_types: Vec<DataTypeTensors> = data_types.into_values().collect();
+        data_types.sort_by_key(|usage| (Reverse(usage.bytes), usage.dtype));
+
+        let mut shards: Vec<ShardEntry> = self
+            .shards
+            .iter()
+            .map(|shard| ShardEntry {
+                file: &shard.file,
+                tensors: 0,
+                parameters: 0,
+                bytes: shard.size,
+            })
+            .collect();
+
+        for info in self.tensors.values() {
+            let shard = &mut shards[info.shard];
+            shard.tensors += 1;
+            shard.parameters = shard.parameters.saturating_add(info.elements());
+        }
 
         Box::new(SafeTensorsSummary {
             filename,
             architecture,
             implementation,
             metadata: &self.metadata,
-            tensors: &self.tensors,
+            index: self.index.as_deref(),
+            shards,
+            parameters: data_types
+                .iter()
+                .fold(0u64, |acc, usage| acc.saturating_add(usage.parameters)),
+            bytes: data_types
+                .iter()
+                .fold(0u64, |acc, usage| acc.saturating_add(usage.bytes)),
+            data_types,
+            tensors: self
+                .tensors
+                .iter()
+                .map(|(name, info)| TensorEntry {
+                    name,
+                    dtype: &info.dtype,
+                    shape: &info.shape,
+                    elements: info.elements(),
+                    bytes: info.bytes(),
+                    data_offsets: info.data_offsets,
+                })
+                .collect(),
+            modules: modules::module_tree(&self.tensors),
+            module_depth: options.module_depth,
+            diagnostics: &self.findings,
         })
     }
+
+    fn check(&self) -> Option<Vec<Finding>> {
+        Some(self.findings.clone())
+    }
+
+    fn format_name(&self) -> &'static str {
+        "safetensors"
+    }
+
+    fn diff_sections(&self) -> Vec<DiffSection> {
+        let tensor_type = |info: &TensorInfo| {
+            let shape: Vec<String> = info.shape.iter().map(|d| d.to_string()).collect();
+            format!("{}[{}]", info.dtype, shape.join(","))
+        };
+
+        vec![
+            DiffSection::new(
+                "Tensors",
+                self.tensors
+                    .iter()
+                    .map(|(name, info)| (name.as_str(), tensor_type(info))),
+            ),
+            DiffSection::new(
+                "Metadata",
+                self.metadata.iter().map(|(key, value)| {
+                    let value = match value.as_str() {
+                        Some(s) => s.to_string(),
+                        None => value.to_string(),
+                    };
+
+                    (key.as_str(), value)
+                }),
+            ),
+        ]
+    }
+
+    fn tensor_names(&self) -> Vec<&str> {
+        self.tensors.keys().map(|name| name.as_str()).collect()
+    }
+
+    fn tensor_data(&self, name: &str) -> anyhow::Result<TensorData<'_>> {
+        let (name, info) = self
+            .tensors
+            .get_key_value(name)
+            .ok_or_else(|| anyhow!("Tensor {} does not exist", name))?;
+
+        self.tensor(name, info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    #[test]
+    fn summary_of_saturated_tensors() {
+        let header = br#"{"a":{"dtype":"U8","shape":[18446744073709551615],"data_offsets":[0,0]},"b":{"dtype":"U8","shape":[18446744073709551615],"data_offsets":[0,0]}}"#;
+        let path =
+            std::env::temp_dir().join(format!("ungraph-saturated-{}.safetensors", process::id()));
+        fs::write(
+            &path,
+            [&(header.len() as u64).to_le_bytes()[..], header].concat(),
+        )
+        .unwrap();
+
+        let model = Safetensors::open(&path);
+        fs::remove_file(&path).unwrap();
+        let model = model.unwrap();
+
+        let options = SummaryOptions {
+            dims: HashMap::new(),
+            top: 10,
+            module_depth: 2,
+            collapse_depth: None,
+        };
+        let summary = model.summary(None, &options);
+        let mut json = Vec::new();
+        summary.dump_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        assert_eq!(json["parameters"], u64::MAX);
+        assert_eq!(json["data_types"][0]["parameters"], u64::MAX);
+        assert_eq!(json["shards"][0]["parameters"], u64::MAX);
+        assert!(summary.to_string().contains("U8"));
+    }
 }
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use crate::safetensors::summary::TensorModule;
+use crate::safetensors::TensorInfo;
+
+/// Modules before aggregation, tensors are only those directly in the module
+struct Scope<'a> {
+    name: &'a str,
+    tensors: Vec<(&'a str, &'a TensorInfo)>,
+    children: Vec<Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn insert(&mut self, path: &[&'a str], tensor: &'a str, info: &'a TensorInfo) {
+        let Some((first, rest)) = path.split_first() else {
+            self.tensors.push((tensor, info));
+            return;
+        };
+
+        let position = match self.children.iter().position(|c| c.name == *first) {
+            Some(position) => position,
+            None => {
+                self.children.push(Scope {
+                    name: first,
+                    tensors: Vec::new(),
+                    children: Vec::new(),
+                });
+                self.children.len() - 1
+            }
+        };
+
+        self.children[position].insert(rest, tensor, info);
+    }
+
+    /// Relative names, dtypes and shapes of all tensors below the module,
+    /// equal for repeated modules such as the layers of a transformer
+    fn signature(&self) -> Vec<(String, &'a str, &'a [u64])> {
+        let mut signature: Vec<(String, &str, &[u64])> = self
+            .tensors
+            .iter()
+            .map(|(name, info)| (name.to_string(), info.dtype.as_str(), info.shape.as_slice()))
+            .collect();
+
+        for child in self.children.iter() {
+            signature.extend(
+                child
+                    .signature()
+                    .into_iter()
+                    .map(|(name, dtype, shape)| (format!("{}.{}", child.name, name), dtype, shape)),
+            );
+        }
+
+        signature.sort();
+        signature
+    }
+}
+
+/// Sum of element or byte counts, which saturate for crafted shapes
+fn saturating_sum(counts: impl Iterator<Item = u64>) -> u64 {
+    counts.fold(0, u64::saturating_add)
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn module(scope: &Scope, path: String) -> TensorModule {
+    let mut children: Vec<&Scope> = scope.children.iter().collect();
+    // Numeric siblings in index order, `layers.2` before `layers.10`
+    children.sort_by_key(|c| (c.name.parse::<u64>().ok(), c.name));
+
+    let mut modules: Vec<TensorModule> = Vec::new();
+    let mut run: Vec<&Scope> = Vec::new();
+
+    let flush = |run: &mut Vec<&Scope>, modules: &mut Vec<TensorModule>| {
+        let (Some(first), Some(last)) = (run.first(), run.last()) else {
+            return;
+        };
+
+        let mut collapsed = module(first, join(&path, first.name));
+
+        if run.len() > 1 {
+            let instances: Vec<TensorModule> = run[1..]
+                .iter()
+                .map(|scope| module(scope, join(&path, scope.name)))
+                .collect();
+
+            collapsed.name = format!("{}..{}", first.name, last.name);
+            collapsed.path = join(&path, &collapsed.name);
+            collapsed.repeat = run.len();
+            collapsed.tensors += instances.iter().map(|m| m.tensors).sum::<usize>();
+            collapsed.parameters = collapsed
+                .parameters
+                .saturating_add(saturating_sum(instances.iter().map(|m| m.parameters)));
+            collapsed.bytes = collapsed
+                .bytes
+                .saturating_add(saturating_sum(instances.iter().map(|m| m.bytes)));
+        }
+
+        modules.push(collapsed);
+        run.clear();
+    };
+
+    let index = |scope: &Scope| scope.name.parse::<u64>().ok();
+    let signatures: Vec<_> = children.iter().map(|c| c.signature()).collect();
+
+    for (i, child) in children.iter().enumerate() {
+        // Only consecutive indices with the same tensors are collapsed
+        let repeats = i > 0
+            && index(children[i - 1]).is_some_and(|previous| index(child) == Some(previous + 1))
+            && signatures[i - 1] == signatures[i];
+
+        if !repeats {
+            flush(&mut run, &mut modules);
+        }
+
+        run.push(child);
+    }
+
+    flush(&mut run, &mut modules);
+
+    TensorModule {
+        name: scope.name.to_string(),
+        tensors: scope.tensors.len() + modules.iter().map(|m| m.tensors).sum::<usize>(),
+        parameters: saturating_sum(
+            scope
+                .tensors
+                .iter()
+                .map(|(_, t)| t.elements())
+                .chain(modules.iter().map(|m| m.parameters)),
+        ),
+        bytes: saturating_sum(
+            scope
+                .tensors
+                .iter()
+                .map(|(_, t)| t.bytes())
+                .chain(modules.iter().map(|m| m.bytes)),
+        ),
+        path,
+        repeat: 1,
+        children: modules,
+    }
+}
+
+/// Modules named by the dotted tensor names, with runs of numbered modules
+/// holding the same tensors collapsed into one entry
+pub fn module_tree(tensors: &BTreeMap<String, TensorInfo>) -> TensorModule {
+    let mut root = Scope {
+        name: "",
+        tensors: Vec::new(),
+        children: Vec::new(),
+    };
+
+    for (name, info) in tensors.iter() {
+        let mut path: Vec<&str> = name.split('.').collect();
+        // The last component names the tensor itself
+        let tensor = path.pop().unwrap_or_default();
+        root.insert(&path, tensor, info);
+    }
+
+    module(&root, String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crafted_shapes_saturate_module_totals() {
+        let huge = TensorInfo {
+            dtype: "U8".to_string(),
+            shape: vec![u64::MAX],
+            data_offsets: (0, u64::MAX),
+            shard: 0,
+        };
+        let tensors: BTreeMap<String, TensorInfo> = ["layers.0.w", "layers.1.w", "head.w"]
+            .into_iter()
+            .map(|name| (name.to_string(), huge.clone()))
+            .collect();
+
+        let root = module_tree(&tensors);
+
+        assert_eq!(root.tensors, 3);
+        assert_eq!(root.parameters, u64::MAX);
+        assert_eq!(root.bytes, u64::MAX);
+        let layers = root.children.iter().find(|m| m.name == "layers").unwrap();
+        assert_eq!(layers.children[0].repeat, 2);
+        assert_eq!(layers.children[0].parameters, u64::MAX);
+    }
+}
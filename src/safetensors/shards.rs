@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::check::{Finding, Location, Severity};
+use crate::safetensors::{Header, ParsedFile, Safetensors, Shard, TensorInfo};
+
+/// Name Hugging Face gives the index of a sharded checkpoint
+const INDEX_FILE_NAME: &str = "model.safetensors.index.json";
+const INDEX_SUFFIX: &str = ".safetensors.index.json";
+
+/// `model.safetensors.index.json`, mapping tensor names to shard files
+#[derive(Deserialize)]
+struct Index {
+    #[serde(default)]
+    metadata: Header,
+    weight_map: BTreeMap<String, String>,
+}
+
+fn error(location: Location, message: String) -> Finding {
+    Finding {
+        severity: Severity::Error,
+        location,
+        message,
+    }
+}
+
+fn tensor_location(name: &str) -> Location {
+    Location::Tensor {
+        name: name.to_string(),
+    }
+}
+
+/// Whether a path names the index of a sharded checkpoint
+pub fn is_index(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(INDEX_SUFFIX))
+}
+
+/// Index file of a checkpoint directory, `model.safetensors.index.json` or
+/// else the only other index in it
+fn find_index(dir: &Path) -> anyhow::Result<PathBuf> {
+    let path = dir.join(INDEX_FILE_NAME);
+
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    let indexes: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Cannot read directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| is_index(path))
+        .collect();
+
+    match indexes.as_slice() {
+        [index] => Ok(index.clone()),
+        [] => bail!("{} holds no {} file", dir.display(), INDEX_FILE_NAME),
+        _ => bail!(
+            "{} holds {} safetensors indexes, pass one of them",
+            dir.display(),
+            indexes.len()
+        ),
+    }
+}
+
+impl Safetensors {
+    /// Load a checkpoint sharded across several files from its index or the
+    /// directory holding it.
+    ///
+    /// Shards that cannot be read and disagreements between the index and
+    /// the shards' headers are kept as findings.
+    pub fn from_index(path: &Path) -> anyhow::Result<Self> {
+        let index_path = if path.is_dir() {
+            find_index(path)?
+        } else {
+            path.to_path_buf()
+        };
+        let dir = index_path.parent().unwrap_or_else(|| Path::new(""));
+
+        let index: Index = serde_json::from_slice(
+            &fs::read(&index_path)
+                .with_context(|| format!("Cannot read {}", index_path.display()))?,
+        )
+        .with_context(|| format!("{} is not a valid index", index_path.display()))?;
+
+        let files: BTreeSet<&str> = index.weight_map.values().map(|f| f.as_str()).collect();
+
+        let mut metadata = None;
+        let mut tensors: BTreeMap<String, TensorInfo> = BTreeMap::new();
+        let mut shards: Vec<Shard> = Vec::new();
+        let mut findings = Vec::new();
+        let mut unreadable = BTreeSet::new();
+
+        for file in files {
+            let shard_location = || Location::Shard {
+                file: file.to_string(),
+            };
+
+            // Like external data of onnx models, shards must live next to the
+            // index
+            let relative = Path::new(file);
+            if !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                findings.push(error(
+                    shard_location(),
+                    "Shard must be relative to the index directory".to_string(),
+                ));
+                unreadable.insert(file);
+                continue;
+            }
+
+            let parsed = match ParsedFile::read(&dir.join(relative), file.to_string()) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    findings.push(error(
+                        shard_location(),
+                        format!("Cannot read shard: {:#}", err),
+                    ));
+                    unreadable.insert(file);
+                    continue;
+                }
+            };
+
+            let shard = shards.len();
+
+            findings.extend(parsed.findings.into_iter().map(|mut finding| {
+                if matches!(finding.location, Location::Model) {
+                    finding.location = shard_location();
+                }
+                finding
+            }));
+
+            for (name, mut info) in parsed.tensors {
+                if let Some(first) = tensors.get(&name) {
+                    findings.push(error(
+                        tensor_location(&name),
+                        format!(
+                            "Stored in both {} and {}, the first is used",
+                            shards[first.shard].file, file
+                        ),
+                    ));
+                    continue;
+                }
+
+                info.shard = shard;
+                tensors.insert(name, info);
+            }
+
+            metadata.get_or_insert(parsed.metadata);
+            shards.push(parsed.shard);
+        }
+
+        for (name, file) in index.weight_map.iter() {
+            match tensors.get(name) {
+                None if unreadable.contains(file.as_str()) => {}
+                None => findings.push(error(
+                    tensor_location(name),
+                    format!("Listed in the index for {} but stored in no shard", file),
+                )),
+                Some(info) if shards[info.shard].file != *file => findings.push(error(
+                    tensor_location(name),
+                    format!(
+                        "Listed in the index for {} but stored in {}",
+                        file, shards[info.shard].file
+                    ),
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for (name, info) in tensors.iter() {
+            if !index.weight_map.contains_key(name) {
+                findings.push(error(
+                    tensor_location(name),
+                    format!(
+                        "Stored in {} but not listed in the index",
+                        shards[info.shard].file
+                    ),
+                ));
+            }
+        }
+
+        let total_size = index.metadata.get("total_size").and_then(|v| v.as_u64());
+        let bytes = tensors
+            .values()
+            .fold(0u64, |acc, info| acc.saturating_add(info.bytes()));
+
+        if let Some(total_size) = total_size.filter(|size| *size != bytes) {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                location: Location::Model,
+                message: format!(
+                    "Index total_size is {} but the tensors hold {} bytes",
+                    total_size, bytes
+                ),
+            });
+        }
+
+        Ok(Safetensors {
+            metadata: metadata.unwrap_or_default(),
+            tensors,
+            shards,
+            index: Some(index_path.display().to_string()),
+            findings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Safetensors file of `U8` tensors with the given lengths
+    fn write_shard(path: &Path, tensors: &[(&str, u64)]) {
+        let mut header = serde_json::Map::new();
+        let mut offset = 0;
+
+        for (name, len) in tensors {
+            header.insert(
+                name.to_string(),
+                json!({ "dtype": "U8", "shape": [len], "data_offsets": [offset, offset + len] }),
+            );
+            offset += len;
+        }
+
+        let header = serde_json::to_vec(&header).unwrap();
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header);
+        bytes.extend(vec![0; offset as usize]);
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn checkpoint_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ungraph-{}-{}", test, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn findings(model: &Safetensors) -> Vec<String> {
+        model
+            .findings
+            .iter()
+            .map(|finding| format!("{}: {}", finding.location, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn consistent_index_loads_all_shards() {
+        let dir = checkpoint_dir("shards-valid");
+        write_shard(&dir.join("a.safetensors"), &[("x", 4), ("y", 2)]);
+        write_shard(&dir.join("b.safetensors"), &[("z", 8)]);
+        let index = json!({
+            "metadata": { "total_size": 14 },
+            "weight_map": { "x": "a.safetensors", "y": "a.safetensors", "z": "b.safetensors" },
+        });
+        fs::write(dir.join(INDEX_FILE_NAME), index.to_string()).unwrap();
+
+        let model = Safetensors::from_index(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let model = model.unwrap();
+
+        assert!(model.findings.is_empty(), "{:?}", findings(&model));
+        assert_eq!(model.shards.len(), 2);
+        assert_eq!(model.tensors["z"].shard, 1);
+    }
+
+    #[test]
+    fn disagreements_with_the_index() {
+        let dir = checkpoint_dir("shards-invalid");
+        write_shard(
+            &dir.join("a.safetensors"),
+            &[("x", 4), ("moved", 2), ("extra", 1)],
+        );
+        write_shard(&dir.join("b.safetensors"), &[("x", 4)]);
+        let index = json!({
+            "metadata": { "total_size": 100 },
+            "weight_map": {
+                "x": "a.safetensors",
+                "moved": "b.safetensors",
+                "missing": "b.safetensors",
+                "lost": "c.safetensors",
+            },
+        });
+        let index_path = dir.join("custom.safetensors.index.json");
+        fs::write(&index_path, index.to_string()).unwrap();
+
+        let model = Safetensors::from_index(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let model = model.unwrap();
+
+        let findings = findings(&model);
+        assert_eq!(findings.len(), 6, "{:?}", findings);
+        assert_eq!(
+            findings[0],
+            "tensor x: Stored in both a.safetensors and b.safetensors, the first is used"
+        );
+        assert!(findings[1].starts_with("shard c.safetensors: Cannot read shard"));
+        assert_eq!(
+            findings[2..],
+            [
+                "tensor missing: Listed in the index for b.safetensors but stored in no shard",
+                "tensor moved: Listed in the index for b.safetensors but stored in a.safetensors",
+                "tensor extra: Stored in a.safetensors but not listed in the index",
+                "model: Index total_size is 100 but the tensors hold 7 bytes",
+            ]
+        );
+    }
+
+    #[test]
+    fn shards_outside_the_index_directory() {
+        let dir = checkpoint_dir("shards-outside");
+        let outside = dir.join("outside.safetensors");
+        write_shard(&outside, &[("x", 4)]);
+        let index_dir = dir.join("model");
+        fs::create_dir_all(&index_dir).unwrap();
+        let index = json!({
+            "weight_map": {
+                "x": "../outside.safetensors",
+                "y": outside.display().to_string(),
+            },
+        });
+        fs::write(index_dir.join(INDEX_FILE_NAME), index.to_string()).unwrap();
+
+        let model = Safetensors::from_index(&index_dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let model = model.unwrap();
+
+        assert!(model.tensors.is_empty());
+        assert_eq!(
+            findings(&model),
+            [
+                "shard ../outside.safetensors: Shard must be relative to the index directory"
+                    .to_string(),
+                format!(
+                    "shard {}: Shard must be relative to the index directory",
+                    outside.display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn directory_without_a_single_index() {
+        let dir = checkpoint_dir("shards-none");
+        let none = Safetensors::from_index(&dir).err().unwrap().to_string();
+
+        fs::write(dir.join("a.safetensors.index.json"), "{}").unwrap();
+        fs::write(dir.join("b.safetensors.index.json"), "{}").unwrap();
+        let several = Safetensors::from_index(&dir).err().unwrap().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(none.contains("holds no model.safetensors.index.json file"));
+        assert!(several.contains("holds 2 safetensors indexes"));
+    }
+}
@@ -3,8 +3,96 @@ use std::{fmt, io};
 use console::Style;
 use serde::Serialize;
 
+use crate::check::Finding;
 use crate::safetensors::Header;
-use crate::summary::Summary;
+use crate::summary::{ByteSize, Count, Summary};
+
+/// Tensors of one dtype
+#[derive(Serialize)]
+pub struct DataTypeTensors<'a> {
+    pub dtype: &'a str,
+    pub tensors: usize,
+    pub parameters: u64,
+    pub bytes: u64,
+}
+
+/// File of a sharded checkpoint
+#[derive(Serialize)]
+pub struct ShardEntry<'a> {
+    pub file: &'a str,
+    pub tensors: usize,
+    pub parameters: u64,
+    /// Size of the whole file
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct TensorEntry<'a> {
+    pub name: &'a str,
+    pub dtype: &'a str,
+    pub shape: &'a [u64],
+    pub elements: u64,
+    pub bytes: u64,
+    pub data_offsets: (u64, u64),
+}
+
+impl<'a> TensorEntry<'a> {
+    fn shape(&self) -> String {
+        let dims: Vec<String> = self.shape.iter().map(|d| d.to_string()).collect();
+        format!("[{}]", dims.join(","))
+    }
+}
+
+/// Module of the dotted tensor names, with everything below it aggregated
+#[derive(Serialize)]
+pub struct TensorModule {
+    pub name: String,
+    pub path: String,
+    /// Numbered modules with the same tensors merged into this one, the
+    /// children are those of the first
+    pub repeat: usize,
+    pub tensors: usize,
+    pub parameters: u64,
+    pub bytes: u64,
+    pub children: Vec<TensorModule>,
+}
+
+impl TensorModule {
+    fn fmt_tree(&self, f: &mut fmt::Formatter<'_>, level: usize, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}", "", self.name, indent = 4 * level)?;
+
+        if self.repeat > 1 {
+            write!(f, " ×{}", self.repeat)?;
+        }
+
+        let repeat = self.repeat as u64;
+        write!(
+            f,
+            ": {} tensors, {} params ({})",
+            self.tensors / self.repeat,
+            Count(self.parameters / repeat),
+            ByteSize(self.bytes / repeat)
+        )?;
+
+        if self.repeat > 1 {
+            write!(f, " each")?;
+        }
+
+        if level == depth && !self.children.is_empty() {
+            write!(f, " (+{} submodules)", self.children.len())?;
+        }
+
+        writeln!(f)?;
+
+        if level < depth {
+            for child in self.children.iter() {
+                child.fmt_tree(f, level + 1, depth)?;
+            }
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Serialize)]
 pub struct SafeTensorsSummary<'a> {
@@ -12,7 +100,21 @@ pub struct SafeTensorsSummary<'a> {
     pub(crate) architecture: Option<&'a str>,
     pub(crate) implementation: Option<&'a str>,
     pub(crate) metadata: &'a Header,
-    pub(crate) tensors: &'a Header,
+    /// Index of a sharded checkpoint
+    pub(crate) index: Option<&'a str>,
+    pub(crate) shards: Vec<ShardEntry<'a>>,
+    pub(crate) parameters: u64,
+    pub(crate) bytes: u64,
+    /// Largest first
+    pub(crate) data_types: Vec<DataTypeTensors<'a>>,
+    /// Sorted by name
+    pub(crate) tensors: Vec<TensorEntry<'a>>,
+    pub(crate) modules: TensorModule,
+    /// Levels of the module tree shown in text output
+    #[serde(skip)]
+    pub(crate) module_depth: usize,
+    /// Problems of the header and its agreement with the data
+    pub(crate) diagnostics: &'a [Finding],
 }
 
 impl<'a> Summary for SafeTensorsSummary<'a> {
@@ -21,6 +123,61 @@ impl<'a> Summary for SafeTensorsSummary<'a> {
     }
 }
 
+impl<'a> SafeTensorsSummary<'a> {
+    fn fmt_table(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+
+        let shapes: Vec<String> = self.tensors.iter().map(|t| t.shape()).collect();
+        let sizes: Vec<String> = self
+            .tensors
+            .iter()
+            .map(|t| ByteSize(t.bytes).to_string())
+            .collect();
+        let name_width = self
+            .tensors
+            .iter()
+            .map(|t| t.name.len())
+            .chain([4])
+            .max()
+            .unwrap_or(0);
+        let dtype_width = self
+            .tensors
+            .iter()
+            .map(|t| t.dtype.len())
+            .chain([5])
+            .max()
+            .unwrap_or(0);
+        let shape_width = shapes.iter().map(|s| s.len()).chain([5]).max().unwrap_or(0);
+        let elements_width = self
+            .tensors
+            .iter()
+            .map(|t| t.elements.to_string().len())
+            .chain([8])
+            .max()
+            .unwrap_or(0);
+        let bytes_width = sizes.iter().map(|s| s.len()).chain([5]).max().unwrap_or(0);
+
+        writeln!(
+            f,
+            "{}",
+            bold.apply_to(format!(
+                "{:name_width$}  {:dtype_width$}  {:shape_width$}  {:>elements_width$}  {:>bytes_width$}",
+                "Name", "Dtype", "Shape", "Elements", "Bytes",
+            ))
+        )?;
+
+        for ((tensor, shape), size) in self.tensors.iter().zip(shapes.iter()).zip(sizes.iter()) {
+            writeln!(
+                f,
+                "{:name_width$}  {:dtype_width$}  {:shape_width$}  {:>elements_width$}  {:>bytes_width$}",
+                tensor.name, tensor.dtype, shape, tensor.elements, size,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<'a> fmt::Display for SafeTensorsSummary<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let name = self.filename.unwrap_or("<NO FILENAME>");
@@ -36,6 +193,76 @@ impl<'a> fmt::Display for SafeTensorsSummary<'a> {
             writeln!(f, "Implementation: {}", implementation)?;
         }
 
+        if self.architecture.is_some() || self.implementation.is_some() {
+            writeln!(f)?;
+        }
+
+        writeln!(
+            f,
+            "{} {} tensors, {} parameters ({})",
+            bold.apply_to("Tensors:"),
+            self.tensors.len(),
+            self.parameters,
+            ByteSize(self.bytes)
+        )?;
+
+        for usage in self.data_types.iter() {
+            writeln!(
+                f,
+                "    {}: {} tensors, {} parameters ({})",
+                usage.dtype,
+                usage.tensors,
+                usage.parameters,
+                ByteSize(usage.bytes)
+            )?;
+        }
+
+        if let Some(index) = self.index {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{} {} files ({}) from {}",
+                bold.apply_to("Shards:"),
+                self.shards.len(),
+                ByteSize(self.shards.iter().map(|s| s.bytes).sum()),
+                index
+            )?;
+        }
+
+        for shard in self.shards.iter().filter(|_| self.index.is_some()) {
+            writeln!(
+                f,
+                "    {}: {} tensors, {} parameters ({})",
+                shard.file,
+                shard.tensors,
+                shard.parameters,
+                ByteSize(shard.bytes)
+            )?;
+        }
+
+        if !self.modules.children.is_empty() && self.module_depth > 0 {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Modules:"))?;
+
+            for module in self.modules.children.iter() {
+                module.fmt_tree(f, 1, self.module_depth)?;
+            }
+        }
+
+        if !self.tensors.is_empty() {
+            writeln!(f)?;
+            self.fmt_table(f)?;
+        }
+
+        if !self.diagnostics.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", bold.apply_to("Diagnostics:"))?;
+        }
+
+        for diagnostic in self.diagnostics.iter() {
+            writeln!(f, "    {}", diagnostic)?;
+        }
+
         Ok(())
     }
 }
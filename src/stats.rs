@@ -0,0 +1,408 @@
+use std::cmp::Ordering;
+use std::{fmt, io};
+
+use clap::ValueEnum;
+use console::Style;
+use serde::Serialize;
+
+use crate::model::Model;
+use crate::summary::{Count, Summary};
+use crate::tensor::TensorData;
+
+/// Value statistics of a single tensor.
+///
+/// Min, max, mean and std only cover finite elements and are `None` when a
+/// tensor has none.
+#[derive(Serialize, Debug, Clone)]
+pub struct TensorStats {
+    pub name: String,
+    pub dtype: String,
+    pub shape: Vec<u64>,
+    pub count: u64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub std: Option<f64>,
+    pub absmax: Option<f64>,
+    pub nan: u64,
+    pub inf: u64,
+    /// Fraction of elements that are exactly zero
+    pub zeros: f64,
+}
+
+/// Running statistics, using Welford's algorithm for a stable variance
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    finite: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+    nan: u64,
+    inf: u64,
+    zeros: u64,
+}
+
+impl Accumulator {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+
+        if value.is_nan() {
+            self.nan += 1;
+            return;
+        }
+
+        if value.is_infinite() {
+            self.inf += 1;
+            return;
+        }
+
+        if value == 0.0 {
+            self.zeros += 1;
+        }
+
+        if self.finite == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        self.finite += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.finite as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+}
+
+impl TensorStats {
+    /// Statistics of a tensor, `None` if its data type cannot be decoded to
+    /// real numbers
+    pub fn new(tensor: &TensorData) -> Option<Self> {
+        let element_type = tensor.element_type.filter(|t| !t.is_complex())?;
+        let mut acc = Accumulator::default();
+
+        for value in element_type.values(&tensor.data) {
+            acc.add(value);
+        }
+
+        let finite = |value: f64| (acc.finite > 0).then_some(value);
+
+        Some(TensorStats {
+            name: tensor.name.to_string(),
+            dtype: tensor.dtype.clone(),
+            shape: tensor.shape.clone(),
+            count: acc.count,
+            min: finite(acc.min),
+            max: finite(acc.max),
+            mean: finite(acc.mean),
+            std: finite((acc.m2 / acc.finite as f64).sqrt()),
+            absmax: finite(acc.min.abs().max(acc.max.abs())),
+            nan: acc.nan,
+            inf: acc.inf,
+            zeros: if acc.count > 0 {
+                acc.zeros as f64 / acc.count as f64
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsSort {
+    Name,
+    Size,
+    Min,
+    Max,
+    Mean,
+    Std,
+    Absmax,
+    Nan,
+    Inf,
+    Zeros,
+}
+
+/// Which tensors are listed, tensors must pass every filter that is set
+#[derive(Debug, Clone, Default)]
+pub struct StatsFilter {
+    pub absmax_above: Option<f64>,
+    pub std_below: Option<f64>,
+    pub zeros_above: Option<f64>,
+    pub non_finite: bool,
+}
+
+impl StatsFilter {
+    fn matches(&self, stats: &TensorStats) -> bool {
+        let above = |value: Option<f64>, threshold: Option<f64>| match threshold {
+            Some(threshold) => value.is_some_and(|v| v > threshold),
+            None => true,
+        };
+
+        above(stats.absmax, self.absmax_above)
+            && above(Some(stats.zeros), self.zeros_above)
+            && self
+                .std_below
+                .is_none_or(|threshold| stats.std.is_some_and(|std| std < threshold))
+            && (!self.non_finite || stats.nan + stats.inf > 0)
+    }
+}
+
+fn compare(a: &TensorStats, b: &TensorStats, sort: StatsSort) -> Ordering {
+    // Tensors without finite values sort last
+    let value = |a: Option<f64>, b: Option<f64>| match (a, b) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    };
+
+    match sort {
+        StatsSort::Name => a.name.cmp(&b.name),
+        StatsSort::Size => b.count.cmp(&a.count),
+        StatsSort::Min => value(a.min, b.min),
+        StatsSort::Max => value(a.max, b.max),
+        StatsSort::Mean => value(a.mean, b.mean),
+        StatsSort::Std => value(a.std, b.std),
+        StatsSort::Absmax => value(a.absmax, b.absmax),
+        StatsSort::Nan => b.nan.cmp(&a.nan),
+        StatsSort::Inf => b.inf.cmp(&a.inf),
+        StatsSort::Zeros => b.zeros.total_cmp(&a.zeros),
+    }
+}
+
+/// Tensor whose data could not be read
+#[derive(Serialize, Debug, Clone)]
+pub struct TensorError {
+    pub name: String,
+    pub error: String,
+}
+
+/// Statistics of the tensors matching a filter
+#[derive(Serialize)]
+pub struct StatsSummary<'a> {
+    pub filename: Option<&'a str>,
+    pub sort: StatsSort,
+    /// Number of tensors with statistics, before filtering
+    pub total: usize,
+    /// Tensors whose data type cannot be decoded to real numbers
+    pub skipped: Vec<String>,
+    /// Tensors whose data is corrupt or missing, such as a size that
+    /// disagrees with the shape
+    pub errors: Vec<TensorError>,
+    pub tensors: Vec<TensorStats>,
+}
+
+impl<'a> StatsSummary<'a> {
+    /// Sorted by `sort`, name ascending and everything else descending, and
+    /// truncated to `limit` entries
+    pub fn new(
+        filename: Option<&'a str>,
+        model: &dyn Model,
+        filter: &StatsFilter,
+        sort: StatsSort,
+        limit: Option<usize>,
+    ) -> Self {
+        let mut stats = Vec::new();
+        let mut skipped = Vec::new();
+        let mut errors = Vec::new();
+        let mut total = 0;
+
+        for name in model.tensor_names() {
+            let tensor = match model.tensor_data(name) {
+                Ok(tensor) => tensor,
+                Err(err) => {
+                    errors.push(TensorError {
+                        name: name.to_string(),
+                        error: format!("{:#}", err),
+                    });
+                    continue;
+                }
+            };
+
+            match TensorStats::new(&tensor) {
+                Some(tensor_stats) => {
+                    total += 1;
+
+                    if filter.matches(&tensor_stats) {
+                        stats.push(tensor_stats);
+                    }
+                }
+                None => skipped.push(tensor.name.to_string()),
+            }
+        }
+
+        stats.sort_by(|a, b| compare(a, b, sort));
+
+        if let Some(limit) = limit {
+            stats.truncate(limit);
+        }
+
+        StatsSummary {
+            filename,
+            sort,
+            total,
+            skipped,
+            errors,
+            tensors: stats,
+        }
+    }
+}
+
+impl<'a> Summary for StatsSummary<'a> {
+    fn dump_json(&self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, &self)?)
+    }
+}
+
+/// Compact number that keeps small and large magnitudes readable
+fn number(value: Option<f64>) -> String {
+    match value {
+        None => "-".to_string(),
+        Some(0.0) => "0".to_string(),
+        Some(v) if (1e-3..1e5).contains(&v.abs()) => format!("{:.4}", v),
+        Some(v) => format!("{:.3e}", v),
+    }
+}
+
+impl<'a> fmt::Display for StatsSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bold = Style::new().bold();
+        let warning = Style::new().yellow();
+
+        writeln!(
+            f,
+            "{} {}",
+            bold.apply_to("Tensor statistics:"),
+            self.filename.unwrap_or("<NO FILENAME>")
+        )?;
+        writeln!(f)?;
+
+        if self.tensors.is_empty() {
+            writeln!(f, "No tensors match")?;
+        } else {
+            let types: Vec<String> = self
+                .tensors
+                .iter()
+                .map(|t| {
+                    let shape: Vec<String> = t.shape.iter().map(|d| d.to_string()).collect();
+                    format!("{}[{}]", t.dtype, shape.join(","))
+                })
+                .collect();
+            let name_width = self
+                .tensors
+                .iter()
+                .map(|t| t.name.len())
+                .chain([4])
+                .max()
+                .unwrap_or(0);
+            let type_width = types.iter().map(|t| t.len()).chain([4]).max().unwrap_or(0);
+
+            writeln!(
+                f,
+                "{}",
+                bold.apply_to(format!(
+                    "{:name_width$}  {:type_width$}  {:>7}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}  {:>6}  {:>5}  {:>5}",
+                    "Name", "Type", "Count", "Min", "Max", "Mean", "Std", "Absmax", "Zeros", "NaN", "Inf",
+                ))
+            )?;
+
+            for (stats, ty) in self.tensors.iter().zip(types.iter()) {
+                let line = format!(
+                    "{:name_width$}  {:type_width$}  {:>7}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}  {:>5.1}%  {:>5}  {:>5}",
+                    stats.name,
+                    ty,
+                    Count(stats.count).to_string(),
+                    number(stats.min),
+                    number(stats.max),
+                    number(stats.mean),
+                    number(stats.std),
+                    number(stats.absmax),
+                    stats.zeros * 100.0,
+                    stats.nan,
+                    stats.inf,
+                );
+
+                if stats.nan + stats.inf > 0 {
+                    writeln!(f, "{}", warning.apply_to(line))?;
+                } else {
+                    writeln!(f, "{}", line)?;
+                }
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{} of {} tensors shown, sorted by {}",
+            self.tensors.len(),
+            self.total,
+            self.sort.to_possible_value().unwrap().get_name()
+        )?;
+
+        if !self.skipped.is_empty() {
+            writeln!(
+                f,
+                "Skipped {} tensors without real numeric data: {}",
+                self.skipped.len(),
+                self.skipped.join(", ")
+            )?;
+        }
+
+        if !self.errors.is_empty() {
+            let error = Style::new().red();
+
+            writeln!(
+                f,
+                "{}",
+                error.apply_to(format!("Failed to read {} tensors:", self.errors.len()))
+            )?;
+
+            for tensor in self.errors.iter() {
+                writeln!(f, "  {}: {}", tensor.name, tensor.error)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onnx::fixtures::{load, tensor};
+    use crate::onnx::onnx_proto::tensor_proto::DataType;
+    use crate::onnx::onnx_proto::{GraphProto, TensorProto};
+
+    fn float_tensor(name: &str, dims: &[i64], values: &[f32]) -> TensorProto {
+        TensorProto {
+            float_data: values.to_vec(),
+            ..tensor(name, DataType::Float, dims)
+        }
+    }
+
+    #[test]
+    fn corrupt_tensors_are_reported_and_skipped() {
+        let model = load(GraphProto {
+            initializer: vec![
+                float_tensor("a", &[2], &[1.0, -3.0]),
+                float_tensor("w", &[4], &[1.0]),
+                float_tensor("huge", &[i64::MAX, i64::MAX], &[]),
+                float_tensor("z", &[1], &[0.0]),
+            ],
+            ..Default::default()
+        });
+
+        let summary =
+            StatsSummary::new(None, &model, &StatsFilter::default(), StatsSort::Name, None);
+
+        let names: Vec<&str> = summary.tensors.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "z"]);
+        assert_eq!(summary.tensors[0].absmax, Some(3.0));
+
+        let errors: Vec<&str> = summary.errors.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(errors, vec!["w", "huge"]);
+        assert!(summary.errors[0].error.contains("4 bytes"));
+    }
+}
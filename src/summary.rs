@@ -1,5 +1,70 @@
+use std::collections::HashMap;
 use std::{fmt, io};
 
 pub trait Summary: fmt::Display {
     fn dump_json(&self, writer: &mut dyn io::Write) -> anyhow::Result<()>;
 }
+
+/// Options shared by the summaries of all model formats
+#[derive(Debug, Clone)]
+pub struct SummaryOptions {
+    /// Sizes bound to symbolic dimensions
+    pub dims: HashMap<String, u64>,
+    /// Number of entries shown in "largest" and "top" listings
+    pub top: usize,
+    /// Levels of module trees shown in text summaries
+    pub module_depth: usize,
+    /// Draw the modules at this depth as single vertices in graph exports
+    pub collapse_depth: Option<usize>,
+}
+
+/// Byte count displayed with a binary unit suffix
+#[derive(Debug, Copy, Clone)]
+pub struct ByteSize(pub u64);
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = UNITS[0];
+
+        for next_unit in UNITS[1..].iter() {
+            if size < 1024.0 {
+                break;
+            }
+
+            size /= 1024.0;
+            unit = next_unit;
+        }
+
+        write!(f, "{:.1} {}", size, unit)
+    }
+}
+
+/// Large count displayed with a decimal unit suffix, e.g. `1.2G`
+#[derive(Debug, Copy, Clone)]
+pub struct Count(pub u64);
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(u64, &str); 4] = [
+            (1_000_000_000_000, "T"),
+            (1_000_000_000, "G"),
+            (1_000_000, "M"),
+            (1_000, "K"),
+        ];
+
+        for (scale, unit) in UNITS {
+            if self.0 >= scale {
+                return write!(f, "{:.1}{}", self.0 as f64 / scale as f64, unit);
+            }
+        }
+
+        write!(f, "{}", self.0)
+    }
+}
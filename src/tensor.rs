@@ -0,0 +1,146 @@
+use std::borrow::Cow;
+
+use half::{bf16, f16};
+
+/// Element type of raw little-endian tensor data, shared by all formats
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ElementType {
+    F64,
+    F32,
+    F16,
+    BF16,
+    F8E4M3FN,
+    F8E4M3FNUZ,
+    F8E5M2,
+    F8E5M2FNUZ,
+    I64,
+    I32,
+    I16,
+    I8,
+    U64,
+    U32,
+    U16,
+    U8,
+    Bool,
+    /// Pair of float32 real and imaginary parts
+    C64,
+    /// Pair of float64 real and imaginary parts
+    C128,
+}
+
+impl ElementType {
+    pub fn size(self) -> usize {
+        match self {
+            ElementType::C128 => 16,
+            ElementType::F64 | ElementType::C64 | ElementType::I64 | ElementType::U64 => 8,
+            ElementType::F32 | ElementType::I32 | ElementType::U32 => 4,
+            ElementType::F16 | ElementType::BF16 | ElementType::I16 | ElementType::U16 => 2,
+            ElementType::F8E4M3FN
+            | ElementType::F8E4M3FNUZ
+            | ElementType::F8E5M2
+            | ElementType::F8E5M2FNUZ
+            | ElementType::I8
+            | ElementType::U8
+            | ElementType::Bool => 1,
+        }
+    }
+
+    pub fn is_complex(self) -> bool {
+        matches!(self, ElementType::C64 | ElementType::C128)
+    }
+
+    /// Size of a single decoded value, a part of a complex element
+    fn value_size(self) -> usize {
+        match self {
+            ElementType::C64 => 4,
+            ElementType::C128 => 8,
+            _ => self.size(),
+        }
+    }
+
+    /// Decode a single value, `bytes` must hold exactly `value_size()` bytes
+    fn decode(self, bytes: &[u8]) -> f64 {
+        macro_rules! le {
+            ($t:ty) => {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            };
+        }
+
+        match self {
+            ElementType::F64 | ElementType::C128 => le!(f64),
+            ElementType::F32 | ElementType::C64 => le!(f32) as f64,
+            ElementType::F16 => f16::from_bits(le!(u16)).to_f64(),
+            ElementType::BF16 => bf16::from_bits(le!(u16)).to_f64(),
+            ElementType::F8E4M3FN => f8_to_f64(bytes[0], 4, 7, F8Special::Fn),
+            ElementType::F8E4M3FNUZ => f8_to_f64(bytes[0], 4, 8, F8Special::Fnuz),
+            ElementType::F8E5M2 => f8_to_f64(bytes[0], 5, 15, F8Special::Ieee),
+            ElementType::F8E5M2FNUZ => f8_to_f64(bytes[0], 5, 16, F8Special::Fnuz),
+            ElementType::I64 => le!(i64) as f64,
+            ElementType::I32 => le!(i32) as f64,
+            ElementType::I16 => le!(i16) as f64,
+            ElementType::I8 => bytes[0] as i8 as f64,
+            ElementType::U64 => le!(u64) as f64,
+            ElementType::U32 => le!(u32) as f64,
+            ElementType::U16 => le!(u16) as f64,
+            ElementType::U8 | ElementType::Bool => bytes[0] as f64,
+        }
+    }
+
+    /// Elements of little-endian data as `f64`, trailing partial elements
+    /// are ignored. Complex elements yield their real and imaginary parts
+    /// in turn
+    pub fn values(self, data: &[u8]) -> impl Iterator<Item = f64> + '_ {
+        data.chunks_exact(self.value_size())
+            .map(move |bytes| self.decode(bytes))
+    }
+}
+
+/// How a float8 format encodes infinities and NaN
+#[derive(Copy, Clone)]
+enum F8Special {
+    /// Infinities and NaNs like IEEE 754
+    Ieee,
+    /// No infinities, NaN has all exponent and mantissa bits set
+    Fn,
+    /// No infinities or negative zero, NaN is the negative zero pattern
+    Fnuz,
+}
+
+fn f8_to_f64(bits: u8, exponent_bits: u32, bias: i32, special: F8Special) -> f64 {
+    let mantissa_bits = 7 - exponent_bits;
+    let sign = if bits & 0x80 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits & 0x7f) >> mantissa_bits) as i32;
+    let mantissa = (bits & ((1 << mantissa_bits) - 1)) as f64;
+    let max_exponent = (1 << exponent_bits) - 1;
+    let max_mantissa = ((1 << mantissa_bits) - 1) as f64;
+
+    match special {
+        F8Special::Fnuz if bits == 0x80 => return f64::NAN,
+        F8Special::Fn if exponent == max_exponent && mantissa == max_mantissa => return f64::NAN,
+        F8Special::Ieee if exponent == max_exponent && mantissa == 0.0 => {
+            return sign * f64::INFINITY
+        }
+        F8Special::Ieee if exponent == max_exponent => return f64::NAN,
+        _ => {}
+    }
+
+    let scale = (1 << mantissa_bits) as f64;
+
+    if exponent == 0 {
+        sign * (mantissa / scale) * 2f64.powi(1 - bias)
+    } else {
+        sign * (1.0 + mantissa / scale) * 2f64.powi(exponent - bias)
+    }
+}
+
+/// Raw data of a named tensor held by a model
+pub struct TensorData<'a> {
+    pub name: &'a str,
+    /// Data type as the model format spells it
+    pub dtype: String,
+    /// `None` when the data type cannot be decoded to numbers
+    pub element_type: Option<ElementType>,
+    pub shape: Vec<u64>,
+    /// Little-endian element data
+    pub data: Cow<'a, [u8]>,
+}